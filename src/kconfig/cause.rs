@@ -0,0 +1,181 @@
+use super::{
+    Engine,
+    Expr,
+    OptionType,
+    Reverse,
+    SymbolInfo,
+    Tristate,
+    Value,
+    eval_opt,
+    format_number,
+    parse_number,
+};
+
+use std::fmt;
+
+// One reason a symbol has the value the engine gave it
+#[derive(Debug, Clone)]
+pub enum Cause<'t, 'a> {
+    // The user's value, from `.config` or an answer
+    User(Value),
+
+    // The first `default` whose condition holds, or none of them
+    Default {
+        value:     &'t Expr<'a>,
+        condition: Option<&'t Expr<'a>>,
+    },
+    NoDefault,
+    Selected {
+        by:    &'a str,
+        value: Tristate,
+    },
+    Implied {
+        by:    &'a str,
+        value: Tristate,
+    },
+
+    // A term of the dependencies of a prompt that keeps the symbol invisible, or caps how high it
+    // can be set
+    Dependency {
+        expr:  &'t Expr<'a>,
+        value: Tristate,
+    },
+    MenuHidden,
+    NoPrompt,
+
+    // The value of a choice that is `y`, if any
+    Choice(Option<&'a str>),
+
+    // An int or hex value that was moved into the active range
+    Range {
+        min: String,
+        max: String,
+    },
+}
+
+impl fmt::Display for Cause<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cause::User(value)                  => write!(f, "set to {} by the user", value.as_str()),
+            Cause::Default { value, condition } => match condition {
+                Some(condition) => write!(f, "default {} if {}", value, condition),
+                None            => write!(f, "default {}", value),
+            },
+            Cause::NoDefault                    => write!(f, "no default applies"),
+            Cause::Selected { by, value }       => write!(f, "selected by {} ({})", by, value.name()),
+            Cause::Implied { by, value }        => write!(f, "implied by {} ({})", by, value.name()),
+            Cause::Dependency { expr, value }   => write!(f, "depends on {}, which is {}", expr, value.name()),
+            Cause::MenuHidden                   => write!(f, "inside a menu whose `visible if` does not hold"),
+            Cause::NoPrompt                     => write!(f, "has no prompt"),
+            Cause::Choice(Some(selected))       => write!(f, "{} is selected in its choice", selected),
+            Cause::Choice(None)                 => write!(f, "nothing is selected in its choice"),
+            Cause::Range { min, max }           => write!(f, "clamped to the range {} to {}", min, max),
+        }
+    }
+}
+
+// Why the symbol has its current value, following the steps of the engine: what limits the
+// prompt, then the user's value or else the default and `imply`, then `select` and `range`
+pub fn causes<'t, 'a>(engine: &Engine<'t, '_, 'a>, name: &str) -> Vec<Cause<'t, 'a>> {
+    let Some(info) = engine.symbols.get(name) else {
+        return vec![];
+    };
+    let Some(option_type) = info.option_type else {
+        return vec![];
+    };
+    let tristate = matches!(option_type, OptionType::Bool | OptionType::Tristate);
+    let visible = engine.visibility(name);
+    let mut causes = vec![];
+
+    // Only a limit that matters is shown, any cap for a bool or tristate and full invisibility
+    // for strings and numbers
+    if visible == Tristate::No || (tristate && visible < Tristate::Yes) {
+        causes.extend(prompt_limits(engine, info, Tristate::Yes));
+    }
+
+    let user = engine.user_value(name).filter(|_| visible != Tristate::No);
+    if let Some(choice) = engine.choice_of(info) {
+        let selection = engine.selection(choice);
+        if visible == Tristate::Yes && selection.mode == Tristate::Yes {
+            if let Some(user) = user {
+                causes.push(Cause::User(user.clone()));
+            }
+            causes.push(Cause::Choice(selection.selected));
+            return causes;
+        }
+    }
+
+    let mut text = None;
+    match user {
+        Some(user) => {
+            causes.push(Cause::User(user.clone()));
+            text = Some(user.as_str().to_string());
+        },
+        None => {
+            match engine.active_default(info) {
+                Some((default, _)) => {
+                    causes.push(Cause::Default {
+                        value:     &default.value.expression,
+                        condition: default.value.condition.as_ref(),
+                    });
+                    text = Some(engine.default_text(default));
+                },
+                None => causes.push(Cause::NoDefault),
+            }
+            if tristate {
+                let implied = reverse(engine, engine.implied_by(name));
+                causes.extend(implied.into_iter().map(|(by, value)| Cause::Implied { by, value }));
+            }
+        },
+    }
+
+    if tristate {
+        let selected = reverse(engine, engine.selected_by(name));
+        causes.extend(selected.into_iter().map(|(by, value)| Cause::Selected { by, value }));
+    }
+
+    let number = text.filter(|_| !tristate).and_then(|text| parse_number(&text, option_type));
+    if let (Some((min, max)), Some(n)) = (engine.active_range(info), number) {
+        if n < min || n > max {
+            causes.push(Cause::Range {
+                min: format_number(min, option_type),
+                max: format_number(max, option_type),
+            });
+        }
+    }
+    causes
+}
+
+// What keeps the prompts of a symbol below `want`: no prompt at all, each term of a prompt's
+// dependencies that is lower, and a menu whose `visible if` is
+pub(super) fn prompt_limits<'t, 'a>(engine: &Engine<'t, '_, 'a>, info: &'t SymbolInfo<'_, 'a>, want: Tristate) -> Vec<Cause<'t, 'a>> {
+    let mut causes = vec![];
+    if info.prompts.is_empty() {
+        causes.push(Cause::NoPrompt);
+    }
+    for prompt in &info.prompts {
+        let def = &info.definitions[prompt.definition];
+        for expr in def.dep.iter().chain(prompt.value.condition.as_ref()).flat_map(Expr::conjuncts) {
+            let value = expr.eval(engine);
+            if value < want {
+                causes.push(Cause::Dependency { expr, value });
+            }
+        }
+        if engine.menu_visible(def.node) < want {
+            causes.push(Cause::MenuHidden);
+        }
+    }
+    causes
+}
+
+// The symbols behind `select` or `imply` properties that currently apply, with the value each
+// one gives
+pub(super) fn reverse<'a>(engine: &Engine<'_, '_, 'a>, revs: &[Reverse<'_, 'a>]) -> Vec<(&'a str, Tristate)> {
+    revs.iter()
+        .map(|rev| {
+            let from = engine.value(rev.symbol).map_or(Tristate::No, |value| value.tristate());
+            (rev.symbol, from.and(eval_opt(rev.dep, engine)).and(eval_opt(rev.condition, engine)))
+        })
+        .filter(|(_, value)| *value != Tristate::No)
+        .collect()
+}
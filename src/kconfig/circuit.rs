@@ -0,0 +1,208 @@
+use super::{
+    Tristate,
+    sat::{
+        Lit,
+        Sat,
+    },
+};
+
+// A constant or a literal of the SAT problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bit {
+    Const(bool),
+    Lit(Lit),
+}
+
+impl Bit {
+    pub fn not(self) -> Bit {
+        match self {
+            Bit::Const(b) => Bit::Const(!b),
+            Bit::Lit(l)   => Bit::Lit(!l),
+        }
+    }
+
+    pub fn holds(self, model: &[bool]) -> bool {
+        match self {
+            Bit::Const(b) => b,
+            Bit::Lit(l)   => model[l.var()] == l.is_positive(),
+        }
+    }
+}
+
+// A tristate as whether it is at least `m` and whether it is `y`
+#[derive(Debug, Clone, Copy)]
+pub struct Tri {
+    pub m: Bit,
+    pub y: Bit,
+}
+
+impl Tri {
+    pub fn constant(value: Tristate) -> Self {
+        Tri {
+            m: Bit::Const(value >= Tristate::Module),
+            y: Bit::Const(value == Tristate::Yes),
+        }
+    }
+
+    pub fn value(self, model: &[bool]) -> Tristate {
+        match (self.m.holds(model), self.y.holds(model)) {
+            (_, true)      => Tristate::Yes,
+            (true, false)  => Tristate::Module,
+            (false, false) => Tristate::No,
+        }
+    }
+
+    // `!y == n`, `!m == m`, `!n == y`
+    pub fn not(self) -> Tri {
+        Tri { m: self.y.not(), y: self.m.not() }
+    }
+}
+
+// Builds tristate logic out of clauses, one gate at a time. Constants are folded away, so an
+// expression over known values adds nothing to the problem.
+#[derive(Debug)]
+pub struct Circuit {
+    pub sat:   Sat,
+
+    // Added to every clause while set, so the clauses only hold while it is assumed
+    pub guard: Option<Lit>,
+}
+
+impl Circuit {
+    pub fn new() -> Self {
+        Self {
+            sat:   Sat::new(),
+            guard: None,
+        }
+    }
+
+    pub fn var(&mut self) -> Bit {
+        Bit::Lit(Lit::new(self.sat.new_var(), true))
+    }
+
+    // A free tristate, or a bool when `tristate` is false
+    pub fn tri(&mut self, tristate: bool) -> Tri {
+        let m = self.var();
+        let y = if tristate { self.var() } else { m };
+        self.implies(y, m);
+        Tri { m, y }
+    }
+
+    pub fn clause(&mut self, bits: &[Bit]) {
+        let mut lits = vec![];
+        for bit in bits {
+            match bit {
+                Bit::Const(true)  => return,
+                Bit::Const(false) => {},
+                Bit::Lit(lit)     => lits.push(*lit),
+            }
+        }
+        lits.extend(self.guard.map(|guard| !guard));
+        self.sat.add_clause(&lits);
+    }
+    pub fn require(&mut self, bit: Bit) {
+        self.clause(&[bit]);
+    }
+
+    pub fn implies(&mut self, a: Bit, b: Bit) {
+        self.clause(&[a.not(), b]);
+    }
+
+    pub fn equal(&mut self, a: Bit, b: Bit) {
+        self.implies(a, b);
+        self.implies(b, a);
+    }
+
+    pub fn and(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(false), _) | (_, Bit::Const(false)) => Bit::Const(false),
+            (Bit::Const(true), x) | (x, Bit::Const(true))   => x,
+            (x, y) if x == y                                => x,
+            (x, y) if x == y.not()                          => Bit::Const(false),
+            (x, y) => {
+                let v = self.var();
+                self.implies(v, x);
+                self.implies(v, y);
+                self.clause(&[x.not(), y.not(), v]);
+                v
+            },
+        }
+    }
+
+    pub fn or(&mut self, a: Bit, b: Bit) -> Bit {
+        let both = self.and(a.not(), b.not());
+        both.not()
+    }
+
+    pub fn iff(&mut self, a: Bit, b: Bit) -> Bit {
+        let both = self.and(a, b);
+        let neither = self.and(a.not(), b.not());
+        self.or(both, neither)
+    }
+
+    pub fn ite(&mut self, c: Bit, t: Bit, e: Bit) -> Bit {
+        let then = self.and(c, t);
+        let other = self.and(c.not(), e);
+        self.or(then, other)
+    }
+
+    pub fn tri_and(&mut self, a: Tri, b: Tri) -> Tri {
+        Tri { m: self.and(a.m, b.m), y: self.and(a.y, b.y) }
+    }
+
+    pub fn tri_or(&mut self, a: Tri, b: Tri) -> Tri {
+        Tri { m: self.or(a.m, b.m), y: self.or(a.y, b.y) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALUES: [Tristate; 3] = [Tristate::No, Tristate::Module, Tristate::Yes];
+
+    // Fixes a free tristate to `value` for the rest of the problem
+    fn fixed(circuit: &mut Circuit, value: Tristate) -> Tri {
+        let tri = circuit.tri(true);
+        let Tri { m, y } = Tri::constant(value);
+        let (m, y) = (circuit.iff(tri.m, m), circuit.iff(tri.y, y));
+        circuit.require(m);
+        circuit.require(y);
+        tri
+    }
+
+    #[test]
+    fn gates_match_tristate_logic() {
+        for a in VALUES {
+            assert_eq!(Tri::constant(a).not().value(&[]), !a);
+            for b in VALUES {
+                let mut circuit = Circuit::new();
+                let (x, y) = (fixed(&mut circuit, a), fixed(&mut circuit, b));
+                let and = circuit.tri_and(x, y);
+                let or = circuit.tri_or(x, y);
+                let not = x.not();
+                let model = circuit.sat.solve(&[]).unwrap();
+                assert_eq!(and.value(&model), a.and(b));
+                assert_eq!(or.value(&model), a.or(b));
+                assert_eq!(not.value(&model), !a);
+
+                // Constants are folded, the result needs no model
+                let folded = circuit.tri_or(Tri::constant(a), Tri::constant(b));
+                assert_eq!(folded.value(&[]), a.or(b));
+            }
+        }
+    }
+
+    #[test]
+    fn guarded_clauses() {
+        let mut circuit = Circuit::new();
+        let x = circuit.var();
+        let Bit::Lit(guard) = circuit.var() else { unreachable!() };
+        circuit.guard = Some(guard);
+        circuit.require(x);
+        circuit.require(x.not());
+        circuit.guard = None;
+        assert!(circuit.sat.solve(&[]).is_ok());
+        assert_eq!(circuit.sat.solve(&[guard]), Err(vec![guard]));
+    }
+}
@@ -0,0 +1,392 @@
+use super::{
+    Engine,
+    Env,
+    Help,
+    MenuItem,
+    OptionType,
+    Tristate,
+    Value,
+    dotconfig::valid_number,
+    parse_number,
+};
+
+use std::io::{
+    self,
+    BufRead,
+    Write,
+};
+
+// Ask on `input` about every visible symbol that has no user value yet, the way `make oldconfig`
+// does, and return how many questions were asked. Symbols the user can not change, because a
+// `select` already forces them as high as their prompt allows, are not asked about.
+//
+// With `echo` set each answer is written to `output` after its question, which keeps the
+// transcript readable when the answers come from a file or pipe rather than a terminal.
+pub fn oldconfig<R: BufRead, W: Write>(engine: &mut Engine, input: R, output: W, echo: bool) -> io::Result<usize> {
+    let mut conf = Conf {
+        input,
+        output,
+        echo,
+        eof:     false,
+        asked:   0,
+        headers: vec![],
+        shown:   0,
+    };
+    let tree = engine.tree;
+    // An answer can make more symbols visible, go over the tree until nothing new shows up
+    loop {
+        let before = conf.asked;
+        for &idx in &tree.roots {
+            conf.node(engine, idx)?;
+        }
+        if conf.asked == before {
+            return Ok(conf.asked);
+        }
+    }
+}
+
+enum Reply {
+    Value(Value),
+    Help,
+
+    // Not a valid answer, the question is asked again
+    Again,
+}
+
+struct Conf<'k, R, W> {
+    input:   R,
+    output:  W,
+    echo:    bool,
+    eof:     bool,
+    asked:   usize,
+
+    // The visible menus around the current node, and how many of them have been printed. A
+    // menu's header is only printed once there is something to ask in it.
+    headers: Vec<&'k str>,
+    shown:   usize,
+}
+
+impl<'k, R: BufRead, W: Write> Conf<'k, R, W> {
+    fn node(&mut self, engine: &mut Engine<'_, 'k, '_>, idx: usize) -> io::Result<()> {
+        let tree = engine.tree;
+        let node = &tree.nodes[idx];
+        let mut header = false;
+        match node.item {
+            MenuItem::Menu(_) => {
+                if let (Some(prompt), true) = (node.item.prompt(), visible(engine, idx)) {
+                    self.headers.push(prompt);
+                    header = true;
+                }
+            },
+            MenuItem::Choice(_) => {
+                let selection = engine.selection(idx);
+                let values = engine.choice_values(idx);
+                let new = values.iter().all(|name| engine.user_value(name).is_none());
+                if selection.mode == Tristate::Yes && new && !values.is_empty() {
+                    self.choice(engine, idx, &values)?;
+                }
+                // The values of a choice are only asked about as part of the choice
+                return Ok(());
+            },
+            MenuItem::Option(opt) => {
+                let name = opt.name.name;
+                if engine.user_value(name).is_none() && engine.is_changeable(name) {
+                    self.symbol(engine, name, node.item.prompt().unwrap_or(name))?;
+                }
+            },
+            MenuItem::Comment(_) => {},
+        }
+        for &child in &node.children {
+            self.node(engine, child)?;
+        }
+        if header {
+            self.headers.pop();
+            self.shown = self.shown.min(self.headers.len());
+        }
+        Ok(())
+    }
+
+    fn print_headers(&mut self) -> io::Result<()> {
+        for header in &self.headers[self.shown..] {
+            writeln!(self.output, "*\n* {}\n*", header)?;
+        }
+        self.shown = self.headers.len();
+        Ok(())
+    }
+
+    // `None` once the input runs out, after which every question takes its default
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        self.output.flush()?;
+        if self.eof {
+            return Ok(None);
+        }
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            self.eof = true;
+            return Ok(None);
+        }
+        let line = line.trim().to_string();
+        if self.echo {
+            writeln!(self.output, "{}", line)?;
+        }
+        Ok(Some(line))
+    }
+
+    // Show the answer that was taken for the user when nothing could be read
+    fn answer(&mut self, default: &str) -> io::Result<Option<String>> {
+        match self.read_line()? {
+            Some(line) => Ok(Some(line)),
+            None => {
+                writeln!(self.output, "{}", default)?;
+                Ok(None)
+            },
+        }
+    }
+
+    fn symbol(&mut self, engine: &mut Engine, name: &str, prompt: &str) -> io::Result<()> {
+        // Without a type there is nothing to answer, and counting it would keep `oldconfig` going
+        let Some(option_type) = engine.symbols.get(name).and_then(|info| info.option_type) else {
+            return Ok(());
+        };
+        self.print_headers()?;
+        self.asked += 1;
+        let current = engine.value(name).unwrap_or(Value::Tristate(Tristate::No));
+        loop {
+            let answer = match option_type {
+                OptionType::Bool | OptionType::Tristate => {
+                    let allowed = allowed(engine, name);
+                    let current = current.tristate();
+                    let mut choices = current.name().to_uppercase();
+                    for value in allowed.iter().filter(|v| **v != current) {
+                        choices.push('/');
+                        choices.push_str(value.name());
+                    }
+                    write!(self.output, "{} ({}) [{}/?] (NEW) ", prompt, name, choices)?;
+                    let Some(line) = self.answer(current.name())? else {
+                        engine.set(name, Value::Tristate(current));
+                        return Ok(());
+                    };
+                    match line.to_lowercase().as_str() {
+                        ""  => Reply::Value(Value::Tristate(current)),
+                        "?" => Reply::Help,
+                        text => Tristate::from_name(text)
+                            .filter(|value| allowed.contains(value))
+                            .map_or(Reply::Again, |value| Reply::Value(Value::Tristate(value))),
+                    }
+                },
+                _ => {
+                    write!(self.output, "{} ({}) [{}] (NEW) ", prompt, name, current.as_str())?;
+                    let Some(line) = self.answer(current.as_str())? else {
+                        engine.set(name, current);
+                        return Ok(());
+                    };
+                    match line.as_str() {
+                        ""  => Reply::Value(current.clone()),
+                        "?" => Reply::Help,
+                        _   => text_value(engine, name, option_type, line.clone()).map_or(Reply::Again, Reply::Value),
+                    }
+                },
+            };
+            match answer {
+                Reply::Value(value) => {
+                    engine.set(name, value);
+                    return Ok(());
+                },
+                Reply::Help  => self.help(engine, name)?,
+                Reply::Again => {},
+            }
+        }
+    }
+
+    fn choice(&mut self, engine: &mut Engine<'_, 'k, '_>, idx: usize, values: &[&'k str]) -> io::Result<()> {
+        self.print_headers()?;
+        self.asked += 1;
+        let node = &engine.tree.nodes[idx];
+        let selected = engine.selection(idx).selected;
+        let default = values.iter().position(|name| Some(*name) == selected);
+        let help = match node.item {
+            MenuItem::Choice(choice) => choice.help.as_ref(),
+            _                        => None,
+        };
+        loop {
+            writeln!(self.output, "{}", node.item.prompt().unwrap_or(""))?;
+            for (i, name) in values.iter().enumerate() {
+                let marker = if default == Some(i) { '>' } else { ' ' };
+                let prompt = engine.symbols.get(name)
+                    .and_then(|info| info.prompts.first())
+                    .map_or(*name, |prompt| prompt.value.text.as_str());
+                writeln!(self.output, "{} {}. {} ({}) (NEW)", marker, i + 1, prompt, name)?;
+            }
+            write!(self.output, "choice[1-{}?]: ", values.len())?;
+            let fallback = default.map_or(String::new(), |i| (i + 1).to_string());
+            let line = self.answer(&fallback)?.unwrap_or_default();
+            let picked = match line.as_str() {
+                ""   => default,
+                "?"  => {
+                    self.print_help(help)?;
+                    continue;
+                },
+                text => match text.parse::<usize>().ok().filter(|n| (1..=values.len()).contains(n)) {
+                    Some(n) => Some(n - 1),
+                    None    => continue,
+                },
+            };
+            // Every value gets a user value, so none of them shows up as new again. An empty
+            // answer to an optional choice with nothing selected leaves all of them `n`.
+            for (i, name) in values.iter().enumerate() {
+                let value = if Some(i) == picked { Tristate::Yes } else { Tristate::No };
+                engine.set(name, Value::Tristate(value));
+            }
+            return Ok(());
+        }
+    }
+
+    fn help(&mut self, engine: &Engine, name: &str) -> io::Result<()> {
+        let help = engine.symbols.get(name)
+            .and_then(|info| info.definitions.iter().find_map(|def| def.option.help.as_ref()));
+        writeln!(self.output, "\nCONFIG_{}:", name)?;
+        self.print_help(help)
+    }
+
+    fn print_help(&mut self, help: Option<&Help>) -> io::Result<()> {
+        match help {
+            Some(help) => {
+                writeln!(self.output)?;
+                for line in help.lines() {
+                    writeln!(self.output, "{}", line)?;
+                }
+                writeln!(self.output)
+            },
+            None => writeln!(self.output, "\nThere is no help available for this option.\n"),
+        }
+    }
+}
+
+// The values the user can pick, between what `select` forces and what the prompt allows
+fn allowed(engine: &Engine, name: &str) -> Vec<Tristate> {
+    let info = engine.symbols.get(name);
+    let tristate = info.and_then(|info| info.option_type) == Some(OptionType::Tristate)
+        && engine.modules() != Tristate::No;
+    let (min, max) = (engine.rev_dep(name), engine.visibility(name));
+    [Tristate::No, Tristate::Module, Tristate::Yes].into_iter()
+        .filter(|value| *value >= min && *value <= max)
+        .filter(|value| *value != Tristate::Module || tristate)
+        .collect()
+}
+
+fn visible(engine: &Engine, idx: usize) -> bool {
+    let own = super::eval_opt(engine.tree.visibility(idx).as_ref(), engine);
+    own.and(engine.menu_visible(idx)) != Tristate::No
+}
+
+// Numbers have to be valid and within the active range, hex gets its `0x` added
+fn text_value(engine: &Engine, name: &str, option_type: OptionType, text: String) -> Option<Value> {
+    if option_type == OptionType::Str {
+        return Some(Value::Str(text));
+    }
+    if !valid_number(&text, option_type) {
+        return None;
+    }
+    let text = match option_type {
+        OptionType::Hex if !text.starts_with("0x") && !text.starts_with("0X") => format!("0x{}", text),
+        _                                                                     => text,
+    };
+    let info = engine.symbols.get(name)?;
+    if let (Some((min, max)), Some(n)) = (engine.active_range(info), parse_number(&text, option_type)) {
+        if n < min || n > max {
+            return None;
+        }
+    }
+    Some(Value::Str(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::engine::tests::with_engine;
+
+    fn run(kconfig: &str, input: &str) -> (usize, String, Vec<(&'static str, Option<Value>)>) {
+        with_engine(kconfig, |engine| {
+            let mut output = vec![];
+            let asked = oldconfig(engine, input.as_bytes(), &mut output, true).unwrap();
+            let values = ["A", "B", "NUM"].into_iter().map(|name| (name, engine.user_value(name).cloned())).collect();
+            (asked, String::from_utf8(output).unwrap(), values)
+        })
+    }
+
+    #[test]
+    fn untyped_prompt() {
+        let kconfig = "
+config UNTYPED
+	prompt \"untyped\"
+
+config A
+	bool \"a\"
+";
+        let (asked, output, values) = run(kconfig, "y\n");
+        assert_eq!(asked, 1);
+        assert_eq!(output, "a (A) [N/y/?] (NEW) y\n");
+        assert_eq!(values[0], ("A", Some(Value::Tristate(Tristate::Yes))));
+    }
+
+    #[test]
+    fn answers() {
+        let kconfig = "
+menu \"M\"
+
+config A
+	bool \"a\"
+
+config B
+	bool \"b\"
+	depends on A
+
+config NUM
+	int \"num\"
+	range 1 10
+	default 3
+
+endmenu
+";
+        // `B` shows up as soon as `A` is set, an out of range number is asked about again
+        let (asked, output, values) = run(kconfig, "y\nn\n20\n7\n");
+        assert_eq!(asked, 3);
+        assert_eq!(output, "*\n* M\n*\n\
+            a (A) [N/y/?] (NEW) y\n\
+            b (B) [N/y/?] (NEW) n\n\
+            num (NUM) [3] (NEW) 20\n\
+            num (NUM) [3] (NEW) 7\n");
+        assert_eq!(values, [
+            ("A",   Some(Value::Tristate(Tristate::Yes))),
+            ("B",   Some(Value::Tristate(Tristate::No))),
+            ("NUM", Some(Value::Str("7".to_string()))),
+        ]);
+    }
+
+    #[test]
+    fn optional_choice() {
+        let kconfig = "
+choice
+	prompt \"c\"
+	optional
+
+config A
+	bool \"a\"
+
+config B
+	bool \"b\"
+
+endchoice
+";
+        // An empty answer picks nothing, and so does running out of input
+        for input in ["\n", ""] {
+            let (asked, output, values) = run(kconfig, input);
+            assert_eq!(asked, 1);
+            assert!(output.ends_with("choice[1-2?]: \n"), "{}", output);
+            assert_eq!(values[..2], [
+                ("A", Some(Value::Tristate(Tristate::No))),
+                ("B", Some(Value::Tristate(Tristate::No))),
+            ]);
+        }
+    }
+}
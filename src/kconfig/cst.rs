@@ -0,0 +1,376 @@
+use super::util::first_word;
+
+use std::fmt;
+
+// A lossless view of a single Kconfig file, as written and before any macro expansion. Every byte
+// of the input ends up in exactly one `CstLine`, so writing the tree back out gives the original
+// file. This is what `fmt` rewrites, the parser proper works on the expanded text instead.
+#[derive(Debug)]
+pub struct Cst<'a> {
+    pub nodes: Vec<CstNode<'a>>,
+}
+
+// One logical line, a physical line plus any lines joined to it with a trailing backslash
+#[derive(Debug, Clone, Copy)]
+pub struct CstLine<'a> {
+    pub indent: &'a str,
+    // Everything after the indent up to the final line ending, continuations included
+    pub text:   &'a str,
+    // `\n`, `\r\n`, or empty on the last line of a file without a trailing newline
+    pub ending: &'a str,
+}
+
+#[derive(Debug)]
+pub enum CstNode<'a> {
+    Blank(CstLine<'a>),
+    Comment(CstLine<'a>),
+    Entry(CstEntry<'a>),
+    Block(CstBlock<'a>),
+    // Macro assignments, stray end keywords and anything else that is not an entry
+    Other(CstLine<'a>),
+}
+
+// `config`, `menuconfig`, `comment`, `source` and `mainmenu`
+#[derive(Debug)]
+pub struct CstEntry<'a> {
+    pub head:  CstLine<'a>,
+    pub attrs: Vec<CstAttr<'a>>,
+}
+
+// `menu`, `choice` and `if`. `end` is missing when the file ends before the block does.
+#[derive(Debug)]
+pub struct CstBlock<'a> {
+    pub head:     CstLine<'a>,
+    pub attrs:    Vec<CstAttr<'a>>,
+    pub children: Vec<CstNode<'a>>,
+    pub end:      Option<CstLine<'a>>,
+}
+
+#[derive(Debug)]
+pub enum CstAttr<'a> {
+    Line(CstLine<'a>),
+    // Help text lines are kept as physical lines, a backslash does not continue them
+    Help {
+        head: CstLine<'a>,
+        body: Vec<CstLine<'a>>,
+    },
+    // Only kept when another attribute follows, otherwise they belong to the next entry
+    Comment(CstLine<'a>),
+    Blank(CstLine<'a>),
+}
+
+const CONFIG_ATTRS: [&str; 20] = [
+    "bool", "tristate", "string", "hex", "int", "prompt", "default", "def_bool", "def_tristate",
+    "depends", "select", "imply", "visible", "range", "help", "---help---", "option", "modules",
+    "optional", "transitional",
+];
+const MENU_ATTRS:    [&str; 2] = ["depends", "visible"];
+const COMMENT_ATTRS: [&str; 1] = ["depends"];
+
+impl<'a> CstLine<'a> {
+    fn new(raw: &'a str) -> Self {
+        let body = raw.strip_suffix('\n').map(|b| b.strip_suffix('\r').unwrap_or(b)).unwrap_or(raw);
+        let ending = &raw[body.len()..];
+        let text = body.trim_start_matches([' ', '\t']);
+        Self {
+            indent: &body[..body.len() - text.len()],
+            text,
+            ending,
+        }
+    }
+
+    pub fn keyword(&self) -> &'a str {
+        first_word(self.text)
+    }
+
+    // The text following the keyword, with the whitespace in between removed
+    pub fn rest(&self) -> &'a str {
+        self.text[self.keyword().len()..].trim_start_matches([' ', '\t'])
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.text.trim().is_empty()
+    }
+
+    pub fn is_comment(&self) -> bool {
+        self.text.starts_with('#')
+    }
+
+    // Width of the indent with tabs expanded to the next multiple of 8, as the kernel counts it
+    pub fn indent_width(&self) -> usize {
+        self.indent.chars().fold(0, |width, c| match c {
+            '\t' => (width / 8 + 1) * 8,
+            _    => width + 1,
+        })
+    }
+}
+
+impl<'a> Cst<'a> {
+    pub fn parse(content: &'a str) -> Self {
+        let mut parser = Parser {
+            content,
+            lines: content.split_inclusive('\n').collect(),
+            pos: 0,
+            open: vec![],
+        };
+        Self {
+            nodes: parser.nodes(None),
+        }
+    }
+}
+
+struct Parser<'a> {
+    content: &'a str,
+    lines:   Vec<&'a str>,
+    pos:     usize,
+    // End keywords of the blocks we are currently inside of, innermost last
+    open:    Vec<&'static str>,
+}
+
+impl<'a> Parser<'a> {
+    // The logical line at `pos` and the position after it
+    fn peek(&self, pos: usize) -> Option<(CstLine<'a>, usize)> {
+        let first = *self.lines.get(pos)?;
+        let start = first.as_ptr() as usize - self.content.as_ptr() as usize;
+        let mut end = pos + 1;
+        let mut last = first;
+        while end < self.lines.len() && last.trim_end_matches(['\n', '\r']).ends_with('\\') {
+            last = self.lines[end];
+            end += 1;
+        }
+        let stop = last.as_ptr() as usize - self.content.as_ptr() as usize + last.len();
+        Some((CstLine::new(&self.content[start..stop]), end))
+    }
+
+    fn physical(&mut self) -> Option<CstLine<'a>> {
+        let line = CstLine::new(self.lines.get(self.pos)?);
+        self.pos += 1;
+        Some(line)
+    }
+
+    fn next(&mut self) -> Option<CstLine<'a>> {
+        let (line, end) = self.peek(self.pos)?;
+        self.pos = end;
+        Some(line)
+    }
+
+    fn nodes(&mut self, end: Option<&'static str>) -> Vec<CstNode<'a>> {
+        let mut nodes = vec![];
+        while let Some((line, _)) = self.peek(self.pos) {
+            if line.is_blank() {
+                nodes.push(CstNode::Blank(self.physical().unwrap()));
+                continue;
+            }
+            if line.is_comment() {
+                nodes.push(CstNode::Comment(self.next().unwrap()));
+                continue;
+            }
+            let keyword = line.keyword();
+            if Some(keyword) == end {
+                break;
+            }
+            // An end keyword for an enclosing block closes this one too, it is left unterminated
+            if self.open.contains(&keyword) {
+                break;
+            }
+            let node = match keyword {
+                "config" | "menuconfig" => CstNode::Entry(self.entry(&CONFIG_ATTRS)),
+                "comment"               => CstNode::Entry(self.entry(&COMMENT_ATTRS)),
+                "source" | "rsource" | "osource" | "orsource" | "mainmenu" => CstNode::Entry(self.entry(&[])),
+                "menu"   => CstNode::Block(self.block(&MENU_ATTRS,   "endmenu")),
+                "choice" => CstNode::Block(self.block(&CONFIG_ATTRS, "endchoice")),
+                "if"     => CstNode::Block(self.block(&[],           "endif")),
+                _        => CstNode::Other(self.next().unwrap()),
+            };
+            nodes.push(node);
+        }
+        nodes
+    }
+
+    fn entry(&mut self, allowed: &[&str]) -> CstEntry<'a> {
+        let head = self.next().unwrap();
+        CstEntry {
+            head,
+            attrs: self.attrs(allowed),
+        }
+    }
+
+    fn block(&mut self, allowed: &[&str], end: &'static str) -> CstBlock<'a> {
+        let head = self.next().unwrap();
+        let attrs = self.attrs(allowed);
+        self.open.push(end);
+        let children = self.nodes(Some(end));
+        self.open.pop();
+        let end = match self.peek(self.pos) {
+            Some((line, _)) if line.keyword() == end => self.next(),
+            _ => None,
+        };
+        CstBlock {
+            head,
+            attrs,
+            children,
+            end,
+        }
+    }
+
+    fn attrs(&mut self, allowed: &[&str]) -> Vec<CstAttr<'a>> {
+        let mut attrs = vec![];
+        loop {
+            // Look past any blank lines and comments, they only belong here if an attribute follows
+            let mut pos = self.pos;
+            while let Some((line, next)) = self.peek(pos) {
+                if !(line.is_blank() || line.is_comment()) {
+                    break;
+                }
+                pos = next;
+            }
+            match self.peek(pos) {
+                Some((line, _)) if allowed.contains(&line.keyword()) => {},
+                _ => return attrs,
+            }
+            while self.pos < pos {
+                let line = self.next().unwrap();
+                attrs.push(if line.is_blank() { CstAttr::Blank(line) } else { CstAttr::Comment(line) });
+            }
+            let line = self.next().unwrap();
+            if matches!(line.keyword(), "help" | "---help---") {
+                attrs.push(CstAttr::Help {
+                    head: line,
+                    body: self.help(),
+                });
+            } else {
+                attrs.push(CstAttr::Line(line));
+            }
+        }
+    }
+
+    // Help text runs until the first non-blank line indented less than its first line. Blank
+    // lines at the very end are left for whatever follows.
+    fn help(&mut self) -> Vec<CstLine<'a>> {
+        let mut body = vec![];
+        let mut indent = None;
+        while let Some(raw) = self.lines.get(self.pos) {
+            let line = CstLine::new(raw);
+            if !line.is_blank() {
+                let width = line.indent_width();
+                match indent {
+                    None if width == 0 => break,
+                    None => indent = Some(width),
+                    Some(first) if width < first => break,
+                    Some(_) => {},
+                }
+            }
+            body.push(line);
+            self.pos += 1;
+        }
+        while body.last().is_some_and(|line| line.is_blank()) {
+            body.pop();
+            self.pos -= 1;
+        }
+        body
+    }
+}
+
+impl fmt::Display for CstLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.indent, self.text, self.ending)
+    }
+}
+
+impl fmt::Display for CstAttr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CstAttr::Line(line) | CstAttr::Comment(line) | CstAttr::Blank(line) => write!(f, "{}", line),
+            CstAttr::Help { head, body } => {
+                write!(f, "{}", head)?;
+                body.iter().try_for_each(|line| write!(f, "{}", line))
+            },
+        }
+    }
+}
+
+impl fmt::Display for CstNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CstNode::Blank(line) | CstNode::Comment(line) | CstNode::Other(line) => write!(f, "{}", line),
+            CstNode::Entry(entry) => {
+                write!(f, "{}", entry.head)?;
+                entry.attrs.iter().try_for_each(|attr| write!(f, "{}", attr))
+            },
+            CstNode::Block(block) => {
+                write!(f, "{}", block.head)?;
+                block.attrs.iter().try_for_each(|attr| write!(f, "{}", attr))?;
+                block.children.iter().try_for_each(|node| write!(f, "{}", node))?;
+                match &block.end {
+                    Some(end) => write!(f, "{}", end),
+                    None      => Ok(()),
+                }
+            },
+        }
+    }
+}
+
+// Writes the file back out exactly as it was read
+impl fmt::Display for Cst<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.nodes.iter().try_for_each(|node| write!(f, "{}", node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSY: &str = "mainmenu \"Test\"\r\n\
+        \n\
+        config A\n\
+        \tbool \"a\"\n\
+        \t# why\n\
+        \tdepends on B && \\\n\
+        \t           C   \n\
+        \thelp\n\
+        \t  Some help.\n\
+        \n\
+        \t    Indented more.\n\
+        \n\
+        menu \"M\"\n\
+        \tvisible if A\n\
+        choice\n\
+        \tprompt \"c\"\n\
+        config C1\n\
+        \tbool \"c1\"\n\
+        endchoice\n\
+        if A\n\
+        comment \"x\"\n\
+        FOO := bar\n\
+        endmenu\n\
+        source \"unterminated\"";
+
+    #[test]
+    fn round_trip() {
+        for content in [MESSY, "", "\n", "config X\n\tbool\n", "menu \"open\"\nconfig Y\n"] {
+            assert_eq!(Cst::parse(content).to_string(), content);
+        }
+    }
+
+    #[test]
+    fn structure() {
+        let cst = Cst::parse(MESSY);
+        let entry = cst.nodes.iter().find_map(|node| match node {
+            CstNode::Entry(entry) if entry.head.rest() == "A" => Some(entry),
+            _ => None,
+        }).unwrap();
+        // The continued `depends on` is one logical line, the help body keeps its trailing lines
+        assert!(matches!(&entry.attrs[1], CstAttr::Comment(line) if line.text == "# why"));
+        assert!(matches!(&entry.attrs[2], CstAttr::Line(line) if line.text.ends_with("C   ")));
+        assert!(matches!(&entry.attrs[3], CstAttr::Help { body, .. } if body.len() == 3));
+
+        // `endmenu` closes the `if` left open inside of it
+        let menu = cst.nodes.iter().find_map(|node| match node {
+            CstNode::Block(block) if block.head.keyword() == "menu" => Some(block),
+            _ => None,
+        }).unwrap();
+        assert!(menu.end.is_some());
+        assert!(matches!(menu.children.last(), Some(CstNode::Block(block)) if block.end.is_none()));
+    }
+}
@@ -0,0 +1,441 @@
+use super::{
+    CompareOp,
+    Diagnostic,
+    Engine,
+    Entry,
+    Env,
+    Expr,
+    KConfig,
+    MenuItem,
+    MenuTree,
+    OptionType,
+    Severity,
+    SourceMap,
+    Span,
+    SymbolTable,
+    Tristate,
+    Value,
+    VarType,
+    circuit::{
+        Bit,
+        Circuit,
+        Tri,
+    },
+    depends_expr,
+    eval::operand,
+};
+
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+// Entries of the tree that can never do anything, whatever the configuration:
+//
+//  * configs, menus, choices and comments whose dependencies can never hold, because they need
+//    a symbol that is not defined or can never be enabled, or contradict themselves
+//  * `if` blocks whose condition can never hold
+//  * prompts that can never be shown, because of their own `if` or a menu's `visible if`
+//
+// Only the outermost entry is reported when a whole menu or block is affected. A symbol whose
+// dependencies can never hold is `n` unless it is selected, and counts as `n` in the dependencies
+// of other entries in turn.
+pub fn dead_code(config: &KConfig, tree: &MenuTree, symbols: &SymbolTable, sources: &SourceMap) -> Vec<Diagnostic> {
+    let checker = Checker::new(tree, symbols);
+    let mut out = vec![];
+    let mut hidden = HashSet::new();
+    checker.blocks(&config.entries, None, sources, &mut hidden, &mut out);
+
+    // Nodes below a reported one are left out, so are nodes in a reported block
+    let mut quiet = HashSet::new();
+    for (idx, node) in tree.nodes.iter().enumerate() {
+        let span = node.item.span();
+        if hidden.contains(span) || node.parent.is_some_and(|parent| quiet.contains(&parent)) {
+            quiet.insert(idx);
+            continue;
+        }
+        if !checker.satisfiable(node.dep.as_ref()) {
+            quiet.insert(idx);
+            let mut diagnostic = sources.diagnostic(Severity::Warning, span,
+                format!("dependencies of {} can never be met", describe(node.item)));
+            checker.notes(node.dep.as_ref(), "depends on", &mut diagnostic);
+            if let MenuItem::Option(opt) = node.item {
+                for rev in checker.engine.selected_by(opt.name.name) {
+                    diagnostic.notes.push(format!("still selected by `{}`", rev.symbol));
+                }
+            }
+            out.push(diagnostic);
+            continue;
+        }
+        if node.item.prompt().is_none() {
+            continue;
+        }
+        let mut visible = tree.visibility(idx);
+        let own = match node.item {
+            MenuItem::Menu(_) => Some(idx),
+            _                 => node.parent,
+        };
+        let mut current = own;
+        while let Some(menu) = current {
+            if let MenuItem::Menu(kmenu) = tree.nodes[menu].item {
+                visible = Expr::and(visible, depends_expr(&kmenu.visible));
+            }
+            current = tree.nodes[menu].parent;
+        }
+        if !checker.satisfiable(visible.as_ref()) {
+            if let MenuItem::Menu(_) = node.item {
+                quiet.insert(idx);
+            }
+            let mut diagnostic = sources.diagnostic(Severity::Warning, span,
+                format!("prompt of {} can never be visible", describe(node.item)));
+            checker.notes(visible.as_ref(), "shown when", &mut diagnostic);
+            out.push(diagnostic);
+        }
+    }
+    out.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    out
+}
+
+fn describe(item: MenuItem) -> String {
+    match item {
+        MenuItem::Option(opt) => format!("`{}`", opt.name.name),
+        MenuItem::Menu(_)     => format!("menu \"{}\"", item.prompt().unwrap_or("")),
+        MenuItem::Choice(_)   => match item.prompt() {
+            Some(prompt) => format!("choice \"{}\"", prompt),
+            None         => "choice".to_string(),
+        },
+        MenuItem::Comment(_)  => format!("comment \"{}\"", item.prompt().unwrap_or("")),
+    }
+}
+
+// Decides whether an expression can hold for some configuration, with every bool and tristate
+// symbol free to take any value
+struct Checker<'t, 'k, 'a> {
+    engine: Engine<'t, 'k, 'a>,
+
+    // Symbols that are `n` whatever the configuration
+    never:  HashSet<&'a str>,
+}
+
+// One side of a comparison
+enum Side {
+    Free(Tri),
+
+    // A string, int or hex symbol, whose value is not looked at
+    Unknown,
+    Fixed,
+}
+
+// Only asked about symbols that are not defined or can never be enabled, the others are free
+impl Env for Checker<'_, '_, '_> {
+    fn value(&self, name: &str) -> Option<Value> {
+        self.never.contains(name).then_some(Value::Tristate(Tristate::No))
+    }
+
+    fn option_type(&self, name: &str) -> Option<OptionType> {
+        self.engine.symbols.get(name).and_then(|info| info.option_type)
+    }
+}
+
+impl<'t, 'k, 'a> Checker<'t, 'k, 'a> {
+    // Find the symbols that can never be enabled, until no more turn up
+    fn new(tree: &'t MenuTree<'k, 'a>, symbols: &'t SymbolTable<'k, 'a>) -> Self {
+        let mut checker = Checker {
+            engine: Engine::new(tree, symbols),
+            never:  HashSet::new(),
+        };
+        loop {
+            let found: Vec<&'a str> = symbols.iter()
+                .filter(|info| matches!(info.option_type, Some(OptionType::Bool | OptionType::Tristate)))
+                .filter(|info| !checker.never.contains(info.name))
+                .filter(|info| info.definitions.iter().all(|def| !checker.satisfiable(def.dep.as_ref())))
+                .filter(|info| checker.engine.selected_by(info.name).iter().all(|rev| {
+                    checker.never.contains(rev.symbol)
+                        || !checker.satisfiable(Expr::and(rev.dep.cloned(), rev.condition.cloned()).as_ref())
+                }))
+                .map(|info| info.name)
+                .collect();
+            if found.is_empty() {
+                return checker;
+            }
+            checker.never.extend(found);
+        }
+    }
+
+    fn satisfiable(&self, expr: Option<&Expr>) -> bool {
+        let Some(expr) = expr else {
+            return true;
+        };
+        let mut circuit = Circuit::new();
+        let value = self.encode(&mut circuit, &mut HashMap::new(), expr);
+        circuit.require(value.m);
+        circuit.sat.solve(&[]).is_ok()
+    }
+
+    fn encode<'e>(&self, circuit: &mut Circuit, vars: &mut HashMap<&'e str, Tri>, expr: &'e Expr) -> Tri {
+        match expr {
+            // `m && MODULES`
+            Expr::Var(VarType::Tristate(Tristate::Module)) => match self.free(circuit, vars, "MODULES") {
+                Some(modules) => Tri { m: modules.m, y: Bit::Const(false) },
+                None          => Tri::constant(Tristate::No),
+            },
+            Expr::Var(var)  => match self.side(circuit, vars, var) {
+                Side::Free(value) => value,
+                _                 => Tri::constant(expr.eval(self)),
+            },
+            Expr::Not(inner) => self.encode(circuit, vars, inner).not(),
+            Expr::And(l, r) => {
+                let (l, r) = (self.encode(circuit, vars, l), self.encode(circuit, vars, r));
+                circuit.tri_and(l, r)
+            },
+            Expr::Or(l, r)  => {
+                let (l, r) = (self.encode(circuit, vars, l), self.encode(circuit, vars, r));
+                circuit.tri_or(l, r)
+            },
+            Expr::Compare(op, l, r) => {
+                let equal = match (self.side(circuit, vars, l), self.side(circuit, vars, r)) {
+                    (Side::Fixed, Side::Fixed) => return Tri::constant(expr.eval(self)),
+                    // Ordering, and the values of strings and numbers, are not worked out
+                    _ if !matches!(op, CompareOp::Equal | CompareOp::NotEqual) => {
+                        let bit = circuit.var();
+                        return Tri { m: bit, y: bit };
+                    },
+                    (Side::Free(l), Side::Free(r))   => same(circuit, l, r),
+                    (Side::Free(value), Side::Fixed) => self.same_as(circuit, value, r),
+                    (Side::Fixed, Side::Free(value)) => self.same_as(circuit, value, l),
+                    _                                => circuit.var(),
+                };
+                let bit = if *op == CompareOp::Equal { equal } else { equal.not() };
+                Tri { m: bit, y: bit }
+            },
+        }
+    }
+
+    // A bool or tristate is never equal to anything but `n`, `m` or `y`
+    fn same_as(&self, circuit: &mut Circuit, value: Tri, fixed: &VarType) -> Bit {
+        match Tristate::from_name(&operand(fixed, self).0) {
+            Some(fixed) => same(circuit, value, Tri::constant(fixed)),
+            None        => Bit::Const(false),
+        }
+    }
+
+    fn side<'e>(&self, circuit: &mut Circuit, vars: &mut HashMap<&'e str, Tri>, var: &VarType<'e>) -> Side {
+        let VarType::Symbol(symbol) = var else {
+            return Side::Fixed;
+        };
+        match self.engine.symbols.get(symbol.name).and_then(|info| info.option_type) {
+            Some(OptionType::Str | OptionType::Int | OptionType::Hex) => Side::Unknown,
+            _ => self.free(circuit, vars, symbol.name).map_or(Side::Fixed, Side::Free),
+        }
+    }
+
+    // The variables standing for a bool or tristate symbol that can be enabled
+    fn free<'e>(&self, circuit: &mut Circuit, vars: &mut HashMap<&'e str, Tri>, name: &'e str) -> Option<Tri> {
+        if self.never.contains(name) {
+            return None;
+        }
+        let tristate = match self.engine.symbols.get(name).and_then(|info| info.option_type) {
+            Some(OptionType::Bool)     => false,
+            Some(OptionType::Tristate) => true,
+            _                          => return None,
+        };
+        Some(*vars.entry(name).or_insert_with(|| circuit.tri(tristate)))
+    }
+
+    // What the condition needs that can't be had
+    fn notes(&self, expr: Option<&Expr>, what: &str, diagnostic: &mut Diagnostic) {
+        let Some(expr) = expr else {
+            return;
+        };
+        diagnostic.notes.push(format!("{} {}", what, expr));
+        let mut seen = HashSet::new();
+        let mut blamed = false;
+        for symbol in expr.symbols() {
+            if !seen.insert(symbol.name) {
+                continue;
+            }
+            if self.engine.symbols.get(symbol.name).is_none() {
+                diagnostic.notes.push(format!("`{}` is not defined", symbol.name));
+                blamed = true;
+            } else if self.never.contains(symbol.name) {
+                diagnostic.notes.push(format!("`{}` can never be enabled", symbol.name));
+                blamed = true;
+            }
+        }
+        if !blamed {
+            diagnostic.notes.push("the conditions contradict each other".to_string());
+        }
+    }
+
+    // Report `if` blocks whose condition can never hold, and collect the spans of the entries in
+    // them
+    fn blocks<'c>(&self, entries: &'c [Entry], cond: Option<&Expr>, sources: &SourceMap, hidden: &mut HashSet<&'c Span>, out: &mut Vec<Diagnostic>) {
+        for entry in entries {
+            match entry {
+                Entry::Block(block) => {
+                    let full = Expr::and(cond.cloned(), Some(block.condition.clone()));
+                    if self.satisfiable(full.as_ref()) {
+                        self.blocks(&block.config.entries, full.as_ref(), sources, hidden, out);
+                        continue;
+                    }
+                    // Already covered when the surrounding entries can't be shown either
+                    if self.satisfiable(cond) {
+                        let mut diagnostic = sources.diagnostic(Severity::Warning, &block.span,
+                            format!("condition of `if {}` can never hold", block.condition));
+                        self.notes(full.as_ref(), "needs", &mut diagnostic);
+                        out.push(diagnostic);
+                    }
+                    spans(&block.config.entries, hidden);
+                },
+                Entry::Menu(menu) => {
+                    let full = Expr::and(cond.cloned(), depends_expr(&menu.depends));
+                    self.blocks(&menu.entries, full.as_ref(), sources, hidden, out);
+                },
+                Entry::Choice(choice) => {
+                    let full = Expr::and(cond.cloned(), depends_expr(&choice.depends));
+                    self.blocks(&choice.entries, full.as_ref(), sources, hidden, out);
+                },
+                Entry::Source(source) => {
+                    for config in &source.configs {
+                        self.blocks(&config.entries, cond, sources, hidden, out);
+                    }
+                },
+                Entry::Option(_) | Entry::Comment(_) => {},
+            }
+        }
+    }
+}
+
+// A bit that holds when both are the same
+fn same(circuit: &mut Circuit, a: Tri, b: Tri) -> Bit {
+    let m = circuit.iff(a.m, b.m);
+    let y = circuit.iff(a.y, b.y);
+    circuit.and(m, y)
+}
+
+// The spans of the entries in a block and in the files it sources. What is inside menus and
+// choices is left out, it is below a node that is already skipped.
+fn spans<'c>(entries: &'c [Entry], out: &mut HashSet<&'c Span>) {
+    for entry in entries {
+        match entry {
+            Entry::Block(block)   => spans(&block.config.entries, out),
+            Entry::Source(source) => {
+                for config in &source.configs {
+                    spans(&config.entries, out);
+                }
+            },
+            entry                 => {
+                out.insert(entry.span());
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::path::Path;
+
+    // `line: message` for each warning about the file
+    fn lint(test: &str, kconfig: &str) -> Vec<String> {
+        let dir = std::env::temp_dir().join(format!("kconfigurator-dead-{}-{}", std::process::id(), test));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Kconfig"), kconfig).unwrap();
+        let sources = SourceMap::load(&dir, Path::new("Kconfig")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (config, diagnostics) = sources.parse();
+        assert!(diagnostics.is_empty());
+        let tree = MenuTree::build(&config);
+        let symbols = SymbolTable::build(&tree);
+        dead_code(&config, &tree, &symbols, &sources).into_iter()
+            .map(|d| format!("{}: {}", d.line, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn contradictions() {
+        let kconfig = "\
+config A
+	bool \"a\"
+
+config SELF
+	bool \"self\"
+	depends on A && !A
+
+config NEVER_SHOWN
+	bool \"never shown\" if A && !A
+
+config TRI
+	tristate \"tri\"
+
+config BOTH
+	bool \"both\"
+	depends on TRI = m && TRI = y
+
+config EITHER
+	bool \"either\"
+	depends on A || !A
+
+config AFTER
+	bool \"after\"
+	depends on SELF || A
+";
+        assert_eq!(lint("contradictions", kconfig), [
+            "4: dependencies of `SELF` can never be met",
+            "8: prompt of `NEVER_SHOWN` can never be visible",
+            "14: dependencies of `BOTH` can never be met",
+        ]);
+    }
+
+    #[test]
+    fn undefined_dependencies() {
+        let kconfig = "\
+config UNDEF
+	bool \"undef\"
+	depends on MISSING
+
+config CHAIN
+	bool \"chain\"
+	depends on UNDEF
+
+config SELECTED
+	bool
+	depends on MISSING
+
+config SELECTOR
+	bool \"selector\"
+	select SELECTED
+
+config USES_SELECTED
+	bool \"uses selected\"
+	depends on SELECTED
+
+if MISSING
+config IN_IF
+	bool \"in if\"
+endif
+
+menu \"dead menu\"
+	depends on MISSING
+
+config IN_MENU
+	bool \"in menu\"
+
+endmenu
+
+config OK
+	bool \"ok\"
+	depends on UNDEF || !MISSING
+";
+        // Only the outermost entry, and a selected symbol can still be enabled
+        assert_eq!(lint("undefined", kconfig), [
+            "1: dependencies of `UNDEF` can never be met",
+            "9: dependencies of `SELECTED` can never be met",
+            "21: condition of `if MISSING` can never hold",
+            "26: dependencies of menu \"dead menu\" can never be met",
+        ]);
+    }
+}
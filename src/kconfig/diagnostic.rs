@@ -0,0 +1,189 @@
+use super::{
+    Block,
+    KChoice,
+    KMenu,
+    util::{
+        ParseError,
+        first_word,
+    },
+};
+
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file:     PathBuf,
+    pub line:     usize,
+    pub column:   usize,
+    pub message:  String,
+    pub notes:    Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error   => "error",
+        };
+        write!(f, "{}:{}:{}: {}: {}", self.file.display(), self.line, self.column, severity, self.message)?;
+        for note in &self.notes {
+            write!(f, "\n  note: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+// Resolves positions within a single file's text into line and column numbers
+pub struct FileText<'s> {
+    pub path:    &'s std::path::Path,
+    pub content: &'s str,
+}
+
+impl FileText<'_> {
+    // `slice` must point into `content`
+    pub fn position(&self, slice: &str) -> (usize, usize) {
+        let offset = slice.as_ptr() as usize - self.content.as_ptr() as usize;
+        let before = &self.content[..offset];
+        let line_start = before.rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+        (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+    }
+
+    fn location(&self, slice: &str) -> String {
+        let (line, _) = self.position(slice);
+        format!("{}:{}", self.path.display(), line)
+    }
+
+    fn rest<'s>(&'s self, slice: &str) -> &'s str {
+        let offset = slice.as_ptr() as usize - self.content.as_ptr() as usize;
+        &self.content[offset..]
+    }
+
+    pub fn error(&self, at: &str, message: String, notes: Vec<String>) -> Diagnostic {
+        let at = at.trim_start_matches([' ', '\t']);
+        let (line, column) = self.position(at);
+        Diagnostic {
+            severity: Severity::Error,
+            file:     self.path.to_path_buf(),
+            line,
+            column,
+            message,
+            notes,
+        }
+    }
+
+    // Work out why an entry was skipped. Entries which open a block are parsed again on their own
+    // to find where the block went wrong, any other line simply could not be understood.
+    pub fn describe(&self, error: &ParseError) -> Diagnostic {
+        let rest = self.rest(error.skipped.trim_start_matches(['\n', ' ', '\t']));
+        let keyword = first_word(rest);
+
+        let mut notes = vec![];
+        if let Some((outer, start)) = error.inside {
+            notes.push(format!("inside `{}` starting at {}", outer, self.location(start.trim_start())));
+        }
+
+        let failed_at = match keyword {
+            "choice" => KChoice::parse(rest).err(),
+            "menu"   => KMenu::parse(rest).err(),
+            "if"     => Block::parse(rest).err(),
+            _        => None,
+        };
+        let end = match keyword {
+            "choice" => "endchoice",
+            "menu"   => "endmenu",
+            _        => "endif",
+        };
+
+        if let Some(nom::Err::Error(e) | nom::Err::Failure(e)) = failed_at {
+            let found = match e.input.trim_start().lines().next() {
+                Some(line) => format!("found `{}`", line.trim()),
+                None       => "found end of file".to_string(),
+            };
+            let message = format!("inside `{}` starting at {}, expected `{}` but {}",
+                keyword, self.location(rest), end, found);
+            return self.error(e.input.trim_start_matches(['\n', ' ', '\t']), message, notes);
+        }
+
+        let line = rest.lines().next().unwrap_or("").trim();
+        let message = match keyword {
+            "endchoice" | "endmenu" | "endif" => format!("`{}` without a matching `{}`", keyword, &keyword[3..]),
+            _                                 => format!("unable to parse `{}`", line),
+        };
+        self.error(rest, message, notes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        Entry,
+        SourceMap,
+        source_map::tests::Tree,
+    };
+
+    use std::path::Path;
+
+    #[test]
+    fn recovery() {
+        let tree = Tree::new("recovery", &[("Kconfig", "\
+config A
+\tbool \"a\"
+\tbogus line
+\thelp
+\t  if this were an entry the help would end here
+
+config B
+\tbool \"b\"
+
+menu \"m\"
+
+config C
+\tbool \"c\"
+\tdepends on &&
+
+endmenu
+
+choice
+\tprompt \"ch\"
+
+config D
+\tbool \"d\"
+
+endmenu
+
+config E
+\tbool \"e\"
+")]);
+        let sources = SourceMap::load(&tree.0, Path::new("Kconfig")).unwrap();
+        let (config, diagnostics) = sources.parse();
+        let text: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(text, [
+            "Kconfig:3:2: error: unable to parse `bogus line`",
+            "Kconfig:14:2: error: unable to parse `depends on &&`\n  note: inside `menu` starting at Kconfig:10",
+            "Kconfig:24:1: error: inside `choice` starting at Kconfig:18, expected `endchoice` but found `endmenu`",
+            "Kconfig:24:1: error: `endmenu` without a matching `menu`",
+        ]);
+
+        // Parsing carries on with the next entry after each error, even the values of a choice
+        // that is never closed
+        let names: Vec<&str> = config.entries.iter().filter_map(|entry| match entry {
+            Entry::Option(opt) => Some(opt.name.name),
+            _                  => None,
+        }).collect();
+        assert_eq!(names, ["A", "B", "D", "E"]);
+    }
+}
@@ -0,0 +1,166 @@
+use super::{
+    Cause,
+    Engine,
+    MenuItem,
+    OptionType,
+    PREFIX,
+    Value,
+    causes,
+    escape,
+};
+
+use std::collections::HashSet;
+
+// A symbol whose value differs between two configurations of the same tree
+#[derive(Debug, Clone)]
+pub struct Change<'t, 'k, 'a> {
+    pub name:        &'a str,
+    pub option_type: OptionType,
+
+    // The menus leading to the symbol, outermost first
+    pub location:    Vec<&'k str>,
+
+    // As `.config` has them, `None` when the symbol is not written at all
+    pub old:         Option<Value>,
+    pub new:         Option<Value>,
+
+    // Why the symbol has each value, see `causes`
+    pub old_causes:  Vec<Cause<'t, 'a>>,
+    pub new_causes:  Vec<Cause<'t, 'a>>,
+
+    // Whether the user value of the symbol is not the same on both sides. A full `.config` gives
+    // most symbols a user value, so one that kept its user value changed because of other
+    // symbols, through defaults, `select` or dependencies, and its causes say which.
+    pub user:        bool,
+}
+
+// Every symbol that is written differently by the two engines, in menu order. Both have to be
+// built over the same tree.
+pub fn diff<'t, 'k, 'a>(old: &Engine<'t, 'k, 'a>, new: &Engine<'t, 'k, 'a>) -> Vec<Change<'t, 'k, 'a>> {
+    let tree = new.tree;
+    let mut seen = HashSet::new();
+    let mut changes = vec![];
+    for node in &tree.nodes {
+        let MenuItem::Option(opt) = node.item else {
+            continue;
+        };
+        let name = opt.name.name;
+        if !seen.insert(name) {
+            continue;
+        }
+        let Some(info) = new.symbols.get(name) else {
+            continue;
+        };
+        let Some(option_type) = info.option_type else {
+            continue;
+        };
+        let (before, after) = (old.written_value(name), new.written_value(name));
+        if before == after {
+            continue;
+        }
+        changes.push(Change {
+            name,
+            option_type,
+            location:   info.definitions.first().map_or_else(Vec::new, |def| def.location.clone()),
+            old:        before,
+            new:        after,
+            old_causes: causes(old, name),
+            new_causes: causes(new, name),
+            user:       old.user_value(name) != new.user_value(name),
+        });
+    }
+    changes
+}
+
+// The changes grouped under their menu, each group in the order its first change appears. Every
+// change is marked as set by the user or derived, followed by the reasons for the old and the new
+// value.
+pub fn format_diff(changes: &[Change]) -> String {
+    let mut groups: Vec<(&Vec<&str>, Vec<&Change>)> = vec![];
+    for change in changes {
+        match groups.iter_mut().find(|(location, _)| **location == change.location) {
+            Some((_, group)) => group.push(change),
+            None             => groups.push((&change.location, vec![change])),
+        }
+    }
+
+    let mut out = String::new();
+    for (i, (location, group)) in groups.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!("{}:\n", if location.is_empty() { "(top level)".to_string() } else { location.join(" -> ") }));
+        for change in group {
+            out.push_str(&format!("  {}{} {} -> {} ({})\n", PREFIX, change.name,
+                show(change.option_type, change.old.as_ref()),
+                show(change.option_type, change.new.as_ref()),
+                if change.user { "set" } else { "derived" }));
+            for (side, causes) in [("old", &change.old_causes), ("new", &change.new_causes)] {
+                let causes: Vec<String> = causes.iter().map(|cause| cause.to_string()).collect();
+                if !causes.is_empty() {
+                    out.push_str(&format!("    {}: {}\n", side, causes.join("; ")));
+                }
+            }
+        }
+    }
+    out
+}
+
+fn show(option_type: OptionType, value: Option<&Value>) -> String {
+    match (option_type, value) {
+        (_, None)                      => "(absent)".to_string(),
+        (OptionType::Str, Some(value)) => escape(value.as_str()),
+        (_, Some(value))               => value.as_str().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{
+        KConfig,
+        MenuTree,
+        SymbolTable,
+        Tristate,
+    };
+
+    #[test]
+    fn set_and_derived() {
+        let kconfig = "
+config A
+	bool \"a\"
+
+config D
+	bool \"d\"
+	depends on A
+
+config E
+	bool \"e\"
+";
+        let (_, config) = KConfig::parse(kconfig).unwrap();
+        let tree = MenuTree::build(&config);
+        let symbols = SymbolTable::build(&tree);
+        let (mut old, mut new) = (Engine::new(&tree, &symbols), Engine::new(&tree, &symbols));
+        // Both sides are full configurations, `D` keeps its user value but loses its dependency
+        for (engine, a) in [(&mut old, Tristate::Yes), (&mut new, Tristate::No)] {
+            engine.set("A", Value::Tristate(a));
+            engine.set("D", Value::Tristate(Tristate::Yes));
+            engine.set("E", Value::Tristate(Tristate::No));
+        }
+
+        let changes = diff(&old, &new);
+        let summary: Vec<(&str, bool)> = changes.iter().map(|change| (change.name, change.user)).collect();
+        assert_eq!(summary, [("A", true), ("D", false)]);
+        assert_eq!(format_diff(&changes), "\
+(top level):
+  CONFIG_A y -> n (set)
+    old: set to y by the user
+    new: set to n by the user
+
+a:
+  CONFIG_D y -> n (derived)
+    old: set to y by the user
+    new: depends on A, which is n; no default applies
+");
+    }
+}
@@ -1,13 +1,20 @@
 use crate::kconfig::{
+    DefaultKind,
     KOption,
     KMenu,
     Block,
     KChoice,
     KConfig,
     KCommentBlock,
+    KSource,
+    Entry,
+    Annotation,
     OptionType,
     Symbol,
-    Expression,
+    Expr,
+    VarType,
+    CompareOp,
+    Tristate,
     Prompt,
     Dependency,
     Range,
@@ -24,18 +31,18 @@ use super::DisplayKConfig;
 impl DisplayKConfig for OptionType {
     fn display_kconfig(&self) -> String {
         match self {
-            OptionType::Tristate => format!("tristate"),
-            OptionType::Bool     => format!("bool"),
-            OptionType::Hex      => format!("hex"),
-            OptionType::Int      => format!("int"),
-            OptionType::Str      => format!("str"),
+            OptionType::Tristate => "tristate".to_string(),
+            OptionType::Bool     => "bool".to_string(),
+            OptionType::Hex      => "hex".to_string(),
+            OptionType::Int      => "int".to_string(),
+            OptionType::Str      => "string".to_string(),
         }
     }
 }
 
 impl DisplayKConfig for Symbol<'_> {
     fn display_kconfig(&self) -> String {
-        format!("{}", self.name)
+        self.name.to_string()
     }
 }
 
@@ -50,9 +57,66 @@ impl DisplayKConfig for ConstantSymbol<'_> {
     }
 }
 
-impl DisplayKConfig for Expression<'_> {
+impl DisplayKConfig for Tristate {
     fn display_kconfig(&self) -> String {
-        format!("{}", self.val)
+        self.name().to_string()
+    }
+}
+
+impl DisplayKConfig for CompareOp {
+    fn display_kconfig(&self) -> String {
+        match self {
+            CompareOp::Equal        => "=".to_string(),
+            CompareOp::NotEqual     => "!=".to_string(),
+            CompareOp::Less         => "<".to_string(),
+            CompareOp::LessEqual    => "<=".to_string(),
+            CompareOp::Greater      => ">".to_string(),
+            CompareOp::GreaterEqual => ">=".to_string(),
+        }
+    }
+}
+
+impl DisplayKConfig for VarType<'_> {
+    fn display_kconfig(&self) -> String {
+        match self {
+            VarType::Tristate(v)       => format!("{}", v),
+            VarType::Hex(v)            => format!("{}", v),
+            VarType::Int(v)            => format!("{}", v),
+            VarType::ConstantSymbol(v) => format!("{}", v),
+            VarType::Symbol(v)         => format!("{}", v),
+            VarType::Macro(v)          => v.to_string(),
+        }
+    }
+}
+
+// Binding strength, used to decide when a sub-expression needs to be wrapped in parentheses
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Or(_, _)         => 0,
+        Expr::And(_, _)        => 1,
+        Expr::Not(_)           => 2,
+        Expr::Compare(_, _, _) => 3,
+        Expr::Var(_)           => 3,
+    }
+}
+
+fn display_operand(parent: &Expr, child: &Expr) -> String {
+    if precedence(child) < precedence(parent) {
+        format!("({})", child)
+    } else {
+        format!("{}", child)
+    }
+}
+
+impl DisplayKConfig for Expr<'_> {
+    fn display_kconfig(&self) -> String {
+        match self {
+            Expr::Var(v)                => format!("{}", v),
+            Expr::Compare(op, lhs, rhs) => format!("{}{}{}", lhs, op, rhs),
+            Expr::Not(e)                => format!("!{}", display_operand(self, e)),
+            Expr::And(lhs, rhs)         => format!("{} && {}", display_operand(self, lhs), display_operand(self, rhs)),
+            Expr::Or(lhs, rhs)          => format!("{} || {}", display_operand(self, lhs), display_operand(self, rhs)),
+        }
     }
 }
 
@@ -84,7 +148,7 @@ impl DisplayKConfig for Int {
 
 impl DisplayKConfig for Hex {
     fn display_kconfig(&self) -> String {
-        format!("{}", self.val)
+        format!("0x{:x}", self.val)
     }
 }
 
@@ -108,11 +172,19 @@ impl DisplayKConfig for Range<'_> {
     }
 }
 
+// The `help` keyword and its text, indented the way the kernel does
 impl DisplayKConfig for Help<'_> {
     fn display_kconfig(&self) -> String {
-        let mut ret = String::new();
-        for line in &self.text {
-            ret = format!("{}\n  {}", ret, line);
+        let text = self.text.concat();
+        let lines: Vec<&str> = text.lines().map(|line| line.trim_end()).collect();
+        let end = lines.iter().rposition(|line| !line.is_empty()).map_or(0, |i| i + 1);
+        let mut ret = "\thelp\n".to_string();
+        for line in &lines[..end] {
+            if line.is_empty() {
+                ret = format!("{}\n", ret);
+            } else {
+                ret = format!("{}\t  {}\n", ret, line.trim_start());
+            }
         }
         ret
     }
@@ -123,37 +195,43 @@ impl DisplayKConfig for KCommentBlock<'_> {
         let mut ret = format!("comment {}\n", self.prompt);
         if let Some(depends) = &self.depends {
             for dep in depends {
-                ret = format!("{}\n\t{}", ret, dep);
+                ret = format!("{}\tdepends on {}\n", ret, dep);
             }
         }
         ret
     }
 }
 
-impl DisplayKConfig for KOption<'_> {
+impl DisplayKConfig for Annotation<'_> {
     fn display_kconfig(&self) -> String {
-        let mut ret = format!("config {}\n", self.name);
-
-        ret = format!("{}\t{}", ret, self.option_type);
-        if let Some(prompt) = &self.prompt {
-            ret = format!("{} {}", ret, prompt);
-        }
-        ret = format!("{}\n", ret);
+        format!("# {}", self.text)
+    }
+}
 
-        if let Some(defaults) = &self.defaults {
-            for def in defaults {
-                ret = format!("{}\tdefaults {}\n", ret, def);
-            }
-        }
-        if let Some(def_bool) = &self.def_bool {
-            for def in def_bool {
-                ret = format!("{}\tdef_bool {}\n", ret, def);
+impl DisplayKConfig for KOption<'_> {
+    fn display_kconfig(&self) -> String {
+        let keyword = if self.menuconfig { "menuconfig" } else { "config" };
+        let mut ret = format!("{} {}\n", keyword, self.name);
+
+        // An untyped definition only adds to a symbol typed elsewhere, its `option_type` is a guess
+        if self.typed {
+            ret = format!("{}\t{}", ret, self.option_type);
+            if let Some(prompt) = &self.prompt {
+                ret = format!("{} {}", ret, prompt);
             }
+            ret = format!("{}\n", ret);
+        } else if let Some(prompt) = &self.prompt {
+            ret = format!("{}\tprompt {}\n", ret, prompt);
         }
-        if let Some(def_tristate) = &self.def_tristate {
-            for def in def_tristate {
-                ret = format!("{}\tdef_tristate {}\n", ret, def);
-            }
+
+        // The first default that applies wins, so they are written in their original order
+        for (kind, def) in self.default_order.iter().zip(self.all_defaults()) {
+            let keyword = match kind {
+                DefaultKind::Default     => "default",
+                DefaultKind::DefBool     => "def_bool",
+                DefaultKind::DefTristate => "def_tristate",
+            };
+            ret = format!("{}\t{} {}\n", ret, keyword, def);
         }
         if let Some(depends) = &self.depends {
             for dep in depends {
@@ -177,32 +255,25 @@ impl DisplayKConfig for KOption<'_> {
         }
 
         if let Some(help) = &self.help {
-            ret = format!("{}\thelp\n", ret);
-            for line in &help.text {
-                if !line.is_empty() {
-                    ret = format!("{}\t  {}", ret, line);
-                }
-                ret = format!("{}", ret);
-            }
+            ret = format!("{}{}", ret, help);
         }
         ret
     }
 }
 
-impl DisplayKConfig for KMenu<'_> {
+impl DisplayKConfig for KSource<'_> {
     fn display_kconfig(&self) -> String {
-        let mut ret = String::new();
-        macro_rules! writemacro {
-            ($field:ident) => {
-                if let Some($field) = &self.$field {
-                    for i in $field {
-                        ret = format!("{}{}\n", ret, i);
-                    }
-                }
-            };
+        let mut ret = format!("{} {}\n", self.kind.keyword(), self.path);
+        for config in &self.configs {
+            ret = format!("{}{}\n", ret, config);
         }
+        ret
+    }
+}
 
-        ret = format!("{}menu {}\n", ret, self.description);
+impl DisplayKConfig for KMenu<'_> {
+    fn display_kconfig(&self) -> String {
+        let mut ret = format!("menu {}\n", self.description);
         if let Some(conditions) = &self.visible {
             for condition in conditions {
                 ret = format!("{}\tvisible if {}\n", ret, condition);
@@ -213,16 +284,10 @@ impl DisplayKConfig for KMenu<'_> {
                 ret = format!("{}\tdepends on {}\n", ret, dep);
             }
         }
-
-        if let Some(configs) = &self.configs {
-            for config in configs {
-                ret = format!("{}source {}\n", ret, config);
-            }
+        for entry in &self.entries {
+            ret = format!("{}{}", ret, entry);
         }
-        writemacro!(blocks);
-        writemacro!(choices);
-        writemacro!(menus);
-        writemacro!(options);
+        ret = format!("{}endmenu\n", ret);
         ret
     }
 }
@@ -244,7 +309,7 @@ impl DisplayKConfig for KChoice<'_> {
 
         if let Some(defaults) = &self.defaults {
             for def in defaults {
-                ret = format!("{}\tdefaults {}\n", ret, def);
+                ret = format!("{}\tdefault {}\n", ret, def);
             }
         }
 
@@ -255,17 +320,11 @@ impl DisplayKConfig for KChoice<'_> {
         }
 
         if let Some(help) = &self.help {
-            ret = format!("{}\thelp\n", ret);
-            for line in &help.text {
-                if !line.is_empty() {
-                    format!("{}\t  {}", ret, line);
-                }
-                ret = format!("{}", ret);
-            }
+            ret = format!("{}{}", ret, help);
         }
 
-        for opt in &self.options {
-            ret = format!("{}{}\n", ret, opt);
+        for entry in &self.entries {
+            ret = format!("{}{}", ret, entry);
         }
 
         ret = format!("{}endchoice\n", ret);
@@ -276,37 +335,93 @@ impl DisplayKConfig for KChoice<'_> {
 impl DisplayKConfig for KConfig<'_> {
     fn display_kconfig(&self) -> String {
         let mut ret = String::new();
-        macro_rules! writemacro {
-            ($field:ident) => {
-                if let Some($field) = &self.$field {
-                    for i in $field {
-                        ret = format!("{}{}\n", ret, i);
-                    }
-                }
-            };
+        if let Some(notes) = &self.annotations {
+            for note in notes {
+                ret = format!("{}{}\n", ret, note);
+            }
         }
-
         if let Some(mainmenu) = &self.mainmenu {
             ret = format!("{}mainmenu {}\n", ret, mainmenu);
         }
-
-        if let Some(configs) = &self.configs {
-            for config in configs {
-                ret = format!("{}source {}\n", ret, config);
-            }
+        for entry in &self.entries {
+            ret = format!("{}{}", ret, entry);
         }
-
-
-        writemacro!(blocks);
-        writemacro!(choices);
-        writemacro!(menus);
-        writemacro!(options);
         ret
     }
 }
 
+impl DisplayKConfig for Entry<'_> {
+    fn display_kconfig(&self) -> String {
+        // Annotations are all written above the entry, even the ones that trailed it
+        let mut ret = String::new();
+        for note in self.annotations() {
+            ret = format!("{}{}\n", ret, note);
+        }
+        match self {
+            Entry::Option(v)  => format!("{}{}\n", ret, v),
+            Entry::Menu(v)    => format!("{}{}\n", ret, v),
+            Entry::Choice(v)  => format!("{}{}\n", ret, v),
+            Entry::Block(v)   => format!("{}{}\n", ret, v),
+            Entry::Source(v)  => format!("{}{}", ret, v),
+            Entry::Comment(v) => format!("{}{}\n", ret, v),
+        }
+    }
+}
+
 impl DisplayKConfig for Block<'_> {
     fn display_kconfig(&self) -> String {
         format!("if {}\n{}\nendif\n", self.condition, self.config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::kconfig::{
+        Entry,
+        KConfig,
+        KOption,
+    };
+
+    fn options(text: &str) -> Vec<String> {
+        let (rest, config) = KConfig::parse(text).unwrap();
+        assert_eq!(rest.trim(), "", "not parsed");
+        config.entries.iter().filter_map(|entry| match entry {
+            Entry::Option(opt) => Some(describe(opt)),
+            _                  => None,
+        }).collect()
+    }
+
+    // What matters about an option for the round trip: its type, if written, and its defaults
+    fn describe(opt: &KOption) -> String {
+        let defaults: Vec<String> = opt.default_order.iter().zip(opt.all_defaults())
+            .map(|(kind, def)| format!("{:?} {}", kind, def))
+            .collect();
+        let option_type = if opt.typed { opt.option_type.to_string() } else { "untyped".to_string() };
+        format!("{} {}: {}", opt.name.name, option_type, defaults.join(", "))
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = "
+config A
+	tristate \"a\"
+	default n if B
+	def_bool y if C
+	default m
+	def_tristate y
+
+config A
+	prompt \"again\"
+	default y if D
+";
+        let (_, config) = KConfig::parse(text).unwrap();
+        let written = config.to_string();
+        assert_eq!(options(text), [
+            "A tristate: Default n if B, DefBool y if C, Default m, DefTristate y",
+            "A untyped: Default y if D",
+        ]);
+        assert_eq!(options(&written), options(text));
+        assert!(written.contains("\tprompt \"again\"\n"));
+        assert!(!written.contains("int"));
+    }
+}
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 mod kconfig;
 //mod json;
 
@@ -8,10 +9,13 @@ use std::fmt::{
 };
 
 use crate::kconfig::{
+    Annotation,
     Block,
+    CompareOp,
     ConstantSymbol,
     Dependency,
-    Expression,
+    Entry,
+    Expr,
     Help,
     Hex,
     Int,
@@ -20,11 +24,14 @@ use crate::kconfig::{
     KConfig,
     KMenu,
     KOption,
+    KSource,
     OptionType,
     Prompt,
     Range,
     RangeType,
     Symbol,
+    Tristate,
+    VarType,
 };
 
 trait DisplayKConfig {
@@ -41,6 +48,18 @@ impl Display for KConfig<'_> {
     }
 }
 
+impl Display for Entry<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.display_kconfig())
+    }
+}
+
+impl Display for Annotation<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.display_kconfig())
+    }
+}
+
 impl Display for OptionType {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(f, "{}", self.display_kconfig())
@@ -59,7 +78,25 @@ impl Display for ConstantSymbol<'_> {
     }
 }
 
-impl Display for Expression<'_> {
+impl Display for Expr<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.display_kconfig())
+    }
+}
+
+impl Display for VarType<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.display_kconfig())
+    }
+}
+
+impl Display for CompareOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.display_kconfig())
+    }
+}
+
+impl Display for Tristate {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(f, "{}", self.display_kconfig())
     }
@@ -119,6 +156,12 @@ impl Display for KOption<'_> {
     }
 }
 
+impl Display for KSource<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.display_kconfig())
+    }
+}
+
 impl Display for KMenu<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(f, "{}", self.display_kconfig())
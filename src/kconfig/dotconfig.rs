@@ -0,0 +1,531 @@
+use super::{
+    Diagnostic,
+    Engine,
+    MenuItem,
+    OptionType,
+    Severity,
+    Tristate,
+    Value,
+    depends_expr,
+    eval_opt,
+};
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+pub const PREFIX: &str = "CONFIG_";
+
+// The assignments of a `.config` file, in the order they appear. Values are kept as written and
+// only checked against the type of their symbol when applied.
+#[derive(Debug, Default)]
+pub struct DotConfig {
+    pub path:        PathBuf,
+    pub assignments: Vec<Assignment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    // Without the `CONFIG_` prefix
+    pub name:  String,
+
+    // Everything after the `=`, quotes included. `None` for `# CONFIG_FOO is not set`.
+    pub value: Option<String>,
+    pub line:  usize,
+}
+
+impl Assignment {
+    // The value as a symbol of the given type reads it, `None` when it is not valid for the type
+    pub fn typed(&self, option_type: OptionType) -> Option<Value> {
+        let Some(text) = &self.value else {
+            return match option_type {
+                OptionType::Bool | OptionType::Tristate => Some(Value::Tristate(Tristate::No)),
+                _                                       => None,
+            };
+        };
+        match option_type {
+            OptionType::Bool     => Tristate::from_name(text).filter(|t| *t != Tristate::Module).map(Value::Tristate),
+            OptionType::Tristate => Tristate::from_name(text).map(Value::Tristate),
+            OptionType::Str      => unquote(text).map(Value::Str),
+            OptionType::Int | OptionType::Hex => {
+                valid_number(text, option_type).then(|| Value::Str(text.clone()))
+            },
+        }
+    }
+}
+
+impl DotConfig {
+    pub fn load(path: &Path) -> io::Result<(DotConfig, Vec<Diagnostic>)> {
+        let content = fs::read_to_string(path)?;
+        Ok(Self::parse(path, &content))
+    }
+
+    // Anything that is neither an assignment, a `# CONFIG_FOO is not set` line, a comment or blank
+    // is reported and skipped
+    pub fn parse(path: &Path, content: &str) -> (DotConfig, Vec<Diagnostic>) {
+        let mut config = DotConfig {
+            path:        path.to_path_buf(),
+            assignments: vec![],
+        };
+        let mut diagnostics = vec![];
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('#') {
+                let unset = comment.trim_start().strip_prefix(PREFIX)
+                    .and_then(|rest| rest.strip_suffix(" is not set"))
+                    .filter(|name| is_symbol(name));
+                if let Some(name) = unset {
+                    config.assignments.push(Assignment { name: name.to_string(), value: None, line: idx + 1 });
+                }
+                continue;
+            }
+            let assignment = line.strip_prefix(PREFIX)
+                .and_then(|rest| rest.split_once('='))
+                .filter(|(name, _)| is_symbol(name));
+            match assignment {
+                Some((name, value)) => config.assignments.push(Assignment {
+                    name:  name.to_string(),
+                    value: Some(value.to_string()),
+                    line:  idx + 1,
+                }),
+                None => diagnostics.push(config.diagnostic(idx + 1, format!("unexpected data: `{}`", line))),
+            }
+        }
+        (config, diagnostics)
+    }
+
+    // The last value given to a symbol
+    pub fn get(&self, name: &str) -> Option<&Assignment> {
+        self.assignments.iter().rev().find(|a| a.name == name)
+    }
+
+    pub fn diagnostic(&self, line: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            file:     self.path.clone(),
+            line,
+            column:   1,
+            message,
+            notes:    vec![],
+        }
+    }
+
+    // Hand every assignment to the engine as a user value. Unknown symbols, values that do not
+    // suit the symbol's type and symbols assigned twice are reported, the same as the kernel
+    // warns about them. The last of several assignments wins.
+    pub fn apply(&self, engine: &mut Engine) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        let mut seen = HashSet::new();
+        for assignment in &self.assignments {
+            let name = assignment.name.as_str();
+            let Some(option_type) = engine.symbols.get(name).and_then(|info| info.option_type) else {
+                diagnostics.push(self.diagnostic(assignment.line, format!("unknown symbol `{}`", name)));
+                continue;
+            };
+            match assignment.typed(option_type) {
+                Some(value) => {
+                    if !seen.insert(name) {
+                        diagnostics.push(self.diagnostic(assignment.line, format!("override: reassigning to symbol `{}`", name)));
+                    }
+                    // As in the kernel the last value of a choice set to `y` is the one selected
+                    let choice = engine.symbols.get(name).and_then(|info| engine.choice_of(info));
+                    if let (Some(choice), Value::Tristate(Tristate::Yes)) = (choice, &value) {
+                        for other in engine.choice_values(choice) {
+                            if other != name && engine.user_value(other) == Some(&Value::Tristate(Tristate::Yes)) {
+                                engine.set(other, Value::Tristate(Tristate::No));
+                            }
+                        }
+                    }
+                    engine.set(name, value);
+                },
+                // `# CONFIG_FOO is not set` means nothing for strings and numbers
+                None if assignment.value.is_none() => {},
+                None => diagnostics.push(self.diagnostic(assignment.line, format!("value `{}` invalid for {} symbol `{}`",
+                    assignment.value.as_deref().unwrap_or(""), option_type, name))),
+            }
+        }
+        diagnostics
+    }
+}
+
+fn is_symbol(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+// A string in double quotes, where a backslash escapes the character after it
+fn unquote(text: &str) -> Option<String> {
+    let mut chars = text.strip_prefix('"')?.chars();
+    let mut out = String::new();
+    loop {
+        match chars.next()? {
+            '"'  => return chars.as_str().is_empty().then_some(out),
+            '\\' => out.push(chars.next()?),
+            c    => out.push(c),
+        }
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    out
+}
+
+// Mirrors `sym_string_valid()`: an optional `-` and decimal digits for int, hex digits with an
+// optional `0x` for hex
+pub(super) fn valid_number(text: &str, option_type: OptionType) -> bool {
+    let digits = match option_type {
+        OptionType::Hex => text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text),
+        _               => text.strip_prefix('-').unwrap_or(text),
+    };
+    let radix = if option_type == OptionType::Hex { 16 } else { 10 };
+    !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix))
+}
+
+// A symbol's line in `.config`
+pub fn assignment_line(name: &str, option_type: OptionType, value: &Value) -> String {
+    match (option_type, value) {
+        (_, Value::Tristate(Tristate::No)) => format!("# {}{} is not set", PREFIX, name),
+        (OptionType::Str, Value::Str(s))   => format!("{}{}={}", PREFIX, name, escape(s)),
+        (_, value)                         => format!("{}{}={}", PREFIX, name, value.as_str()),
+    }
+}
+
+// Write the configuration in exactly the format `make` does. Symbols are listed in menu order,
+// each visible menu and comment gets a header and every visible menu an `# end of` line.
+pub fn write_dotconfig(engine: &Engine) -> String {
+    let title = engine.tree.mainmenu.map_or("Main menu", |prompt| prompt.text.as_str());
+    let mut writer = Writer {
+        engine,
+        out:          format!("#\n# Automatically generated file; DO NOT EDIT.\n# {}\n#\n", title),
+        written:      HashSet::new(),
+        need_newline: false,
+    };
+    for &idx in &engine.tree.roots {
+        writer.node(idx);
+    }
+    writer.out
+}
+
+struct Writer<'e, 't, 'k, 'a> {
+    engine:       &'e Engine<'t, 'k, 'a>,
+    out:          String,
+
+    // Symbols with several definitions are written at the first of them
+    written:      HashSet<&'a str>,
+
+    // Set after an `# end of` line, so the next symbol is separated from it
+    need_newline: bool,
+}
+
+impl Writer<'_, '_, '_, '_> {
+    fn node(&mut self, idx: usize) {
+        let engine = self.engine;
+        let node = &engine.tree.nodes[idx];
+        let header = match node.item {
+            MenuItem::Menu(_) | MenuItem::Comment(_) if self.visible(idx) => node.item.prompt(),
+            _                                                           => None,
+        };
+        if let Some(prompt) = header {
+            self.out.push_str(&format!("\n#\n# {}\n#\n", prompt));
+            self.need_newline = false;
+        }
+        if let MenuItem::Option(opt) = node.item {
+            let name = opt.name.name;
+            if !self.written.contains(name) && engine.is_written(name) {
+                if self.need_newline {
+                    self.out.push('\n');
+                    self.need_newline = false;
+                }
+                self.written.insert(name);
+                let option_type = engine.symbols.get(name).and_then(|info| info.option_type);
+                if let (Some(option_type), Some(value)) = (option_type, engine.value(name)) {
+                    self.out.push_str(&assignment_line(name, option_type, &value));
+                    self.out.push('\n');
+                }
+            }
+        }
+        for &child in &node.children {
+            self.node(child);
+        }
+        if let (MenuItem::Menu(_), Some(prompt)) = (node.item, header) {
+            self.out.push_str(&format!("# end of {}\n", prompt));
+            self.need_newline = true;
+        }
+    }
+
+    // Whether a menu or comment gets a header. The `visible if` of the menus around it does not
+    // count, the kernel only applies that to the prompts of configs.
+    fn visible(&self, idx: usize) -> bool {
+        let engine = self.engine;
+        let own = match engine.tree.nodes[idx].item {
+            MenuItem::Menu(menu) => eval_opt(depends_expr(&menu.visible).as_ref(), engine),
+            _                    => Tristate::Yes,
+        };
+        eval_opt(engine.tree.visibility(idx).as_ref(), engine).and(own) != Tristate::No
+    }
+}
+
+// The smallest configuration that gives the same result, as `make savedefconfig` writes it. Only
+// symbols the user can change and whose value differs from what they would get anyway are kept,
+// in menu order and without any headers. The selected value of a choice is left out when the
+// choice would pick it by default, unless the choice is optional.
+pub fn write_defconfig(engine: &Engine) -> String {
+    fn node<'a>(engine: &Engine<'_, '_, 'a>, idx: usize, written: &mut HashSet<&'a str>, out: &mut String) {
+        let tree = engine.tree;
+        let current = &tree.nodes[idx];
+        if let MenuItem::Option(opt) = current.item {
+            let name = opt.name.name;
+            if engine.is_written(name) && written.insert(name) && needed(engine, name, current.parent) {
+                let option_type = engine.symbols.get(name).and_then(|info| info.option_type);
+                if let (Some(option_type), Some(value)) = (option_type, engine.value(name)) {
+                    out.push_str(&assignment_line(name, option_type, &value));
+                    out.push('\n');
+                }
+            }
+        }
+        for &child in &current.children {
+            node(engine, child, written, out);
+        }
+    }
+
+    fn needed(engine: &Engine, name: &str, parent: Option<usize>) -> bool {
+        if !engine.is_changeable(name) || engine.value(name) == engine.default_value(name) {
+            return false;
+        }
+        // An optional choice selects nothing by default, so its selected value is always kept
+        match parent.map(|parent| (parent, engine.tree.nodes[parent].item)) {
+            Some((choice, MenuItem::Choice(kchoice))) if !kchoice.optional => {
+                let bool = engine.symbols.get(name).and_then(|info| info.option_type) == Some(OptionType::Bool);
+                let yes = engine.value(name) == Some(Value::Tristate(Tristate::Yes));
+                !(bool && yes && engine.choice_default(choice) == Some(name))
+            },
+            _ => true,
+        }
+    }
+
+    let mut out = String::new();
+    let mut written = HashSet::new();
+    for &idx in &engine.tree.roots {
+        node(engine, idx, &mut written, &mut out);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::engine::tests::with_engine;
+
+    const KCONFIG: &str = "
+config MODULES
+	bool \"modules\"
+	default y
+
+config A
+	tristate \"a\"
+	default y
+
+config B
+	bool \"b\"
+	select C
+
+config C
+	tristate \"c\"
+
+config NAME
+	string \"name\"
+	default \"none\"
+
+config COUNT
+	int \"count\"
+	default 4
+
+config BASE
+	hex \"base\"
+	default 0x1000
+
+choice
+	prompt \"required\"
+	default SECOND
+
+config FIRST
+	bool \"first\"
+
+config SECOND
+	bool \"second\"
+
+endchoice
+
+choice
+	prompt \"optional\"
+	optional
+
+config THIRD
+	bool \"third\"
+
+config FOURTH
+	bool \"fourth\"
+
+endchoice
+";
+
+    // The `.config` that `olddefconfig` makes out of the defconfig written for `user`
+    fn round_trip(user: &[(&str, Value)]) -> (String, String, String) {
+        with_engine(KCONFIG, |engine| {
+            for (name, value) in user {
+                engine.set(name, value.clone());
+            }
+            let defconfig = write_defconfig(engine);
+            let mut fresh = Engine::new(engine.tree, engine.symbols);
+            let (loaded, diagnostics) = DotConfig::parse(Path::new("defconfig"), &defconfig);
+            assert!(diagnostics.is_empty());
+            assert!(loaded.apply(&mut fresh).is_empty());
+            (defconfig, write_dotconfig(engine), write_dotconfig(&fresh))
+        })
+    }
+
+    #[test]
+    fn defaults_are_left_out() {
+        let (defconfig, before, after) = round_trip(&[]);
+        assert_eq!(defconfig, "");
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn savedefconfig_then_olddefconfig() {
+        let y = Value::Tristate(Tristate::Yes);
+        let n = Value::Tristate(Tristate::No);
+        let (defconfig, before, after) = round_trip(&[
+            ("A",      Value::Tristate(Tristate::Module)),
+            ("B",      y.clone()),
+            ("NAME",   Value::Str("a \"b\"".to_string())),
+            ("FIRST",  n.clone()),
+            ("SECOND", y.clone()),
+            ("THIRD",  y),
+            ("FOURTH", n),
+        ]);
+        // `C` is selected and `SECOND` is the default of its choice, `THIRD` is kept even though
+        // it comes first, an optional choice selects nothing on its own
+        assert_eq!(defconfig, "CONFIG_A=m\nCONFIG_B=y\nCONFIG_NAME=\"a \\\"b\\\"\"\nCONFIG_THIRD=y\n");
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn reader_diagnostics() {
+        let text = "\
+# a comment
+CONFIG_A=m
+CONFIG_GONE=y
+# CONFIG_GONE_TOO is not set
+CONFIG_B=m
+CONFIG_A=y
+CONFIG_NAME=unquoted
+CONFIG_NAME=\"open
+CONFIG_COUNT=0x10
+CONFIG_COUNT=-12
+CONFIG_BASE=0xfg
+CONFIG_BASE=ff
+# CONFIG_COUNT is not set
+CONFIG_lower-case=y
+just text
+";
+        let (config, diagnostics) = DotConfig::parse(Path::new(".config"), text);
+        let text: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(text, [
+            ".config:14:1: warning: unexpected data: `CONFIG_lower-case=y`",
+            ".config:15:1: warning: unexpected data: `just text`",
+        ]);
+        assert_eq!(config.get("COUNT").unwrap().value, None);
+
+        with_engine(KCONFIG, |engine| {
+            let text: Vec<String> = config.apply(engine).iter().map(|d| d.to_string()).collect();
+            assert_eq!(text, [
+                ".config:3:1: warning: unknown symbol `GONE`",
+                ".config:4:1: warning: unknown symbol `GONE_TOO`",
+                ".config:5:1: warning: value `m` invalid for bool symbol `B`",
+                ".config:6:1: warning: override: reassigning to symbol `A`",
+                ".config:7:1: warning: value `unquoted` invalid for string symbol `NAME`",
+                ".config:8:1: warning: value `\"open` invalid for string symbol `NAME`",
+                ".config:9:1: warning: value `0x10` invalid for int symbol `COUNT`",
+                ".config:11:1: warning: value `0xfg` invalid for hex symbol `BASE`",
+            ]);
+            // Valid values are kept, the rest leave the symbol at its default
+            assert_eq!(engine.value("A").unwrap().tristate(), Tristate::Yes);
+            assert_eq!(engine.value("B").unwrap().tristate(), Tristate::No);
+            assert_eq!(engine.value("NAME").unwrap().as_str(), "none");
+            assert_eq!(engine.value("COUNT").unwrap().as_str(), "-12");
+            assert_eq!(engine.value("BASE").unwrap().as_str(), "ff");
+        });
+    }
+
+    #[test]
+    fn zero_numbers_left_out() {
+        let kconfig = "
+config COUNT
+	int \"count\"
+
+config BASE
+	hex \"base\"
+
+config MORE
+	int \"more\"
+";
+        // Zero is what a number without a default gets anyway
+        let defconfig = with_engine(kconfig, |engine| {
+            engine.set("COUNT", Value::Str("0".to_string()));
+            engine.set("BASE", Value::Str("0x0".to_string()));
+            engine.set("MORE", Value::Str("3".to_string()));
+            write_defconfig(engine)
+        });
+        assert_eq!(defconfig, "CONFIG_MORE=3\n");
+    }
+
+    #[test]
+    fn headers_under_visible_if() {
+        let kconfig = "
+menu \"outer\"
+	visible if HIDE
+
+comment \"note\"
+
+menu \"inner\"
+
+config A
+	bool \"a\"
+	default y
+
+endmenu
+
+endmenu
+";
+        // The outer menu's own `visible if` hides its header, but not those inside it
+        let text = with_engine(kconfig, |engine| write_dotconfig(engine));
+        let body = text.split_once("#\n\n").unwrap().1;
+        assert_eq!(body, "\
+#
+# note
+#
+
+#
+# inner
+#
+CONFIG_A=y
+# end of inner
+");
+    }
+}
@@ -0,0 +1,754 @@
+use super::{
+    Dependency,
+    Env,
+    Expr,
+    MenuItem,
+    MenuTree,
+    OptionType,
+    Property,
+    RangeType,
+    SymbolInfo,
+    SymbolTable,
+    Tristate,
+    Value,
+    VarType,
+    depends_expr,
+    eval_opt,
+    eval::operand,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// Calculates the value of every symbol the way the kernel's `sym_calc_value()` does, from the
+// values given by the user (usually read from `.config`) and the properties of each symbol:
+//
+//  * the user can only change a symbol while one of its prompts is visible, and a tristate can
+//    not be set higher than the condition of that prompt
+//  * without a user value the first `default` whose condition holds is used, and `n` or an empty
+//    string without one
+//  * `select` raises a bool or tristate to at least the value of the selecting symbol, whether
+//    its own dependencies hold or not
+//  * `imply` raises the default the same way, but only within the symbol's dependencies, and not
+//    when the user gave a value
+//  * int and hex values are clamped into the first `range` whose condition holds
+//  * of the values of a choice, only the selected one is `y`
+//
+// Values are calculated when first asked for and cached. Setting a user value drops the cache.
+#[derive(Debug)]
+pub struct Engine<'t, 'k, 'a> {
+    pub tree:    &'t MenuTree<'k, 'a>,
+    pub symbols: &'t SymbolTable<'k, 'a>,
+
+    user:        HashMap<String, Value>,
+
+    // For each symbol, the `select` and `imply` properties of other symbols that name it
+    selected_by: HashMap<&'a str, Vec<Reverse<'t, 'a>>>,
+    implied_by:  HashMap<&'a str, Vec<Reverse<'t, 'a>>>,
+
+    values:      RefCell<HashMap<&'a str, Slot>>,
+    choices:     RefCell<HashMap<usize, Option<Selection<'a>>>>,
+}
+
+// A `select` or `imply` seen from the symbol it names
+#[derive(Debug)]
+pub struct Reverse<'t, 'a> {
+    pub symbol:    &'a str,
+
+    // The dependencies of the definition the property is part of, and its own `if`
+    pub dep:       Option<&'t Expr<'a>>,
+    pub condition: Option<&'t Expr<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection<'a> {
+    // `y` when exactly one value is selected, `m` when any number of them can be modules and `n`
+    // when the choice is not visible
+    pub mode:     Tristate,
+    pub selected: Option<&'a str>,
+}
+
+#[derive(Debug)]
+enum Slot {
+    Pending,
+
+    // The value, and whether the symbol is written to `.config`
+    Done(Value, bool),
+}
+
+impl<'t, 'k, 'a> Engine<'t, 'k, 'a> {
+    pub fn new(tree: &'t MenuTree<'k, 'a>, symbols: &'t SymbolTable<'k, 'a>) -> Self {
+        let mut selected_by: HashMap<&'a str, Vec<Reverse<'t, 'a>>> = HashMap::new();
+        let mut implied_by: HashMap<&'a str, Vec<Reverse<'t, 'a>>> = HashMap::new();
+        for info in symbols.iter() {
+            for (props, out) in [(&info.selects, &mut selected_by), (&info.implies, &mut implied_by)] {
+                for prop in props {
+                    let Expr::Var(VarType::Symbol(target)) = &prop.value.expression else {
+                        continue;
+                    };
+                    out.entry(target.name).or_default().push(Reverse {
+                        symbol:    info.name,
+                        dep:       info.definitions[prop.definition].dep.as_ref(),
+                        condition: prop.value.condition.as_ref(),
+                    });
+                }
+            }
+        }
+        Self {
+            tree,
+            symbols,
+            user: HashMap::new(),
+            selected_by,
+            implied_by,
+            values:  RefCell::new(HashMap::new()),
+            choices: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.user.insert(name.to_string(), value);
+        self.invalidate();
+    }
+
+    pub fn unset(&mut self, name: &str) -> Option<Value> {
+        let old = self.user.remove(name);
+        self.invalidate();
+        old
+    }
+
+    pub fn user_value(&self, name: &str) -> Option<&Value> {
+        self.user.get(name)
+    }
+
+    fn invalidate(&self) {
+        self.values.borrow_mut().clear();
+        self.choices.borrow_mut().clear();
+    }
+
+    // `None` for symbols that are not defined anywhere
+    pub fn value(&self, name: &str) -> Option<Value> {
+        self.compute(name).map(|(value, _)| value)
+    }
+
+    // Whether the symbol belongs in `.config`: it is visible, `select` or `imply` applies to it,
+    // or it has a default other than `n`. Everything else keeps its value without being written
+    // out.
+    pub fn is_written(&self, name: &str) -> bool {
+        self.compute(name).is_some_and(|(_, write)| write)
+    }
+
+    // The value as `.config` has it. A bool or tristate that is not written out reads as `n`,
+    // anything else is absent.
+    pub fn written_value(&self, name: &str) -> Option<Value> {
+        match self.compute(name)? {
+            (value, true)               => Some(value),
+            (Value::Tristate(_), false) => Some(Value::Tristate(Tristate::No)),
+            (Value::Str(_), false)      => None,
+        }
+    }
+
+    fn compute(&self, name: &str) -> Option<(Value, bool)> {
+        let info = self.symbols.get(name)?;
+        match self.values.borrow().get(info.name) {
+            Some(Slot::Done(value, write)) => return Some((value.clone(), *write)),
+            // The symbol depends on itself, which the kernel refuses to load. Treating the inner
+            // reference as unset keeps the rest of the configuration usable.
+            Some(Slot::Pending)            => return Some((empty(info), false)),
+            None                           => {},
+        }
+        self.values.borrow_mut().insert(info.name, Slot::Pending);
+        let (value, write) = self.calculate(info);
+        self.values.borrow_mut().insert(info.name, Slot::Done(value.clone(), write));
+        Some((value, write))
+    }
+
+    fn calculate(&self, info: &SymbolInfo<'k, 'a>) -> (Value, bool) {
+        let Some(option_type) = info.option_type else {
+            return (empty(info), false);
+        };
+        let visible = self.visibility(info.name);
+        let user = self.user.get(info.name).filter(|_| visible != Tristate::No);
+        let mut write = visible != Tristate::No;
+
+        if matches!(option_type, OptionType::Bool | OptionType::Tristate) {
+            if let (Tristate::Yes, Some(choice)) = (visible, self.choice_of(info)) {
+                let selection = self.selection(choice);
+                if selection.mode == Tristate::Yes {
+                    let picked = selection.selected == Some(info.name);
+                    return (Value::Tristate(if picked { Tristate::Yes } else { Tristate::No }), true);
+                }
+            }
+
+            let mut value = Tristate::No;
+            match user {
+                Some(user) => value = user.tristate().and(visible),
+                None => {
+                    // A default of `n` alone does not get a symbol written, as with `SYMBOL_WRITE`
+                    if let Some((_, default)) = self.active_default(info) {
+                        value = default;
+                        write |= value != Tristate::No;
+                    }
+                    let implied = self.implied(info.name);
+                    if implied != Tristate::No {
+                        value = value.or(implied).and(self.dir_dep(info.name));
+                        write = true;
+                    }
+                },
+            }
+            let rev_dep = self.rev_dep(info.name);
+            if rev_dep != Tristate::No {
+                write = true;
+            }
+            value = self.fix_bool(info, value.or(rev_dep));
+            return (Value::Tristate(value), write);
+        }
+
+        let value = match (user, self.active_default(info)) {
+            (Some(user), _)            => user.as_str().to_string(),
+            (None, Some((default, _))) => {
+                write = true;
+                self.default_text(default)
+            },
+            (None, None)               => String::new(),
+        };
+        (Value::Str(self.clamp(info, option_type, value)), write)
+    }
+
+    // As in the kernel, only a single symbol or constant can be the default of a string or number
+    pub(super) fn default_text(&self, default: &Property<Dependency>) -> String {
+        match &default.value.expression {
+            Expr::Var(var) => operand(var, self).0,
+            _              => String::new(),
+        }
+    }
+
+    fn clamp(&self, info: &SymbolInfo<'k, 'a>, option_type: OptionType, value: String) -> String {
+        let Some((min, max)) = self.active_range(info) else {
+            return value;
+        };
+        match parse_number(&value, option_type) {
+            Some(n) if n < min => format_number(min, option_type),
+            Some(n) if n > max => format_number(max, option_type),
+            _                  => value,
+        }
+    }
+
+    // The value the symbol would have without a user value, everything else staying as it is.
+    // This is the kernel's `sym_get_string_default()`, which `savedefconfig` compares against.
+    pub fn default_value(&self, name: &str) -> Option<Value> {
+        let info = self.symbols.get(name)?;
+        let Some(option_type) = info.option_type else {
+            return Some(empty(info));
+        };
+        let default = self.active_default(info);
+        if !matches!(option_type, OptionType::Bool | OptionType::Tristate) {
+            let value = default.map_or_else(String::new, |(default, _)| self.default_text(default));
+            let value = match (self.clamp(info, option_type, value), option_type) {
+                // Numbers without a default read as zero
+                (value, OptionType::Int) if value.is_empty() => "0".to_string(),
+                (value, OptionType::Hex) if value.is_empty() => "0x0".to_string(),
+                (value, _)                                   => value,
+            };
+            return Some(Value::Str(value));
+        }
+        let value = default.map_or(Tristate::No, |(_, value)| value);
+        let value = self.fix_bool(info, value.or(self.rev_dep(name)));
+        Some(Value::Tristate(value.or(self.implied(name))))
+    }
+
+    // Whether the user has any say in the value, which is not the case while it is invisible or
+    // a `select` already forces it as high as its prompt allows
+    pub fn is_changeable(&self, name: &str) -> bool {
+        self.visibility(name) > self.rev_dep(name)
+    }
+
+    // A tristate is treated as a bool while modules are disabled, so `m` turns into `y`
+    fn fix_bool(&self, info: &SymbolInfo, value: Tristate) -> Tristate {
+        let tristate = info.option_type == Some(OptionType::Tristate) && self.modules() != Tristate::No;
+        if value == Tristate::Module && !tristate { Tristate::Yes } else { value }
+    }
+
+    fn cond(&self, dep: Option<&Expr>, condition: Option<&Expr>) -> Tristate {
+        eval_opt(dep, self).and(eval_opt(condition, self))
+    }
+
+    // `visible if` on the menus around a node hides the prompts inside them
+    pub fn menu_visible(&self, idx: usize) -> Tristate {
+        let mut visible = Tristate::Yes;
+        let mut current = self.tree.nodes[idx].parent;
+        while let Some(parent) = current {
+            if let MenuItem::Menu(menu) = self.tree.nodes[parent].item {
+                visible = visible.and(eval_opt(depends_expr(&menu.visible).as_ref(), self));
+            }
+            current = self.tree.nodes[parent].parent;
+        }
+        visible
+    }
+
+    // The highest value the user can give the symbol, `n` when none of its prompts is visible
+    pub fn visibility(&self, name: &str) -> Tristate {
+        let Some(info) = self.symbols.get(name) else {
+            return Tristate::No;
+        };
+        let visible = self.prompt_visibility(info);
+        match self.choice_of(info) {
+            Some(choice) => visible.and(self.selection(choice).mode),
+            None         => visible,
+        }
+    }
+
+    fn prompt_visibility(&self, info: &SymbolInfo) -> Tristate {
+        let visible = info.prompts.iter().fold(Tristate::No, |acc, prompt| {
+            let def = &info.definitions[prompt.definition];
+            acc.or(self.cond(def.dep.as_ref(), prompt.value.condition.as_ref()).and(self.menu_visible(def.node)))
+        });
+        let tristate = info.option_type == Some(OptionType::Tristate) && self.modules() != Tristate::No;
+        if visible == Tristate::Module && !tristate { Tristate::Yes } else { visible }
+    }
+
+    // The `||` of the dependencies of all definitions
+    pub fn dir_dep(&self, name: &str) -> Tristate {
+        let Some(info) = self.symbols.get(name) else {
+            return Tristate::No;
+        };
+        self.fix_bool(info, eval_opt(info.dir_dep.as_ref(), self))
+    }
+
+    // The strongest `select` of the symbol whose condition holds
+    pub fn rev_dep(&self, name: &str) -> Tristate {
+        self.reverse(name, self.selected_by.get(name))
+    }
+
+    // The strongest `imply` of the symbol whose condition holds
+    pub fn implied(&self, name: &str) -> Tristate {
+        self.reverse(name, self.implied_by.get(name))
+    }
+
+    fn reverse(&self, name: &str, by: Option<&Vec<Reverse<'t, 'a>>>) -> Tristate {
+        let Some(info) = self.symbols.get(name) else {
+            return Tristate::No;
+        };
+        let value = by.into_iter().flatten().fold(Tristate::No, |acc, rev| {
+            let from = self.value(rev.symbol).map_or(Tristate::No, |v| v.tristate());
+            acc.or(from.and(self.cond(rev.dep, rev.condition)))
+        });
+        self.fix_bool(info, value)
+    }
+
+    pub fn selected_by(&self, name: &str) -> &[Reverse<'t, 'a>] {
+        self.selected_by.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn implied_by(&self, name: &str) -> &[Reverse<'t, 'a>] {
+        self.implied_by.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    // The first default whose condition holds, with its value as a bool or tristate limited by
+    // that condition
+    pub fn active_default<'i>(&self, info: &'i SymbolInfo<'k, 'a>) -> Option<(&'i Property<'k, Dependency<'a>>, Tristate)> {
+        info.defaults.iter().find_map(|default| {
+            let def = &info.definitions[default.definition];
+            match self.cond(def.dep.as_ref(), default.value.condition.as_ref()) {
+                Tristate::No => None,
+                cond         => Some((default, default.value.expression.eval_value(self).and(cond))),
+            }
+        })
+    }
+
+    // The bounds of the first range whose condition holds
+    pub fn active_range(&self, info: &SymbolInfo<'k, 'a>) -> Option<(i128, i128)> {
+        let option_type = info.option_type?;
+        let range = info.ranges.iter().find(|range| {
+            let def = &info.definitions[range.definition];
+            self.cond(def.dep.as_ref(), range.value.condition.as_ref()) != Tristate::No
+        })?;
+        let bound = |bound: &RangeType| match bound {
+            RangeType::Int(i)    => Some(i.val),
+            RangeType::Hex(h)    => i128::try_from(h.val).ok(),
+            RangeType::Symbol(s) => {
+                let text = self.value(s.name).map_or_else(|| s.name.to_string(), |v| v.as_str().to_string());
+                parse_number(&text, option_type)
+            },
+        };
+        Some((bound(&range.value.start)?, bound(&range.value.end)?))
+    }
+
+    // The node of the choice the symbol is a value of
+    pub fn choice_of(&self, info: &SymbolInfo) -> Option<usize> {
+        info.definitions.iter()
+            .find(|def| def.choice.is_some())
+            .and_then(|def| self.tree.nodes[def.node].parent)
+    }
+
+    // Which value of a choice is `y`. This is the one the user set to `y`, or else the first
+    // visible symbol named by an active `default` of the choice, or else the first visible value.
+    pub fn selection(&self, choice: usize) -> Selection<'a> {
+        match self.choices.borrow().get(&choice) {
+            Some(Some(selection)) => return *selection,
+            Some(None)            => return Selection { mode: Tristate::No, selected: None },
+            None                  => {},
+        }
+        self.choices.borrow_mut().insert(choice, None);
+        let selection = self.select(choice);
+        self.choices.borrow_mut().insert(choice, Some(selection));
+        selection
+    }
+
+    fn select(&self, choice: usize) -> Selection<'a> {
+        let node = &self.tree.nodes[choice];
+        let MenuItem::Choice(kchoice) = node.item else {
+            return Selection { mode: Tristate::No, selected: None };
+        };
+        let condition = kchoice.prompt.as_ref().and_then(|p| p.condition.as_ref());
+        let mut mode = self.cond(node.dep.as_ref(), condition).and(self.menu_visible(choice));
+        let tristate = kchoice.option_type == OptionType::Tristate && self.modules() != Tristate::No;
+        if mode == Tristate::Module && !tristate {
+            mode = Tristate::Yes;
+        }
+
+        let values = self.choice_values(choice);
+        let user = |want: Tristate| values.iter().copied()
+            .find(|name| self.user.get(*name).is_some_and(|v| v.tristate() == want));
+
+        // A tristate choice holds modules when the user only made modules of its values
+        if mode == Tristate::Yes && tristate && user(Tristate::Yes).is_none() && user(Tristate::Module).is_some() {
+            mode = Tristate::Module;
+        }
+        if mode != Tristate::Yes {
+            return Selection { mode, selected: None };
+        }
+
+        // An optional choice has nothing selected until the user picks something
+        let selected = match user(Tristate::Yes) {
+            Some(name)               => Some(name),
+            None if kchoice.optional => None,
+            None                     => self.choice_default(choice),
+        };
+        Selection { mode, selected }
+    }
+
+    // The values of a choice that have a visible prompt, in the order they are listed
+    pub fn choice_values(&self, choice: usize) -> Vec<&'a str> {
+        self.tree.nodes[choice].children.iter()
+            .filter_map(|&child| match self.tree.nodes[child].item {
+                MenuItem::Option(opt) => Some(opt.name.name),
+                _                     => None,
+            })
+            .filter(|name| self.symbols.get(name).is_some_and(|info| self.prompt_visibility(info) != Tristate::No))
+            .collect()
+    }
+
+    // The value a choice selects when the user has not picked one
+    pub fn choice_default(&self, choice: usize) -> Option<&'a str> {
+        let MenuItem::Choice(kchoice) = self.tree.nodes[choice].item else {
+            return None;
+        };
+        let values = self.choice_values(choice);
+        kchoice.defaults.iter().flatten()
+            .filter(|default| eval_opt(default.condition.as_ref(), self) != Tristate::No)
+            .find_map(|default| match &default.expression {
+                Expr::Var(VarType::Symbol(s)) => values.iter().copied().find(|name| *name == s.name),
+                _                             => None,
+            })
+            .or_else(|| values.first().copied())
+    }
+}
+
+impl Env for Engine<'_, '_, '_> {
+    fn value(&self, name: &str) -> Option<Value> {
+        Engine::value(self, name)
+    }
+
+    fn option_type(&self, name: &str) -> Option<OptionType> {
+        self.symbols.get(name).and_then(|info| info.option_type)
+    }
+}
+
+// The value of a symbol nothing applies to. Symbols without a type get their own name, as in the
+// kernel.
+fn empty(info: &SymbolInfo) -> Value {
+    match info.option_type {
+        Some(OptionType::Bool | OptionType::Tristate) => Value::Tristate(Tristate::No),
+        Some(_)                                       => Value::Str(String::new()),
+        None                                          => Value::Str(info.name.to_string()),
+    }
+}
+
+pub fn parse_number(text: &str, option_type: OptionType) -> Option<i128> {
+    match option_type {
+        OptionType::Hex => {
+            let digits = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text);
+            i128::from_str_radix(digits, 16).ok()
+        },
+        _ => text.parse().ok(),
+    }
+}
+
+pub fn format_number(n: i128, option_type: OptionType) -> String {
+    match option_type {
+        OptionType::Hex => format!("0x{:x}", n),
+        _               => n.to_string(),
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use super::super::{
+        KConfig,
+        write_dotconfig,
+    };
+
+    // Runs `f` on an engine for a Kconfig file given as text, with no user values set
+    pub(in super::super) fn with_engine<R>(text: &str, f: impl FnOnce(&mut Engine) -> R) -> R {
+        let (rest, config) = KConfig::parse(text).unwrap();
+        assert_eq!(rest.trim(), "", "not parsed");
+        let tree = MenuTree::build(&config);
+        let symbols = SymbolTable::build(&tree);
+        f(&mut Engine::new(&tree, &symbols))
+    }
+
+    fn tri(engine: &Engine, name: &str) -> Tristate {
+        engine.value(name).unwrap().tristate()
+    }
+
+    fn text(engine: &Engine, name: &str) -> String {
+        engine.value(name).unwrap().as_str().to_string()
+    }
+
+    #[test]
+    fn defaults() {
+        let kconfig = "
+config MODULES
+	bool \"modules\"
+	default y
+
+config A
+	tristate \"a\"
+	default m if B
+	default y
+
+config B
+	bool
+
+config C
+	tristate
+	default A
+
+config S
+	string \"s\"
+	default \"x\" if B
+	default \"y\\\"z\"
+";
+        with_engine(kconfig, |engine| {
+            assert_eq!(tri(engine, "A"), Tristate::Yes);
+            assert_eq!(tri(engine, "C"), Tristate::Yes);
+            assert_eq!(text(engine, "S"), "y\"z");
+
+            engine.set("B", Value::Tristate(Tristate::Yes));
+            // `B` has no prompt, so its user value is ignored
+            assert_eq!(tri(engine, "A"), Tristate::Yes);
+
+            engine.set("A", Value::Tristate(Tristate::Module));
+            assert_eq!(tri(engine, "A"), Tristate::Module);
+            assert_eq!(tri(engine, "C"), Tristate::Module);
+
+            // Without modules a tristate is a bool
+            engine.set("MODULES", Value::Tristate(Tristate::No));
+            assert_eq!(tri(engine, "A"), Tristate::Yes);
+        });
+    }
+
+    // `m` is only rewritten to `m && MODULES` in conditions, a default of `m` turns into `y`
+    #[test]
+    fn default_m_without_modules() {
+        let kconfig = "
+config MODULES
+	bool \"modules\"
+
+config DM
+	tristate
+	default m
+
+config DMP
+	tristate \"dmp\"
+	default m
+
+config OFF
+	tristate \"off\"
+	default y if m
+";
+        with_engine(kconfig, |engine| {
+            assert_eq!(tri(engine, "DM"), Tristate::Yes);
+            assert_eq!(tri(engine, "DMP"), Tristate::Yes);
+            assert_eq!(tri(engine, "OFF"), Tristate::No);
+            assert_eq!(engine.default_value("DMP"), Some(Value::Tristate(Tristate::Yes)));
+            assert!(write_dotconfig(engine).ends_with("CONFIG_DM=y\nCONFIG_DMP=y\n# CONFIG_OFF is not set\n"));
+
+            engine.set("MODULES", Value::Tristate(Tristate::Yes));
+            assert_eq!(tri(engine, "DM"), Tristate::Module);
+            assert_eq!(tri(engine, "OFF"), Tristate::Module);
+        });
+    }
+
+    #[test]
+    fn select_and_imply() {
+        let kconfig = "
+config MODULES
+	bool \"modules\"
+	default y
+
+config DEP
+	bool \"dep\"
+
+config SEL
+	tristate \"sel\"
+	select TARGET
+	imply SOFT
+
+config TARGET
+	tristate \"target\"
+	depends on DEP
+
+config SOFT
+	tristate \"soft\"
+	depends on DEP
+";
+        with_engine(kconfig, |engine| {
+            engine.set("SEL", Value::Tristate(Tristate::Module));
+            // A select ignores the dependencies of its target, an imply does not
+            assert_eq!(tri(engine, "TARGET"), Tristate::Module);
+            assert_eq!(tri(engine, "SOFT"), Tristate::No);
+
+            engine.set("DEP", Value::Tristate(Tristate::Yes));
+            assert_eq!(tri(engine, "SOFT"), Tristate::Module);
+
+            // Only `select` sets a lower bound the user can not go below
+            engine.set("TARGET", Value::Tristate(Tristate::No));
+            engine.set("SOFT", Value::Tristate(Tristate::No));
+            assert_eq!(tri(engine, "TARGET"), Tristate::Module);
+            assert_eq!(tri(engine, "SOFT"), Tristate::No);
+            assert!(engine.is_changeable("TARGET"));
+
+            engine.set("SEL", Value::Tristate(Tristate::Yes));
+            assert_eq!(tri(engine, "TARGET"), Tristate::Yes);
+            assert!(!engine.is_changeable("TARGET"));
+        });
+    }
+
+    #[test]
+    fn choice_mode() {
+        let kconfig = "
+config MODULES
+	bool \"modules\"
+	default y
+
+choice
+	tristate \"choice\"
+	default SECOND
+
+config FIRST
+	tristate \"first\"
+
+config SECOND
+	tristate \"second\"
+
+endchoice
+";
+        with_engine(kconfig, |engine| {
+            assert_eq!(tri(engine, "FIRST"), Tristate::No);
+            assert_eq!(tri(engine, "SECOND"), Tristate::Yes);
+
+            engine.set("FIRST", Value::Tristate(Tristate::Yes));
+            assert_eq!(tri(engine, "FIRST"), Tristate::Yes);
+            assert_eq!(tri(engine, "SECOND"), Tristate::No);
+
+            // With only modules picked any number of values can be `m`
+            engine.set("FIRST", Value::Tristate(Tristate::Module));
+            engine.set("SECOND", Value::Tristate(Tristate::Module));
+            assert_eq!(tri(engine, "FIRST"), Tristate::Module);
+            assert_eq!(tri(engine, "SECOND"), Tristate::Module);
+        });
+    }
+
+    #[test]
+    fn range_clamping() {
+        let kconfig = "
+config BIG
+	bool \"big\"
+
+config NUM
+	int \"num\"
+	range 10 20 if !BIG
+	range 10 1000
+	default 5
+
+config ADDR
+	hex \"addr\"
+	range 0x10 0x1f
+	default 0x100
+";
+        with_engine(kconfig, |engine| {
+            assert_eq!(text(engine, "NUM"), "10");
+            assert_eq!(text(engine, "ADDR"), "0x1f");
+
+            engine.set("NUM", Value::Str("500".to_string()));
+            assert_eq!(text(engine, "NUM"), "20");
+            engine.set("BIG", Value::Tristate(Tristate::Yes));
+            assert_eq!(text(engine, "NUM"), "500");
+        });
+    }
+
+    // What the kernel's `conf --olddefconfig` writes for the same file: prompt-less symbols only
+    // show up when something gave them a value other than `n`, or they are not bool or tristate
+    #[test]
+    fn written_like_the_kernel() {
+        let kconfig = "
+config VISIBLE
+	bool \"visible\"
+
+config HIDDEN_N
+	bool
+	default n
+
+config HIDDEN_FALSE
+	tristate
+	default HIDDEN_N
+
+config HIDDEN_OFF
+	bool
+	default y if HIDDEN_N
+
+config HIDDEN_Y
+	bool
+	default y
+
+config HIDDEN_STR
+	string
+	default \"\"
+
+config HIDDEN_INT
+	int
+
+config SELECTED
+	bool
+
+config SELECTOR
+	bool \"selector\"
+	default y
+	select SELECTED
+";
+        let expected = "\
+#
+# Automatically generated file; DO NOT EDIT.
+# Main menu
+#
+# CONFIG_VISIBLE is not set
+CONFIG_HIDDEN_Y=y
+CONFIG_HIDDEN_STR=\"\"
+CONFIG_SELECTED=y
+CONFIG_SELECTOR=y
+";
+        with_engine(kconfig, |engine| assert_eq!(write_dotconfig(engine), expected));
+    }
+}
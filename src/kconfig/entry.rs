@@ -0,0 +1,166 @@
+use super::{
+    Annotation,
+    Block,
+    KChoice,
+    KCommentBlock,
+    KMenu,
+    KOption,
+    KSource,
+    Span,
+};
+
+use nom::{
+    branch::alt,
+    combinator::map,
+    IResult,
+};
+
+// A child of a file, `menu` or `if` block. These are kept in the order they were written, which
+// is the order menuconfig shows them in and the order `.config` is written out in.
+#[derive(Debug)]
+pub enum Entry<'a> {
+    Option(KOption<'a>),
+    Menu(KMenu<'a>),
+    Choice(KChoice<'a>),
+    Block(Block<'a>),
+    Source(KSource<'a>),
+    Comment(KCommentBlock<'a>),
+}
+
+impl<'a> Entry<'a> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        // `KOption` must come before `KMenu`, as `menu` is a prefix of `menuconfig`
+        alt((
+            map(Block::parse,         Entry::Block),
+            map(KSource::parse,       Entry::Source),
+            map(KOption::parse,       Entry::Option),
+            map(KMenu::parse,         Entry::Menu),
+            map(KChoice::parse,       Entry::Choice),
+            map(KCommentBlock::parse, Entry::Comment),
+        ))(input)
+    }
+
+    pub fn span(&self) -> &Span {
+        match self {
+            Entry::Option(v)  => &v.span,
+            Entry::Menu(v)    => &v.span,
+            Entry::Choice(v)  => &v.span,
+            Entry::Block(v)   => &v.span,
+            Entry::Source(v)  => &v.span,
+            Entry::Comment(v) => &v.span,
+        }
+    }
+
+    pub fn annotations(&self) -> &[Annotation<'a>] {
+        match self {
+            Entry::Option(v)  => v.annotations.as_deref(),
+            Entry::Menu(v)    => v.annotations.as_deref(),
+            Entry::Choice(v)  => v.annotations.as_deref(),
+            Entry::Block(v)   => v.annotations.as_deref(),
+            Entry::Source(v)  => v.annotations.as_deref(),
+            Entry::Comment(v) => v.annotations.as_deref(),
+        }.unwrap_or(&[])
+    }
+
+    fn annotations_mut(&mut self) -> &mut Option<Vec<Annotation<'a>>> {
+        match self {
+            Entry::Option(v)  => &mut v.annotations,
+            Entry::Menu(v)    => &mut v.annotations,
+            Entry::Choice(v)  => &mut v.annotations,
+            Entry::Block(v)   => &mut v.annotations,
+            Entry::Source(v)  => &mut v.annotations,
+            Entry::Comment(v) => &mut v.annotations,
+        }
+    }
+
+    // Comments written above the entry, these go before any the entry collected itself
+    pub fn annotate(&mut self, notes: Vec<Annotation<'a>>) {
+        if notes.is_empty() {
+            return;
+        }
+        let slot = self.annotations_mut();
+        let mut all = notes;
+        all.extend(slot.take().into_iter().flatten());
+        *slot = Some(all);
+    }
+
+    // Comments written after the entry with nothing following them
+    pub fn trail(&mut self, notes: Vec<Annotation<'a>>) {
+        if !notes.is_empty() {
+            self.annotations_mut().get_or_insert_with(Vec::new).extend(notes);
+        }
+    }
+
+    pub fn collect_options(&self) -> Vec<&KOption<'a>> {
+        match self {
+            Entry::Option(v)  => vec![v],
+            Entry::Menu(v)    => v.collect_options(),
+            Entry::Choice(v)  => v.collect_options(),
+            Entry::Block(v)   => v.config.collect_options(),
+            Entry::Source(v)  => v.collect_options(),
+            Entry::Comment(_) => vec![],
+        }
+    }
+}
+
+// Comments that no entry followed, each tagged with how many entries came before it. They trail
+// the entry before them, those that come first are handed back for the enclosing node to keep.
+pub fn attach_trailing<'a>(entries: &mut [Entry<'a>], loose: Vec<(usize, Annotation<'a>)>) -> Option<Vec<Annotation<'a>>> {
+    let mut leading = vec![];
+    for (count, note) in loose {
+        match count.checked_sub(1).and_then(|idx| entries.get_mut(idx)) {
+            Some(entry) => entry.trail(vec![note]),
+            None        => leading.push(note),
+        }
+    }
+    if leading.is_empty() { None } else { Some(leading) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::KConfig;
+
+    fn notes<'a>(entry: &Entry<'a>) -> Vec<&'a str> {
+        entry.annotations().iter().map(|note| note.text).collect()
+    }
+
+    #[test]
+    fn comments_attach() {
+        let input = "\
+# top of file
+
+# about a
+config A
+	bool \"a\"
+	# between attributes
+	default y
+
+# about the menu
+menu \"m\"
+
+config B
+	bool \"b\"
+# after b
+
+endmenu
+
+config C
+	bool \"c\"
+# at the end
+";
+        let (rest, config) = KConfig::parse(input).unwrap();
+        assert_eq!(rest.trim(), "");
+        // Comments above an entry come first, then those among its attributes. A comment with
+        // no entry after it trails the one before it, even inside a menu.
+        assert!(config.annotations.is_none());
+        let top: Vec<Vec<&str>> = config.entries.iter().map(notes).collect();
+        assert_eq!(top, [
+            vec!["top of file", "about a", "between attributes"],
+            vec!["about the menu"],
+            vec!["at the end"],
+        ]);
+        let Entry::Menu(menu) = &config.entries[1] else { panic!() };
+        assert_eq!(notes(&menu.entries[0]), ["after b"]);
+    }
+}
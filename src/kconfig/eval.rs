@@ -0,0 +1,301 @@
+use super::{
+    CompareOp,
+    Expr,
+    OptionType,
+    Tristate,
+    VarType,
+};
+
+use std::cmp::Ordering;
+use std::ops;
+
+// The value of a symbol. Everything that is not a bool or tristate is kept as the string written
+// to `.config`, the same as the kernel does, numbers are only parsed when compared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Tristate(Tristate),
+    Str(String),
+}
+
+impl Value {
+    // Strings, ints and hex values count as `n` when used as a condition on their own
+    pub fn tristate(&self) -> Tristate {
+        match self {
+            Value::Tristate(t) => *t,
+            Value::Str(_)      => Tristate::No,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Value::Tristate(t) => t.name(),
+            Value::Str(s)      => s,
+        }
+    }
+}
+
+// What an expression is evaluated against
+pub trait Env {
+    // `None` for symbols that are not defined anywhere
+    fn value(&self, name: &str) -> Option<Value>;
+
+    fn option_type(&self, name: &str) -> Option<OptionType>;
+
+    // With modules disabled a literal `m` in an expression means `n`. The kernel marks the symbol
+    // controlling this with `modules`, which is `MODULES` in every tree.
+    fn modules(&self) -> Tristate {
+        self.value("MODULES").map_or(Tristate::No, |v| v.tristate())
+    }
+}
+
+impl Tristate {
+    pub fn and(self, other: Tristate) -> Tristate {
+        self.min(other)
+    }
+
+    pub fn or(self, other: Tristate) -> Tristate {
+        self.max(other)
+    }
+}
+
+// `!y == n`, `!m == m`, `!n == y`
+impl ops::Not for Tristate {
+    type Output = Tristate;
+
+    fn not(self) -> Tristate {
+        match self {
+            Tristate::No     => Tristate::Yes,
+            Tristate::Module => Tristate::Module,
+            Tristate::Yes    => Tristate::No,
+        }
+    }
+}
+
+// How one side of a comparison is read
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Kind {
+    Signed(i64),
+    Unsigned(u64),
+    Text,
+}
+
+impl<'a> Expr<'a> {
+    // The value of a dependency or an `if` condition
+    pub fn eval<E: Env + ?Sized>(&self, env: &E) -> Tristate {
+        self.calc(env, true)
+    }
+
+    // The value of a `default` itself, which the kernel does not rewrite: `default m` is `m` even
+    // with modules disabled, and only then turned into `y` for a tristate
+    pub fn eval_value<E: Env + ?Sized>(&self, env: &E) -> Tristate {
+        self.calc(env, false)
+    }
+
+    fn calc<E: Env + ?Sized>(&self, env: &E, rewrite: bool) -> Tristate {
+        match self {
+            Expr::Var(v)                => factor(v, env, rewrite),
+            Expr::Not(e)                => !e.calc(env, rewrite),
+            Expr::And(l, r)             => l.calc(env, rewrite).and(r.calc(env, rewrite)),
+            Expr::Or(l, r)              => l.calc(env, rewrite).or(r.calc(env, rewrite)),
+            Expr::Compare(op, lhs, rhs) => compare(*op, lhs, rhs, env),
+        }
+    }
+}
+
+// A missing expression, such as a default without an `if`, is always true
+pub fn eval_opt<E: Env + ?Sized>(expr: Option<&Expr>, env: &E) -> Tristate {
+    expr.map_or(Tristate::Yes, |e| e.eval(env))
+}
+
+fn factor<E: Env + ?Sized>(var: &VarType, env: &E, rewrite: bool) -> Tristate {
+    match var {
+        // The kernel rewrites a literal `m` into `m && MODULES` in dependencies and `if`
+        // conditions only, never in the value of a `default`
+        VarType::Tristate(Tristate::Module) if rewrite => Tristate::Module.and(env.modules()),
+        VarType::Tristate(t)       => *t,
+        VarType::Symbol(s)         => env.value(s.name).map_or(Tristate::No, |v| v.tristate()),
+        // `"y"`, `"m"` and `"n"` are the same symbols as their bare forms, any other string is `n`
+        VarType::ConstantSymbol(c) => match Tristate::from_name(&c.unescaped()) {
+            Some(t) => factor(&VarType::Tristate(t), env, rewrite),
+            None    => Tristate::No,
+        },
+        _                          => Tristate::No,
+    }
+}
+
+// The text of an operand and how it should be parsed when compared. Constants have no type, as
+// in the kernel, so a hex literal is read as a signed C integer rather than as unsigned.
+pub(super) fn operand<E: Env + ?Sized>(var: &VarType, env: &E) -> (String, Option<OptionType>) {
+    match var {
+        VarType::Tristate(t)       => (t.name().to_string(), None),
+        VarType::Hex(h)            => (format!("0x{:x}", h.val), None),
+        VarType::Int(i)            => (i.val.to_string(), None),
+        VarType::ConstantSymbol(c) => (c.unescaped().into_owned(), None),
+        VarType::Macro(m)          => (m.to_string(), None),
+        // An undefined symbol compares as its own name, so `FOO = bar` works without quotes
+        VarType::Symbol(s)         => (
+            env.value(s.name).map_or_else(|| s.name.to_string(), |v| v.as_str().to_string()),
+            env.option_type(s.name),
+        ),
+    }
+}
+
+// `strtoll()` and `strtoull()` that have to use up all of the text. Base 0 picks the base from
+// the prefix, `0x` for hex and `0` for octal, as C does.
+fn parse_number(text: &str, base: u32) -> Option<(bool, u64)> {
+    let text = text.trim_start();
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None       => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let hex = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"));
+    let (digits, radix) = match (base, hex) {
+        (0 | 16, Some(rest))                                 => (rest, 16),
+        (0, None) if text.len() > 1 && text.starts_with('0') => (&text[1..], 8),
+        (0, None)                                            => (text, 10),
+        (base, _)                                            => (text, base),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    Some((negative, u64::from_str_radix(digits, radix).ok()?))
+}
+
+// Out of range values are not numbers, as `strtoll()` fails with `ERANGE` on them
+fn parse_signed(text: &str, base: u32) -> Option<i64> {
+    let (negative, magnitude) = parse_number(text, base)?;
+    let magnitude = magnitude as i128;
+    i64::try_from(if negative { -magnitude } else { magnitude }).ok()
+}
+
+// Mirrors `expr_parse_string()`: bools and tristates compare as 0, 1 and 2, ints as signed
+// decimal, hex as unsigned and anything else as a C integer constant. Text that does not parse
+// as a whole is compared as text.
+fn kind(text: &str, option_type: Option<OptionType>) -> Kind {
+    let parsed = match option_type {
+        Some(OptionType::Bool | OptionType::Tristate) => match Tristate::from_name(text) {
+            Some(t) => Some(Kind::Signed(t as i64)),
+            None    => Some(Kind::Signed(-1)),
+        },
+        Some(OptionType::Int) => parse_signed(text, 10).map(Kind::Signed),
+        // `strtoull()` takes a minus sign and negates the value
+        Some(OptionType::Hex) => parse_number(text, 16).map(|(negative, magnitude)| match negative {
+            true  => Kind::Unsigned(magnitude.wrapping_neg()),
+            false => Kind::Unsigned(magnitude),
+        }),
+        Some(OptionType::Str) | None => parse_signed(text, 0).map(Kind::Signed),
+    };
+    parsed.unwrap_or(Kind::Text)
+}
+
+fn compare<E: Env + ?Sized>(op: CompareOp, lhs: &VarType, rhs: &VarType, env: &E) -> Tristate {
+    let (ltext, ltype) = operand(lhs, env);
+    let (rtext, rtype) = operand(rhs, env);
+
+    // Two strings are always compared as text
+    let both_strings = ltype == Some(OptionType::Str) && rtype == Some(OptionType::Str);
+    // Like `expr_calc_value()`, numbers are only compared as such when both sides are numbers, and
+    // as unsigned as soon as one of them is
+    let ordering = match (both_strings, kind(&ltext, ltype), kind(&rtext, rtype)) {
+        (true, _, _) | (_, Kind::Text, _) | (_, _, Kind::Text) => ltext.cmp(&rtext),
+        (_, Kind::Signed(l), Kind::Signed(r))       => l.cmp(&r),
+        (_, Kind::Unsigned(l), Kind::Unsigned(r))   => l.cmp(&r),
+        (_, Kind::Signed(l), Kind::Unsigned(r))     => (l as u64).cmp(&r),
+        (_, Kind::Unsigned(l), Kind::Signed(r))     => l.cmp(&(r as u64)),
+    };
+    let holds = match op {
+        CompareOp::Equal        => ordering == Ordering::Equal,
+        CompareOp::NotEqual     => ordering != Ordering::Equal,
+        CompareOp::Less         => ordering == Ordering::Less,
+        CompareOp::LessEqual    => ordering != Ordering::Greater,
+        CompareOp::Greater      => ordering == Ordering::Greater,
+        CompareOp::GreaterEqual => ordering != Ordering::Less,
+    };
+    if holds { Tristate::Yes } else { Tristate::No }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::expr::parse_expr;
+
+    use std::collections::HashMap;
+
+    struct Symbols(HashMap<&'static str, (OptionType, &'static str)>);
+
+    impl Env for Symbols {
+        fn value(&self, name: &str) -> Option<Value> {
+            self.0.get(name).map(|(option_type, value)| match option_type {
+                OptionType::Bool | OptionType::Tristate => Value::Tristate(Tristate::from_name(value).unwrap()),
+                _                                       => Value::Str(value.to_string()),
+            })
+        }
+
+        fn option_type(&self, name: &str) -> Option<OptionType> {
+            self.0.get(name).map(|(option_type, _)| *option_type)
+        }
+    }
+
+    fn eval(text: &str, modules: &'static str) -> Tristate {
+        let env = Symbols(HashMap::from([
+            ("MODULES", (OptionType::Bool,     modules)),
+            ("TRI",     (OptionType::Tristate, "m")),
+            ("NUM",     (OptionType::Int,      "-5")),
+            ("ADDR",    (OptionType::Hex,      "0x10")),
+            ("NAME",    (OptionType::Str,      "a\"b")),
+        ]));
+        let (rest, expr) = parse_expr(text).unwrap();
+        assert_eq!(rest.trim(), "", "{}", text);
+        expr.eval(&env)
+    }
+
+    #[test]
+    fn constant_symbols() {
+        assert_eq!(eval("\"y\"", "y"), Tristate::Yes);
+        assert_eq!(eval("'n'", "y"), Tristate::No);
+        assert_eq!(eval("\"m\"", "y"), Tristate::Module);
+        assert_eq!(eval("\"m\"", "n"), Tristate::No);
+        assert_eq!(eval("\"yes\"", "y"), Tristate::No);
+        assert_eq!(eval("\"y\" && TRI", "y"), Tristate::Module);
+        assert_eq!(eval("TRI = \"m\"", "y"), Tristate::Yes);
+    }
+
+    #[test]
+    fn unescaped_strings() {
+        assert_eq!(eval(r#"NAME = "a\"b""#, "y"), Tristate::Yes);
+        assert_eq!(eval(r#"NAME = "a\\\"b""#, "y"), Tristate::No);
+        let (_, expr) = parse_expr(r#""a\\b\"c""#).unwrap();
+        let Expr::Var(var) = expr else { panic!() };
+        assert_eq!(operand(&var, &Symbols(HashMap::new())).0, r#"a\b"c"#);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(eval("NUM < 0", "y"), Tristate::Yes);
+        assert_eq!(eval("NUM = \"-5\"", "y"), Tristate::Yes);
+        assert_eq!(eval("ADDR = 16", "y"), Tristate::Yes);
+        assert_eq!(eval("ADDR = \"0x010\"", "y"), Tristate::Yes);
+        assert_eq!(eval("ADDR > 0xf", "y"), Tristate::Yes);
+        // An octal constant, as `strtoll()` reads it with base 0
+        assert_eq!(eval("ADDR = \"020\"", "y"), Tristate::Yes);
+        assert_eq!(eval("ADDR = 020", "y"), Tristate::Yes);
+        // Mixed signed and unsigned compare as unsigned, so -5 is huge
+        assert_eq!(eval("NUM > ADDR", "y"), Tristate::Yes);
+        // A hex constant is not a hex symbol, it is signed like any other constant
+        assert_eq!(eval("NUM > 0x10", "y"), Tristate::No);
+        assert_eq!(eval("NUM < 0x10", "y"), Tristate::Yes);
+        assert_eq!(eval("ADDR = 0x10", "y"), Tristate::Yes);
+    }
+
+    #[test]
+    fn text_compare() {
+        // Either side not being a number of its symbol's type makes it a string compare
+        assert_eq!(eval("NUM = \"-5x\"", "y"), Tristate::No);
+        assert_eq!(eval("NUM < \"abc\"", "y"), Tristate::Yes);
+        assert_eq!(eval("ADDR = \"0x\"", "y"), Tristate::No);
+        assert_eq!(eval("NAME < \"b\"", "y"), Tristate::Yes);
+        // Too large for 64 bits is not a number either
+        assert_eq!(eval("\"-99999999999999999999\" < \"-2\"", "y"), Tristate::No);
+        assert_eq!(eval("\"10\" < 9", "y"), Tristate::No);
+    }
+}
@@ -0,0 +1,141 @@
+use super::{
+    Cause,
+    Engine,
+    OptionType,
+    PREFIX,
+    Value,
+    causes,
+    escape,
+};
+
+use std::collections::HashSet;
+
+// Explain the value of a symbol, and of the symbols the reasons name, down to `depth` levels.
+// Each symbol is shown with its value, type and visibility, then its reasons, the way `causes`
+// gives them. A symbol already explained higher up is not explained again.
+pub fn explain(engine: &Engine, name: &str, depth: usize) -> String {
+    let mut out = String::new();
+    let mut done = HashSet::new();
+    explain_symbol(engine, name, depth, 0, &mut done, &mut out);
+    out
+}
+
+fn explain_symbol<'a>(engine: &Engine<'_, '_, 'a>, name: &str, depth: usize, level: usize, done: &mut HashSet<&'a str>, out: &mut String) {
+    let indent = "  ".repeat(level * 2);
+    let Some(info) = engine.symbols.get(name) else {
+        out.push_str(&format!("{}{}{} is not defined\n", indent, PREFIX, name));
+        return;
+    };
+    let value = engine.value(name).map_or_else(String::new, |value| match (info.option_type, value) {
+        (Some(OptionType::Str), Value::Str(text)) => escape(&text),
+        (_, value)                                => value.as_str().to_string(),
+    });
+    let option_type = info.option_type.map_or_else(|| "unknown".to_string(), |option_type| option_type.to_string());
+    out.push_str(&format!("{}{}{}={} ({}, visible: {})\n", indent, PREFIX, info.name, value, option_type,
+        engine.visibility(name).name()));
+    if !done.insert(info.name) {
+        out.push_str(&format!("{}  (explained above)\n", indent));
+        return;
+    }
+    for cause in causes(engine, name) {
+        out.push_str(&format!("{}  {}\n", indent, cause));
+        if level >= depth {
+            continue;
+        }
+        for symbol in named(&cause) {
+            if symbol != info.name && engine.symbols.get(symbol).is_some() {
+                explain_symbol(engine, symbol, depth, level + 1, done, out);
+            }
+        }
+    }
+}
+
+// The symbols a reason refers to, each once
+fn named<'a>(cause: &Cause<'_, 'a>) -> Vec<&'a str> {
+    let mut names = match cause {
+        Cause::Default { value, condition } => value.symbols().into_iter()
+            .chain(condition.iter().flat_map(|condition| condition.symbols()))
+            .map(|symbol| symbol.name)
+            .collect(),
+        Cause::Dependency { expr, .. }      => expr.symbols().into_iter().map(|symbol| symbol.name).collect(),
+        Cause::Selected { by, .. }          => vec![*by],
+        Cause::Implied { by, .. }           => vec![*by],
+        Cause::Choice(Some(selected))       => vec![*selected],
+        _                                   => vec![],
+    };
+    let mut seen = HashSet::new();
+    names.retain(|name| seen.insert(*name));
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{
+        Tristate,
+        engine::tests::with_engine,
+    };
+
+    const KCONFIG: &str = "
+config A
+	bool \"a\"
+	depends on B && C
+	default y
+
+config B
+	bool
+	default C
+
+config C
+	bool \"c\"
+	depends on D
+
+config D
+	bool
+	default y
+";
+
+    fn run(depth: usize) -> String {
+        with_engine(KCONFIG, |engine| {
+            engine.set("C", Value::Tristate(Tristate::No));
+            explain(engine, "A", depth)
+        })
+    }
+
+    #[test]
+    fn depth_and_dedup() {
+        assert_eq!(run(0), "\
+CONFIG_A=n (bool, visible: n)
+  depends on B, which is n
+  depends on C, which is n
+  no default applies
+");
+        // The reasons of `B` are shown, but not the symbols they name
+        assert_eq!(run(1), "\
+CONFIG_A=n (bool, visible: n)
+  depends on B, which is n
+    CONFIG_B=n (bool, visible: n)
+      has no prompt
+      default C
+  depends on C, which is n
+    CONFIG_C=n (bool, visible: y)
+      set to n by the user
+  no default applies
+");
+        // `C` is reached through `B` first, and only explained there
+        assert_eq!(run(2), "\
+CONFIG_A=n (bool, visible: n)
+  depends on B, which is n
+    CONFIG_B=n (bool, visible: n)
+      has no prompt
+      default C
+        CONFIG_C=n (bool, visible: y)
+          set to n by the user
+  depends on C, which is n
+    CONFIG_C=n (bool, visible: y)
+      (explained above)
+  no default applies
+");
+        assert_eq!(with_engine(KCONFIG, |engine| explain(engine, "E", 1)), "CONFIG_E is not defined\n");
+    }
+}
@@ -17,6 +17,7 @@ use nom::{
     combinator::{
         opt,
         map,
+        peek,
         recognize,
     },
     sequence::{
@@ -28,23 +29,144 @@ use nom::{
     multi::many0,
 };
 
-#[derive(Debug, PartialEq)]
+// Ordering matters here, `n < m < y` is what allows `&&` to be `min` and `||` to be `max`
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
+pub enum Tristate {
+    No,
+    Module,
+    Yes,
+}
+
+impl Tristate {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "y" => Some(Tristate::Yes),
+            "m" => Some(Tristate::Module),
+            "n" => Some(Tristate::No),
+            _   => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tristate::Yes    => "y",
+            Tristate::Module => "m",
+            Tristate::No     => "n",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum VarType<'a> {
+    Tristate(Tristate),
     Hex(Hex),
     Int(Int),
-    Str(&'a str),
     ConstantSymbol(ConstantSymbol<'a>),
     Symbol(Symbol<'a>),
+
+    // An unexpanded `$(...)` macro, kept verbatim including the delimiters
+    Macro(&'a str),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum CompareOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum Expr<'a> {
     Var(VarType<'a>),
+    Compare(CompareOp, VarType<'a>, VarType<'a>),
     Not(Box<Expr<'a>>),
     And(Box<Expr<'a>>, Box<Expr<'a>>),
     Or(Box<Expr<'a>>, Box<Expr<'a>>),
 }
 
+impl<'a> Expr<'a> {
+    // Every symbol referenced by this expression, in the order they appear
+    pub fn symbols(&self) -> Vec<&Symbol<'a>> {
+        fn var<'e, 'a>(v: &'e VarType<'a>, out: &mut Vec<&'e Symbol<'a>>) {
+            if let VarType::Symbol(s) = v {
+                out.push(s);
+            }
+        }
+        fn walk<'e, 'a>(e: &'e Expr<'a>, out: &mut Vec<&'e Symbol<'a>>) {
+            match e {
+                Expr::Var(v)              => var(v, out),
+                Expr::Compare(_, l, r)    => { var(l, out); var(r, out); },
+                Expr::Not(e)              => walk(e, out),
+                Expr::And(l, r) | Expr::Or(l, r) => { walk(l, out); walk(r, out); },
+            }
+        }
+        let mut out = vec![];
+        walk(self, &mut out);
+        out
+    }
+
+    // The operands of a chain of `&&`, or the expression itself when it is not one
+    pub fn conjuncts(&self) -> Vec<&Expr<'a>> {
+        match self {
+            Expr::And(l, r) => {
+                let mut out = l.conjuncts();
+                out.extend(r.conjuncts());
+                out
+            },
+            _ => vec![self],
+        }
+    }
+
+    // `lhs && rhs`, where a missing side is always true. Terms of `rhs` already required by `lhs`
+    // are left out, so a `depends on A` inside `if A` does not show up twice.
+    pub fn and(lhs: Option<Expr<'a>>, rhs: Option<Expr<'a>>) -> Option<Expr<'a>> {
+        match (lhs, rhs) {
+            (Some(l), Some(r)) => {
+                let have = l.conjuncts();
+                let mut terms = vec![];
+                for term in r.conjuncts() {
+                    if !have.contains(&term) && !terms.contains(&term) {
+                        terms.push(term);
+                    }
+                }
+                let rest = terms.into_iter().cloned().reduce(|acc, t| Expr::And(Box::new(acc), Box::new(t)));
+                match rest {
+                    Some(r) => Some(Expr::And(Box::new(l), Box::new(r))),
+                    None    => Some(l),
+                }
+            },
+            (l, None)          => l,
+            (None, r)          => r,
+        }
+    }
+
+    // `lhs || rhs`, where a missing side is always true and so is the result
+    pub fn or(lhs: Option<Expr<'a>>, rhs: Option<Expr<'a>>) -> Option<Expr<'a>> {
+        match (lhs, rhs) {
+            (Some(l), Some(r)) if l == r => Some(l),
+            (Some(l), Some(r))           => Some(Expr::Or(Box::new(l), Box::new(r))),
+            _                            => None,
+        }
+    }
+
+    // True when this can only be satisfied if `name` is enabled: `name`, `name=y`, `name=m` or
+    // `name!=n`, possibly as one side of an `&&`. This is the kernel's `expr_depends_symbol()`,
+    // used to decide which entries make up the implicit menu below a config.
+    pub fn depends_on(&self, name: &str) -> bool {
+        let is = |v: &VarType| matches!(v, VarType::Symbol(s) if s.name == name);
+        match self {
+            Expr::And(l, r) => l.depends_on(name) || r.depends_on(name),
+            Expr::Var(v)    => is(v),
+            Expr::Compare(CompareOp::Equal, l, VarType::Tristate(Tristate::Yes | Tristate::Module)) => is(l),
+            Expr::Compare(CompareOp::NotEqual, l, VarType::Tristate(Tristate::No)) => is(l),
+            _ => false,
+        }
+    }
+}
+
 fn take_parens(input: &str) -> IResult<&str, &str> {
     let (input, ret) = recognize(delimited(
         alt((tag("$("), tag("("))),
@@ -65,62 +187,62 @@ fn take_parens(input: &str) -> IResult<&str, &str> {
     Ok((input, ret))
 }
 
-fn take_operation(input: &str) -> IResult<&str, VarType> {
-    preceded(
-        tuple((
-            special_space,
-            alt((
-                tag("<="),
-                tag(">="),
-                tag("!="),
-                tag("<"),
-                tag(">"),
-                tag("="),
-            )),
-            special_space,
-        )),
-        alt((
-            map(Hex::parse,            |v| VarType::Hex(v)            ),
-            map(Int::parse,            |v| VarType::Int(v)            ),
-            map(Symbol::parse,         |v| VarType::Symbol(v)         ),
-            map(ConstantSymbol::parse, |v| VarType::ConstantSymbol(v) ),
-        )),
-    )(input)
+fn take_macro(input: &str) -> IResult<&str, &str> {
+    preceded(peek(tag("$(")), take_parens)(input)
+}
+
+// Kconfig does not distinguish between symbol names and numbers at the lexer level, `64BIT` and
+// `64` are both valid symbol tokens. We split them apart here once the whole token is known.
+fn classify_symbol(sym: Symbol<'_>) -> VarType<'_> {
+    if let Some(tri) = Tristate::from_name(sym.name) {
+        return VarType::Tristate(tri);
+    }
+    if let Ok(("", hex)) = Hex::parse(sym.name) {
+        return VarType::Hex(hex);
+    }
+    // With a leading zero the kernel reads a constant as octal when it compares it, which it
+    // does by taking the symbol's own name. Kept as a symbol it is compared the same way.
+    let octal = sym.name.len() > 1 && sym.name.starts_with('0');
+    if let (Ok(("", int)), false) = (Int::parse(sym.name), octal) {
+        return VarType::Int(int);
+    }
+    VarType::Symbol(sym)
+}
+
+pub fn parse_var(input: &str) -> IResult<&str, VarType<'_>> {
+    alt((
+        map(take_macro,            VarType::Macro),
+        map(ConstantSymbol::parse, VarType::ConstantSymbol),
+        map(Symbol::parse,         classify_symbol),
+        map(Int::parse,            VarType::Int), // negative numbers are not valid symbol names
+    ))(input)
 }
 
-fn take_state(input: &str) -> IResult<&str, &str> {
-    preceded(
-        tag("="),
+fn take_operator(input: &str) -> IResult<&str, CompareOp> {
+    delimited(
+        special_space,
         alt((
-            tag("y"),
-            tag("m"),
-            tag("n"),
+            map(tag("<="), |_| CompareOp::LessEqual),
+            map(tag(">="), |_| CompareOp::GreaterEqual),
+            map(tag("!="), |_| CompareOp::NotEqual),
+            map(tag("<"),  |_| CompareOp::Less),
+            map(tag(">"),  |_| CompareOp::Greater),
+            map(tag("="),  |_| CompareOp::Equal),
         )),
+        special_space,
     )(input)
 }
 
-fn var(input: &str) -> IResult<&str, Expr> {
-    map(
-        recognize(
-            tuple((
-                alt((
-                    recognize(Symbol::parse),
-                    recognize(Hex::parse),
-                    recognize(Int::parse),
-                    take_parens,
-                    recognize(ConstantSymbol::parse),
-                )),
-                opt(alt((
-                    take_state,
-                    recognize(take_operation),
-                ))),
-            ))
-        ),
-        |var_name: &str| Expr::Var(VarType::Str(var_name)),
-    )(input)
+fn var(input: &str) -> IResult<&str, Expr<'_>> {
+    let (input, lhs) = parse_var(input)?;
+    let (input, rhs) = opt(tuple((take_operator, parse_var)))(input)?;
+    Ok((input, match rhs {
+        Some((op, rhs)) => Expr::Compare(op, lhs, rhs),
+        None            => Expr::Var(lhs),
+    }))
 }
 
-fn parens(input: &str) -> IResult<&str, Expr> {
+fn parens(input: &str) -> IResult<&str, Expr<'_>> {
     delimited(
         tuple((special_space, tag("("), special_space)),
         parse_expr,
@@ -128,7 +250,7 @@ fn parens(input: &str) -> IResult<&str, Expr> {
     )(input)
 }
 
-fn factor(input: &str) -> IResult<&str, Expr> {
+fn factor(input: &str) -> IResult<&str, Expr<'_>> {
     alt((
         var,
         parens,
@@ -139,18 +261,18 @@ fn factor(input: &str) -> IResult<&str, Expr> {
     ))(input)
 }
 
-fn term(input: &str) -> IResult<&str, Expr> {
+fn term(input: &str) -> IResult<&str, Expr<'_>> {
     let (input, init) = factor(input)?;
-    let (input, terms) = many0(
-        preceded(tuple((special_space, tag("&&"), special_space)), term)
+    let (input, factors) = many0(
+        preceded(tuple((special_space, tag("&&"), special_space)), factor)
     )(input)?;
 
-    let result = terms.into_iter().fold(init, |acc, e| Expr::And(Box::new(acc), Box::new(e)));
+    let result = factors.into_iter().fold(init, |acc, e| Expr::And(Box::new(acc), Box::new(e)));
 
     Ok((input, result))
 }
 
-pub fn parse_expr(input: &str) -> IResult<&str, Expr> {
+pub fn parse_expr(input: &str) -> IResult<&str, Expr<'_>> {
     let (input, init) = term(input)?;
     let (input, terms) = many0(
         preceded(tuple((special_space, tag("||"), special_space)), term)
@@ -160,3 +282,105 @@ pub fn parse_expr(input: &str) -> IResult<&str, Expr> {
 
     Ok((input, result))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Expr<'_> {
+        let (rest, expr) = parse_expr(text).unwrap();
+        assert_eq!(rest, "", "{}", text);
+        expr
+    }
+
+    // Every operator with its operands in parentheses, to see how the parser grouped them
+    fn shape(expr: &Expr) -> String {
+        match expr {
+            Expr::Var(v)                => format!("{}", v),
+            Expr::Compare(op, lhs, rhs) => format!("[{}{}{}]", lhs, op, rhs),
+            Expr::Not(e)                => format!("!{}", shape(e)),
+            Expr::And(l, r)             => format!("({} && {})", shape(l), shape(r)),
+            Expr::Or(l, r)              => format!("({} || {})", shape(l), shape(r)),
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        let cases = [
+            ("A || B && C",          "(A || (B && C))"),
+            ("A && B || C && D",     "((A && B) || (C && D))"),
+            ("A && B && C",          "((A && B) && C)"),
+            ("(A || B) && C",        "((A || B) && C)"),
+            ("!A && B",              "(!A && B)"),
+            ("!(A && B)",            "!(A && B)"),
+            ("! !A",                 "!!A"),
+            ("A = y && !B != n",     "([A=y] && ![B!=n])"),
+            ("( A || ( B ) ) && C",  "((A || B) && C)"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(shape(&parse(text)), expected, "{}", text);
+        }
+        // Parentheses are only printed where they are needed
+        assert_eq!(parse("(A || B) && !(C || D) && (E && F)").to_string(), "(A || B) && !(C || D) && E && F");
+    }
+
+    #[test]
+    fn compare_ops() {
+        let ops = [
+            ("=",  CompareOp::Equal),
+            ("!=", CompareOp::NotEqual),
+            ("<",  CompareOp::Less),
+            ("<=", CompareOp::LessEqual),
+            (">",  CompareOp::Greater),
+            (">=", CompareOp::GreaterEqual),
+        ];
+        for (text, op) in ops {
+            let expected = Expr::Compare(op, parse_var("A").unwrap().1, VarType::Int(Int { val: 1 }));
+            assert_eq!(parse(&format!("A {} 1", text)), expected);
+            assert_eq!(parse(&format!("A{}1", text)), expected);
+        }
+    }
+
+    #[test]
+    fn literals() {
+        let var = |text| parse_var(text).unwrap().1;
+        assert_eq!(var("y"), VarType::Tristate(Tristate::Yes));
+        assert_eq!(var("m"), VarType::Tristate(Tristate::Module));
+        assert_eq!(var("n"), VarType::Tristate(Tristate::No));
+        assert_eq!(var("0x1F"), VarType::Hex(Hex { val: 0x1f }));
+        assert_eq!(var("64"), VarType::Int(Int { val: 64 }));
+        assert_eq!(var("0"), VarType::Int(Int { val: 0 }));
+        assert_eq!(var("-3"), VarType::Int(Int { val: -3 }));
+        assert!(matches!(var("64BIT"), VarType::Symbol(s) if s.name == "64BIT"));
+        assert!(matches!(var("yes"), VarType::Symbol(s) if s.name == "yes"));
+        // Octal, see `classify_symbol`
+        assert!(matches!(var("010"), VarType::Symbol(s) if s.name == "010"));
+        assert!(matches!(var("\"y\""), VarType::ConstantSymbol(_)));
+        assert_eq!(var("$(cc-option,-m64)"), VarType::Macro("$(cc-option,-m64)"));
+    }
+
+    #[test]
+    fn helpers() {
+        let expr = parse("A && (B || C) && !D");
+        let terms: Vec<String> = expr.conjuncts().iter().map(|term| term.to_string()).collect();
+        assert_eq!(terms, ["A", "B || C", "!D"]);
+        let names: Vec<&str> = parse("A = B || !(C < 3) && \"D\"").symbols().iter().map(|s| s.name).collect();
+        assert_eq!(names, ["A", "B", "C"]);
+
+        // Terms already required on the left are not repeated
+        let and = Expr::and(Some(parse("A && B")), Some(parse("B && C && A && C")));
+        assert_eq!(and.unwrap().to_string(), "A && B && C");
+        assert_eq!(Expr::and(Some(parse("A")), Some(parse("A"))), Some(parse("A")));
+        assert_eq!(Expr::and(None, Some(parse("A"))), Some(parse("A")));
+        assert_eq!(Expr::or(Some(parse("A")), Some(parse("A"))), Some(parse("A")));
+        assert_eq!(Expr::or(Some(parse("A")), Some(parse("B"))), Some(parse("A || B")));
+        assert_eq!(Expr::or(Some(parse("A")), None), None);
+
+        for text in ["A", "A = y", "A=m", "A != n", "B && A", "A && (B || C)"] {
+            assert!(parse(text).depends_on("A"), "{}", text);
+        }
+        for text in ["!A", "A = n", "A || B", "A != y", "y = A", "AB"] {
+            assert!(!parse(text).depends_on("A"), "{}", text);
+        }
+    }
+}
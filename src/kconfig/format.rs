@@ -0,0 +1,274 @@
+use super::{
+    Cst,
+    CstAttr,
+    CstLine,
+    CstNode,
+};
+
+// Rewrite a Kconfig file in the style used throughout the kernel tree:
+//
+//  * entries, block keywords and top level comments start in the first column, nesting inside
+//    `menu`, `choice` and `if` is not indented
+//  * attributes are indented with a single tab and sorted into the usual order. The sort is
+//    stable and only moves attributes within a run that is not broken by a blank line, so the
+//    relative order of attributes of the same kind is kept.
+//  * help text is indented with a tab and two spaces, deeper indentation within it is kept
+//  * trailing whitespace is removed, runs of blank lines are collapsed into one and the file
+//    ends in exactly one line ending
+//
+// Comments move together with the attribute that follows them, and a file with `\r\n` line
+// endings keeps them.
+pub fn format(content: &str) -> String {
+    let cst = Cst::parse(content);
+    let mut writer = Writer {
+        out: String::new(),
+        eol: if content.split('\n').next().is_some_and(|line| line.ends_with('\r')) { "\r\n" } else { "\n" },
+    };
+    writer.nodes(&cst.nodes);
+    while writer.ends_blank() {
+        writer.out.truncate(writer.out.len() - writer.eol.len());
+    }
+    writer.out
+}
+
+// `default`, `def_bool` and `def_tristate` share a rank, the first of them whose condition holds
+// is the one that applies, so their order must not change
+fn rank(keyword: &str) -> usize {
+    match keyword {
+        "bool" | "tristate" | "string" | "hex" | "int" | "prompt" => 0,
+        "depends"                                         => 1,
+        "visible"                                         => 2,
+        "select"                                          => 3,
+        "imply"                                           => 4,
+        "default" | "def_bool" | "def_tristate"           => 5,
+        "range"                                           => 6,
+        "help" | "---help---"                             => 8,
+        _                                                 => 7,
+    }
+}
+
+struct Writer {
+    out: String,
+    eol: &'static str,
+}
+
+impl Writer {
+    fn nodes(&mut self, nodes: &[CstNode]) {
+        for node in nodes {
+            match node {
+                CstNode::Blank(_) => self.blank(),
+                CstNode::Comment(line) | CstNode::Other(line) => self.line("", line.text),
+                CstNode::Entry(entry) => {
+                    self.keyword("", &entry.head);
+                    self.attrs(&entry.attrs);
+                },
+                CstNode::Block(block) => {
+                    self.keyword("", &block.head);
+                    self.attrs(&block.attrs);
+                    self.nodes(&block.children);
+                    if let Some(end) = &block.end {
+                        self.keyword("", end);
+                    }
+                },
+            }
+        }
+    }
+
+    fn ends_blank(&self) -> bool {
+        self.out.ends_with(&self.eol.repeat(2))
+    }
+
+    // A single blank line, never at the start of the file or after another one
+    fn blank(&mut self) {
+        if !self.out.is_empty() && !self.ends_blank() {
+            self.out.push_str(self.eol);
+        }
+    }
+
+    fn line(&mut self, indent: &str, text: &str) {
+        self.out.push_str(indent);
+        self.out.push_str(text.trim_end());
+        self.out.push_str(self.eol);
+    }
+
+    // `keyword   rest` becomes `keyword rest`
+    fn keyword(&mut self, indent: &str, line: &CstLine) {
+        let rest = line.rest();
+        if rest.is_empty() {
+            self.line(indent, line.keyword());
+        } else {
+            self.line(indent, &format!("{} {}", line.keyword(), rest));
+        }
+    }
+
+    fn attrs(&mut self, attrs: &[CstAttr]) {
+        let mut run: Vec<(Vec<&CstLine>, &CstAttr)> = vec![];
+        let mut comments = vec![];
+        for attr in attrs {
+            match attr {
+                CstAttr::Comment(line) => comments.push(line),
+                CstAttr::Blank(_)      => {
+                    self.run(std::mem::take(&mut run));
+                    for comment in std::mem::take(&mut comments) {
+                        self.line("\t", comment.text);
+                    }
+                    self.blank();
+                },
+                _ => run.push((std::mem::take(&mut comments), attr)),
+            }
+        }
+        self.run(run);
+    }
+
+    fn run(&mut self, mut run: Vec<(Vec<&CstLine>, &CstAttr)>) {
+        run.sort_by_key(|(_, attr)| match attr {
+            CstAttr::Line(line) => rank(line.keyword()),
+            _                   => rank("help"),
+        });
+        for (comments, attr) in run {
+            for comment in comments {
+                self.line("\t", comment.text);
+            }
+            match attr {
+                CstAttr::Line(line) => self.keyword("\t", line),
+                CstAttr::Help { head, body } => {
+                    self.keyword("\t", head);
+                    self.help(body);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn help(&mut self, body: &[CstLine]) {
+        let base = body.iter()
+            .find(|line| !line.is_blank())
+            .map(|line| line.indent_width())
+            .unwrap_or(0);
+        for line in body {
+            if line.is_blank() {
+                self.out.push_str(self.eol);
+            } else {
+                let extra = " ".repeat(line.indent_width().saturating_sub(base));
+                self.line(&format!("\t  {}", extra), line.text);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::KOption;
+
+    #[test]
+    fn canonical_style() {
+        let content = "\n\
+            \n\
+            menu   \"M\"\n\
+            \x20 config A\n\
+            \x20   help\n\
+            \x20     Text.\n\
+            \x20       Deeper.\n\
+            \x20   default y   \n\
+            \x20   # the prompt\n\
+            \x20   bool \"a\"\n\
+            \n\
+            \n\
+            \n\
+            endmenu\n\
+            \n";
+        let expected = "menu \"M\"\n\
+            config A\n\
+            \t# the prompt\n\
+            \tbool \"a\"\n\
+            \tdefault y\n\
+            \thelp\n\
+            \t  Text.\n\
+            \t    Deeper.\n\
+            \n\
+            endmenu\n";
+        assert_eq!(format(content), expected);
+    }
+
+    #[test]
+    fn idempotent() {
+        let content = "mainmenu \"T\"\n\
+            config A\n\
+            \x20   depends on B\n\
+            \tbool \"a\"\n\
+            \tselect C if \\\n\
+            \t\tD\n\
+            \thelp\n\
+            \t  Help.\n\
+            \n\
+            \t  More.\n\
+            \n\
+            choice\n\
+            \tdefault C1\n\
+            \tprompt \"c\"\n\
+            # kept\n\
+            config C1\n\
+            \tbool \"c1\"\n\
+            endchoice\n\
+            X := 1";
+        let once = format(content);
+        assert_eq!(format(&once), once);
+        assert!(once.ends_with("endchoice\nX := 1\n"));
+    }
+
+    #[test]
+    fn whitespace_only() {
+        // Attributes are not moved across a blank line, which stays, and keep their keywords
+        let content = "config A\r\n\
+            \x20 default y if B\r\n\
+            \r\n\
+            \r\n\
+            \x20 bool \"a\"   \r\n\
+            \x20 ---help---\r\n\
+            \x20   Text.\r\n\
+            \r\n\
+            config B\r\n\
+            \tbool\r\n";
+        let expected = "config A\r\n\
+            \tdefault y if B\r\n\
+            \r\n\
+            \tbool \"a\"\r\n\
+            \t---help---\r\n\
+            \t  Text.\r\n\
+            \r\n\
+            config B\r\n\
+            \tbool\r\n";
+        assert_eq!(format(content), expected);
+        assert_eq!(format(expected), expected);
+        assert_eq!(format("config A\n\tbool\n\n\n"), "config A\n\tbool\n");
+    }
+
+    #[test]
+    fn defaults_keep_order() {
+        let content = "config A\n\
+            \tdefault n if B\n\
+            \tselect C\n\
+            \tdef_tristate m if D\n\
+            \tdepends on E\n\
+            \tdef_bool y\n\
+            \ttristate \"a\"\n";
+        let expected = "config A\n\
+            \ttristate \"a\"\n\
+            \tdepends on E\n\
+            \tselect C\n\
+            \tdefault n if B\n\
+            \tdef_tristate m if D\n\
+            \tdef_bool y\n";
+        let formatted = format(content);
+        assert_eq!(formatted, expected);
+
+        // The same default applies before and after
+        let defaults = |text: &str| {
+            let (_, option) = KOption::parse(text).unwrap();
+            option.all_defaults().iter().map(|d| d.expression.to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(defaults(&formatted), ["n", "m", "y"]);
+        assert_eq!(defaults(content), defaults(&formatted));
+    }
+}
@@ -0,0 +1,289 @@
+use super::{
+    Engine,
+    Env,
+    MenuItem,
+    OptionType,
+    Tristate,
+    Value,
+};
+
+// The configurations `make allyesconfig` and friends produce. Each gives every bool and tristate
+// symbol without a user value the same answer, or a random one, and leaves the rest to the value
+// engine. Strings and numbers always keep their defaults. Values preset with `KCONFIG_ALLCONFIG`
+// are set on the engine beforehand and are left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generator {
+    AllYes,
+    AllNo,
+
+    // Tristates become modules wherever they can, bools are `y`
+    AllMod,
+    AllDef,
+    Random {
+        seed:        u64,
+        probability: Probability,
+    },
+}
+
+// Percentages for `randconfig`, as `KCONFIG_PROBABILITY` sets them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Probability {
+    pub bool_yes:     u32,
+    pub tristate_yes: u32,
+    pub tristate_mod: u32,
+}
+
+impl Default for Probability {
+    fn default() -> Self {
+        Self {
+            bool_yes:     50,
+            tristate_yes: 33,
+            tristate_mod: 33,
+        }
+    }
+}
+
+impl Probability {
+    // `N` is the chance of `y` for bools, split evenly between `y` and `m` for tristates. `Y:M`
+    // are the chances of `y` and `m` for tristates, bools get their sum. `N:Y:M` sets all three.
+    pub fn parse(text: &str) -> Option<Self> {
+        let values = text.split(':')
+            .map(|part| part.parse::<u32>().ok().filter(|p| *p <= 100))
+            .collect::<Option<Vec<u32>>>()?;
+        let probability = match values[..] {
+            [bool_yes] => Self {
+                bool_yes,
+                tristate_yes: bool_yes - bool_yes / 2,
+                tristate_mod: bool_yes / 2,
+            },
+            [tristate_yes, tristate_mod] => Self {
+                bool_yes: tristate_yes + tristate_mod,
+                tristate_yes,
+                tristate_mod,
+            },
+            [bool_yes, tristate_yes, tristate_mod] => Self {
+                bool_yes,
+                tristate_yes,
+                tristate_mod,
+            },
+            _ => return None,
+        };
+        (probability.tristate_yes + probability.tristate_mod <= 100 && probability.bool_yes <= 100).then_some(probability)
+    }
+}
+
+// splitmix64, small and good enough to pick values with. What matters is that a seed always
+// gives the same configuration.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // A number below `n`
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+pub fn generate(engine: &mut Engine, generator: Generator) {
+    let (seed, probability) = match generator {
+        Generator::AllDef                       => return,
+        Generator::Random { seed, probability } => (seed, probability),
+        _                                       => (0, Probability::default()),
+    };
+    let mut rng = Rng::new(seed);
+    let symbols = engine.symbols;
+    let tree = engine.tree;
+
+    // The values of choices are picked afterwards, once it is known which of them are visible
+    for info in symbols.iter() {
+        if engine.user_value(info.name).is_some() || engine.choice_of(info).is_some() {
+            continue;
+        }
+        let tristate = match info.option_type {
+            Some(OptionType::Tristate) => true,
+            Some(OptionType::Bool)     => false,
+            _                          => continue,
+        };
+        let value = match generator {
+            Generator::AllYes                  => Tristate::Yes,
+            Generator::AllNo                   => Tristate::No,
+            Generator::AllMod if tristate      => Tristate::Module,
+            Generator::AllMod                  => Tristate::Yes,
+            Generator::AllDef                  => continue,
+            Generator::Random { .. } => {
+                let roll = rng.below(100) as u32;
+                match tristate {
+                    true if roll < probability.tristate_yes                              => Tristate::Yes,
+                    true if roll < probability.tristate_yes + probability.tristate_mod => Tristate::Module,
+                    false if roll < probability.bool_yes                                 => Tristate::Yes,
+                    _                                                                    => Tristate::No,
+                }
+            },
+        };
+        engine.set(info.name, Value::Tristate(value));
+    }
+
+    // Choices in menu order, as picking a value can make a later choice visible
+    for idx in 0..tree.nodes.len() {
+        let MenuItem::Choice(choice) = tree.nodes[idx].item else {
+            continue;
+        };
+        let values = engine.choice_values(idx);
+        let preset = values.iter().any(|name| engine.user_value(name).is_some());
+        if values.is_empty() || preset || engine.selection(idx).mode == Tristate::No {
+            continue;
+        }
+        let tristate = choice.option_type == OptionType::Tristate && engine.modules() != Tristate::No;
+        let pick = match generator {
+            // A choice that has to pick something keeps its default
+            Generator::AllNo if choice.optional => Pick::Nothing,
+            Generator::AllMod if tristate       => Pick::Modules(values.iter().map(|_| true).collect()),
+            Generator::Random { .. } => {
+                let roll = rng.below(100) as u32;
+                if tristate && roll >= probability.tristate_yes && roll < probability.tristate_yes + probability.tristate_mod {
+                    Pick::Modules(values.iter().map(|_| rng.below(2) == 0).collect())
+                } else if choice.optional && roll >= probability.bool_yes {
+                    Pick::Nothing
+                } else {
+                    Pick::One(rng.below(values.len() as u64) as usize)
+                }
+            },
+            _ => continue,
+        };
+        for (i, name) in values.iter().enumerate() {
+            let value = match &pick {
+                Pick::Nothing       => Tristate::No,
+                Pick::One(one)      => if i == *one { Tristate::Yes } else { Tristate::No },
+                Pick::Modules(mods) => if mods[i] { Tristate::Module } else { Tristate::No },
+            };
+            engine.set(name, Value::Tristate(value));
+        }
+    }
+}
+
+enum Pick {
+    Nothing,
+    One(usize),
+
+    // For a tristate choice holding modules, which of its values are one
+    Modules(Vec<bool>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{
+        engine::tests::with_engine,
+        write_dotconfig,
+    };
+
+    const KCONFIG: &str = "
+config MODULES
+	bool \"modules\"
+
+config A
+	tristate \"a\"
+
+config B
+	bool \"b\"
+
+choice
+	prompt \"required\"
+	default SECOND
+
+config FIRST
+	bool \"first\"
+
+config SECOND
+	bool \"second\"
+
+endchoice
+
+choice
+	prompt \"optional\"
+	optional
+
+config THIRD
+	bool \"third\"
+
+endchoice
+
+choice
+	tristate \"modular\"
+
+config FOURTH
+	tristate \"fourth\"
+
+config FIFTH
+	tristate \"fifth\"
+
+endchoice
+";
+
+    fn values(generator: Generator) -> Vec<(&'static str, Tristate)> {
+        with_engine(KCONFIG, |engine| {
+            generate(engine, generator);
+            ["MODULES", "A", "B", "FIRST", "SECOND", "THIRD", "FOURTH", "FIFTH"].into_iter()
+                .map(|name| (name, engine.value(name).unwrap().tristate()))
+                .collect()
+        })
+    }
+
+    #[test]
+    fn probability() {
+        let parse = |text| Probability::parse(text).map(|p| (p.bool_yes, p.tristate_yes, p.tristate_mod));
+        assert_eq!(parse("33"), Some((33, 17, 16)));
+        assert_eq!(parse("20:30"), Some((50, 20, 30)));
+        assert_eq!(parse("10:20:30"), Some((10, 20, 30)));
+        assert_eq!(parse("100:0:100"), Some((100, 0, 100)));
+        for text in ["101", "60:50", "10:60:50", "1:2:3:4", "", "y", "-1"] {
+            assert_eq!(parse(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn all_choices() {
+        use Tristate::*;
+        // A choice that has to pick something keeps its default, an optional one picks nothing
+        assert_eq!(values(Generator::AllNo), [
+            ("MODULES", No), ("A", No), ("B", No), ("FIRST", No), ("SECOND", Yes), ("THIRD", No),
+            ("FOURTH", Yes), ("FIFTH", No),
+        ]);
+        // Every value of a tristate choice becomes a module
+        assert_eq!(values(Generator::AllMod), [
+            ("MODULES", Yes), ("A", Module), ("B", Yes), ("FIRST", No), ("SECOND", Yes), ("THIRD", No),
+            ("FOURTH", Module), ("FIFTH", Module),
+        ]);
+        assert_eq!(values(Generator::AllYes)[..6], [
+            ("MODULES", Yes), ("A", Yes), ("B", Yes), ("FIRST", No), ("SECOND", Yes), ("THIRD", No),
+        ]);
+    }
+
+    #[test]
+    fn random_seed() {
+        let config = |seed| with_engine(KCONFIG, |engine| {
+            generate(engine, Generator::Random { seed, probability: Probability::default() });
+            write_dotconfig(engine)
+        });
+        assert_eq!(config(7), config(7));
+        assert!((0..8).any(|seed| config(seed) != config(7)));
+
+        let mut rng = Rng::new(1);
+        let first: Vec<u64> = (0..4).map(|_| rng.next_u64()).collect();
+        let mut again = Rng::new(1);
+        assert!(first.iter().all(|n| *n == again.next_u64()));
+        assert!((0..100).all(|_| rng.below(3) < 3));
+    }
+}
@@ -1,16 +1,22 @@
 use super::{
-    KCommentBlock,
+    Entry,
     KOption,
     OptionType,
     Dependency,
     Help,
     Prompt,
     Annotation,
+    Span,
+    entry::attach_trailing,
     util::{
+        ParseError,
+        take_gap,
         take_line_ending,
     },
 };
 
+use std::cell::Cell;
+
 use nom::{
     branch::alt,
     bytes::complete::tag,
@@ -31,15 +37,22 @@ use nom::{
 pub struct KChoice<'a> {
     pub option_type: OptionType,
     pub prompt:      Option<Prompt<'a>>,
-    pub options:     Vec<KOption<'a>>,
+
+    // The values of the choice and any comments, `if` blocks and sourced files between them, in
+    // the order they were written
+    pub entries:     Vec<Entry<'a>>,
     pub optional:    bool,
     pub defaults:    Option<Vec<Dependency<'a>>>,
     pub depends:     Option<Vec<Dependency<'a>>>,
     pub help:        Option<Help<'a>>,
+    pub annotations: Option<Vec<Annotation<'a>>>,
+    pub errors:      Option<Vec<ParseError<'a>>>,
+    pub span:        Span,
 }
 
 impl<'a> KChoice<'a> {
     pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let start = input;
         let mut opt_option_type = None;
         let mut opt_prompt_from_type = None;
         let mut prompt   = None;
@@ -47,7 +60,11 @@ impl<'a> KChoice<'a> {
         let mut optional = false;
         let mut depends  = vec![];
         let mut defaults = vec![];
-        let mut options  = vec![];
+        let mut entries  = vec![];
+        let mut loose    = vec![];
+        let mut errors   = vec![];
+        let count = Cell::new(0usize);
+        let inside = Some(("choice", input));
 
         let type_line_parser = tuple((
             OptionType::parse,
@@ -61,12 +78,15 @@ impl<'a> KChoice<'a> {
                 space0,
             )),
             many1(alt((
+                map(tuple((take_gap, Entry::parse)), |(notes, mut v)| {
+                    v.annotate(notes);
+                    entries.push(v);
+                    count.set(count.get() + 1);
+                }),
                 map(take_line_ending,                |_| {}),
-                map(Annotation::parse,               |_| {}),
+                map(Annotation::parse,               |v| loose.push((count.get(), v))),
                 map(tuple((space0, tag("optional"))), |_| optional = true),
                 map(Help::parse("help"),             |v| help = Some(v)),
-                map(KOption::parse,                  |v| options.push(v)),
-                map(KCommentBlock::parse,            |_| {}), // TODO: something useful with these?
                 map(Prompt::parse("prompt"),         |v| prompt = Some(v)),
                 map(Dependency::parse("default"),    |v| defaults.push(v)),
                 map(Dependency::parse("depends on"), |v| depends.push(v)),
@@ -74,6 +94,7 @@ impl<'a> KChoice<'a> {
                     opt_option_type = Some(opttype);
                     opt_prompt_from_type = opt_prompt;
                 }),
+                map(ParseError::parse(inside), |v| errors.push(v)),
             ))),
             tuple((
                 space0,
@@ -86,7 +107,7 @@ impl<'a> KChoice<'a> {
         // it do work tho
         let mut opt_types = vec![];
         let mut tmptype = OptionType::Int; 
-        for opt in &options {
+        for opt in entries.iter().flat_map(Entry::collect_options) {
             opt_types.push(opt.option_type);
             tmptype = opt.option_type;
         }
@@ -113,14 +134,106 @@ impl<'a> KChoice<'a> {
             prompt = Some(p);
         }
 
+        // Comments that no entry followed trail the entry before them, the rest are among the
+        // choice's own attributes
+        let annotations = attach_trailing(&mut entries, loose);
+
         Ok((input, Self{
+                span: Span::between(start, input),
                 option_type,
                 optional,
                 prompt,
                 defaults: if defaults.is_empty() { None } else { Some(defaults) },
                 depends:  if depends.is_empty()  { None } else { Some(depends)  },
                 help,
-                options,
+                entries,
+                annotations,
+                errors: if errors.is_empty() { None } else { Some(errors) },
         }))
     }
+
+    pub fn collect_options(&self) -> Vec<&KOption<'a>> {
+        self.entries.iter().flat_map(|e| e.collect_options()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{
+        KConfig,
+        MenuItem,
+        MenuTree,
+    };
+
+    #[test]
+    fn entries_in_order() {
+        let input = "\
+choice
+	prompt \"pick\"
+
+config A
+	bool \"a\"
+
+# about b
+comment \"between\"
+
+config B
+	bool \"b\"
+
+endchoice
+";
+        let (rest, choice) = KChoice::parse(input).unwrap();
+        assert_eq!(rest, "\n");
+        let kinds: Vec<&str> = choice.entries.iter().map(|entry| match entry {
+            Entry::Option(opt) => opt.name.name,
+            Entry::Comment(_)  => "comment",
+            _                  => "other",
+        }).collect();
+        assert_eq!(kinds, ["A", "comment", "B"]);
+        assert_eq!(choice.collect_options().len(), 2);
+
+        let text = choice.to_string();
+        let at = |needle: &str| text.find(needle).unwrap();
+        assert!(at("config A") < at("# about b"));
+        assert!(at("# about b") < at("comment \"between\""));
+        assert!(at("comment \"between\"") < at("config B"));
+    }
+
+    #[test]
+    fn blocks_and_sources() {
+        let input = "\
+choice
+	prompt \"pick\"
+	depends on D
+
+config A
+	bool \"a\"
+
+if X
+config B
+	bool \"b\"
+endif
+
+source \"Kconfig.more\"
+
+endchoice
+";
+        let (rest, config) = KConfig::parse(input).unwrap();
+        assert_eq!(rest.trim(), "");
+        let Entry::Choice(choice) = &config.entries[0] else { panic!() };
+        assert!(choice.errors.is_none());
+        assert!(matches!(choice.entries[..], [Entry::Option(_), Entry::Block(_), Entry::Source(_)]));
+        assert_eq!(choice.collect_options().len(), 2);
+
+        // The values in the block become values of the choice, with the block's condition
+        let tree = MenuTree::build(&config);
+        let values: Vec<String> = tree.nodes[tree.roots[0]].children.iter()
+            .map(|&child| match tree.nodes[child].item {
+                MenuItem::Option(opt) => format!("{} {}", opt.name.name, tree.nodes[child].dep.as_ref().unwrap()),
+                _                     => "other".to_string(),
+            })
+            .collect();
+        assert_eq!(values, ["A D", "B D && X"]);
+    }
 }
@@ -1,6 +1,8 @@
 use super::{
+    Annotation,
     Prompt,
     Dependency,
+    Span,
     util::{
         take_line_ending,
     },
@@ -24,12 +26,15 @@ use nom::{
 
 #[derive(Debug)]
 pub struct KCommentBlock<'a> {
-    pub prompt:  Prompt<'a>,
-    pub depends: Option<Vec<Dependency<'a>>>,
+    pub prompt:      Prompt<'a>,
+    pub depends:     Option<Vec<Dependency<'a>>>,
+    pub annotations: Option<Vec<Annotation<'a>>>,
+    pub span:        Span,
 }
 
 impl<'a> KCommentBlock<'a> {
     pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let start = input;
         let mut depends = vec![];
 
         let (input, (prompt, _)) = preceded(
@@ -47,8 +52,10 @@ impl<'a> KCommentBlock<'a> {
             )),
         )(input)?;
         Ok((input, Self {
+                span: Span::between(start, input),
                 prompt,
                 depends: if depends.is_empty() { None } else { Some(depends) },
+                annotations: None,
         }))
     }
 }
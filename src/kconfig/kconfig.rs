@@ -1,88 +1,100 @@
 use super::{
-    KChoice,
-    KCommentBlock,
-    KMenu,
+    Entry,
     KOption,
     Prompt,
-    Block,
     Annotation,
+    Span,
+    entry::attach_trailing,
     util::{
+        ParseError,
+        take_gap,
         take_line_ending,
     },
 };
 
+use std::cell::Cell;
+
 use nom::{
     branch::alt,
     combinator::map,
     multi::many0,
+    sequence::tuple,
     IResult,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct KConfig<'a> {
-    pub mainmenu: Option<Prompt<'a>>,
-    pub blocks:   Option<Vec<Block<'a>>>,
-    pub choices:  Option<Vec<KChoice<'a>>>,
-    pub configs:  Option<Vec<Prompt<'a>>>,
-    pub menus:    Option<Vec<KMenu<'a>>>,
-    pub options:  Option<Vec<KOption<'a>>>,
+    pub mainmenu:    Option<Prompt<'a>>,
+    pub entries:     Vec<Entry<'a>>,
+
+    // `#` comments that come before the first entry without leading up to it, such as those
+    // above `mainmenu`
+    pub annotations: Option<Vec<Annotation<'a>>>,
+    pub errors:      Option<Vec<ParseError<'a>>>,
+    pub span:        Span,
 }
 
 impl<'a> KConfig<'a> {
     pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        Self::parse_inside(None)(input)
+    }
+
+    // The body of an `if` block is parsed like a file of its own, `inside` is only used to give
+    // better context to any errors
+    pub fn parse_inside(inside: Option<(&'static str, &'a str)>) -> impl Fn(&'a str) -> IResult<&'a str, KConfig<'a>> {
+        move |input: &'a str| -> IResult<&'a str, Self> {
+            Self::parse_body(input, inside)
+        }
+    }
+
+    fn parse_body(input: &'a str, inside: Option<(&'static str, &'a str)>) -> IResult<&'a str, Self> {
+        let start = input;
         let mut mainmenu = None;
-        let mut blocks  = vec![];
-        let mut choices = vec![];
-        let mut configs = vec![];
-        let mut menus   = vec![];
-        let mut options = vec![];
+        let mut entries = vec![];
+        let mut loose   = vec![];
+        let mut errors  = vec![];
+        let count = Cell::new(0);
 
         let (input, _) = many0(alt((
+            map(tuple((take_gap, Entry::parse)), |(notes, mut v)| {
+                v.annotate(notes);
+                entries.push(v);
+                count.set(count.get() + 1);
+            }),
             map(take_line_ending,     |_| {}),
-            map(Annotation::parse,    |_| {}),
-            map(Block::parse,         |v| blocks.push(v)),
-            map(Prompt::parse("source"),   |v| configs.push(v)),
+            map(Annotation::parse,    |v| loose.push((count.get(), v))),
             map(Prompt::parse("mainmenu"), |v| mainmenu = Some(v)),
-            map(KOption::parse,       |v| options.push(v)),
-            map(KMenu::parse,         |v| menus.push(v)),
-            map(KChoice::parse,       |v| choices.push(v)),
-            map(KCommentBlock::parse, |_c| {}), //eprintln!("\n```\n{}```\n", _c)),
+            map(ParseError::parse(inside), |v| errors.push(v)),
         )))(input)?;
+        let annotations = attach_trailing(&mut entries, loose);
         Ok((input, Self{
+                span: Span::between(start, input),
                 mainmenu,
-                blocks:  if blocks.is_empty()   { None } else { Some(blocks) },
-                choices: if choices.is_empty()  { None } else { Some(choices) },
-                configs: if configs.is_empty()  { None } else { Some(configs) },
-                menus:   if menus.is_empty()    { None } else { Some(menus) },
-                options: if options.is_empty()  { None } else { Some(options) },
+                entries,
+                annotations,
+                errors:  if errors.is_empty()   { None } else { Some(errors) },
         }))
     }
 
-    pub fn collect_options(&self) -> Vec<&KOption<'a>> {
-        let mut options: Vec<&KOption<'a>> = Vec::new();
-
-        if let Some(opts) = &self.options {
-            options.extend(opts.iter());
+    // Used when recovering from an error at the top of a file, the parts before and after the
+    // broken entry are stitched back together
+    pub fn append(&mut self, other: KConfig<'a>) {
+        if other.mainmenu.is_some() {
+            self.mainmenu = other.mainmenu;
         }
-
-        if let Some(choices) = &self.choices {
-            for choice in choices {
-                options.extend(choice.options.iter());
-            }
-        }
-
-        if let Some(blocks) = &self.blocks {
-            for block in blocks {
-                options.extend(block.config.collect_options());
+        if let Some(notes) = other.annotations {
+            match self.entries.last_mut() {
+                Some(last) => last.trail(notes),
+                None       => self.annotations.get_or_insert_with(Vec::new).extend(notes),
             }
         }
-
-        if let Some(menus) = &self.menus {
-            for menu in menus {
-                options.extend(menu.collect_options());
-            }
+        self.entries.extend(other.entries);
+        if let Some(errors) = other.errors {
+            self.errors.get_or_insert_with(Vec::new).extend(errors);
         }
+    }
 
-        options
+    pub fn collect_options(&self) -> Vec<&KOption<'a>> {
+        self.entries.iter().flat_map(|e| e.collect_options()).collect()
     }
 }
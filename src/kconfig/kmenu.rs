@@ -1,17 +1,20 @@
 use super::{
-    KChoice,
-    KCommentBlock,
+    Entry,
     KOption,
     Dependency,
-    Prompt,
-    Block,
     Annotation,
+    Span,
+    entry::attach_trailing,
     util::{
+        ParseError,
         take_continued_line,
+        take_gap,
         take_line_ending,
     },
 };
 
+use std::cell::Cell;
+
 use nom::{
     branch::alt,
     bytes::complete::tag,
@@ -29,24 +32,24 @@ use nom::{
 #[derive(Debug)]
 pub struct KMenu<'a> {
     pub description: &'a str,
-    pub blocks:  Option<Vec<Block<'a>>>,
-    pub choices: Option<Vec<KChoice<'a>>>,
-    pub configs: Option<Vec<Prompt<'a>>>,
-    pub depends: Option<Vec<Dependency<'a>>>,
-    pub menus:   Option<Vec<KMenu<'a>>>,
-    pub options: Option<Vec<KOption<'a>>>,
-    pub visible: Option<Vec<Dependency<'a>>>,
+    pub depends:     Option<Vec<Dependency<'a>>>,
+    pub visible:     Option<Vec<Dependency<'a>>>,
+    pub entries:     Vec<Entry<'a>>,
+    pub annotations: Option<Vec<Annotation<'a>>>,
+    pub errors:      Option<Vec<ParseError<'a>>>,
+    pub span:        Span,
 }
 
 impl<'a> KMenu<'a> {
     pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        let mut blocks  = vec![];
-        let mut choices = vec![];
-        let mut configs = vec![];
+        let start = input;
         let mut depends = vec![];
-        let mut menus   = vec![];
-        let mut options = vec![];
         let mut visible = vec![];
+        let mut entries = vec![];
+        let mut loose   = vec![];
+        let mut errors  = vec![];
+        let count = Cell::new(0);
+        let inside = Some(("menu", input));
 
         let (input, (description, _)) = delimited(
             tuple((
@@ -57,16 +60,16 @@ impl<'a> KMenu<'a> {
             tuple((
                 take_continued_line,
                 many1(alt((
+                    map(tuple((take_gap, Entry::parse)), |(notes, mut v)| {
+                        v.annotate(notes);
+                        entries.push(v);
+                        count.set(count.get() + 1);
+                    }),
                     map(take_line_ending,     |_| {}),
-                    map(Annotation::parse,    |_| {}),
-                    map(Block::parse,         |v| blocks.push(v)),
-                    map(KChoice::parse,       |v| choices.push(v)),
-                    map(KMenu::parse,         |v| menus.push(v)),
-                    map(KOption::parse,       |v| options.push(v)),
+                    map(Annotation::parse,    |v| loose.push((count.get(), v))),
                     map(Dependency::parse("visible if"), |v| visible.push(v)),
                     map(Dependency::parse("depends on"), |v| depends.push(v)),
-                    map(Prompt::parse("source"),         |v| configs.push(v)),
-                    map(KCommentBlock::parse, |_| {}), // TODO: something useful with these?
+                    map(ParseError::parse(inside), |v| errors.push(v)),
                 ))),
             )),
             tuple((
@@ -76,43 +79,20 @@ impl<'a> KMenu<'a> {
             )),
         )(input)?;
 
+        // Comments in between the menu's own attributes belong to the menu
+        let annotations = attach_trailing(&mut entries, loose);
         Ok((input, Self{
+                span: Span::between(start, input),
                 description,
-                blocks:  if blocks.is_empty()   { None } else { Some(blocks) },
-                choices: if choices.is_empty()  { None } else { Some(choices) },
-                configs: if configs.is_empty()  { None } else { Some(configs) },
                 depends: if depends.is_empty()  { None } else { Some(depends) },
-                menus:   if menus.is_empty()    { None } else { Some(menus) },
-                options: if options.is_empty()  { None } else { Some(options) },
                 visible: if visible.is_empty()  { None } else { Some(visible) },
+                entries,
+                annotations,
+                errors:  if errors.is_empty()   { None } else { Some(errors) },
         }))
     }
 
     pub fn collect_options(&self) -> Vec<&KOption<'a>> {
-        let mut options: Vec<&KOption<'a>> = Vec::new();
-
-        if let Some(opts) = &self.options {
-            options.extend(opts.iter());
-        }
-
-        if let Some(choices) = &self.choices {
-            for choice in choices {
-                options.extend(choice.options.iter());
-            }
-        }
-
-        if let Some(menus) = &self.menus {
-            for menu in menus {
-                options.extend(menu.collect_options());
-            }
-        }
-
-        if let Some(blocks) = &self.blocks {
-            for block in blocks {
-                options.extend(block.config.collect_options());
-            }
-        }
-
-        options
+        self.entries.iter().flat_map(|e| e.collect_options()).collect()
     }
 }
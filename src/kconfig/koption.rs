@@ -6,11 +6,14 @@ use super::{
     Prompt,
     Symbol,
     Annotation,
+    Span,
     util::{
-        take_line_ending,
+        take_gap,
     },
 };
 
+use std::cell::RefCell;
+
 use nom::{
     branch::alt,
     bytes::complete::tag,
@@ -24,20 +27,35 @@ use nom::{
     },
     multi::many1,
     sequence::{
-        preceded,
+        delimited,
         tuple,
     },
     IResult,
 };
 
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum DefaultKind {
+    Default,
+    DefBool,
+    DefTristate,
+}
+
 #[derive(Debug)]
 pub struct KOption<'a> {
     // This field must always exist
     pub name:         Symbol<'a>,
 
+    // Declared with `menuconfig` rather than `config`, the entries that depend on it are shown
+    // as a separate menu instead of indented below it
+    pub menuconfig:   bool,
+
     // This may be inferred from `def_bool` or `def_tristate`
     pub option_type:  OptionType,
 
+    // False when neither a type, `def_bool` nor `def_tristate` was given and `option_type` is only
+    // a guess. These are usually extra definitions adding a default to a symbol typed elsewhere.
+    pub typed:        bool,
+
     // Raw help text, with leading whitespace on each line
     pub help:         Option<Help<'a>>,
 
@@ -64,12 +82,21 @@ pub struct KOption<'a> {
     // as of v6.3.1, the only option that declares def_tristate more than once is `INET_DCCP_DIAG`
     pub def_tristate: Option<Vec<Dependency<'a>>>,
 
+    // Which of `defaults`, `def_bool` and `def_tristate` each default was written as, in the order
+    // they were written. The first one whose condition holds is the one that applies.
+    pub default_order: Vec<DefaultKind>,
+
     // Only valid for `hex` and `int` types
     pub ranges:       Option<Vec<Range<'a>>>,
+
+    // `#` comments leading up to the option and in between its attributes
+    pub annotations:  Option<Vec<Annotation<'a>>>,
+    pub span:         Span,
 }
 
 impl<'a> KOption<'a> {
     pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let start = input;
         let mut opt_option_type = None;
         let mut opt_prompt_from_type = None;
         let mut help = None;
@@ -82,47 +109,62 @@ impl<'a> KOption<'a> {
         let mut defaults = vec![];
         let mut def_bool = vec![];
         let mut def_tristate = vec![];
+        let mut annotations  = vec![];
+        let default_order = RefCell::new(vec![]);
 
         let type_line_parser = tuple((
             OptionType::parse,
             opt(Prompt::parse("")),
         ));
 
-        let (input, (name, _)) = preceded(
-            tuple((
+        let (input, (menuconfig, (name, _))) = tuple((
+            delimited(
                 space0,
-                alt((tag("config"), tag("menuconfig"))),
+                alt((
+                    map(tag("config"),     |_| false),
+                    map(tag("menuconfig"), |_| true),
+                )),
                 space1,
-            )),
+            ),
             tuple((
                 Symbol::parse,
-                many1(alt((
-                    map(take_line_ending,  |_| {}),
-                    map(Annotation::parse, |_| {}),
+                // Comments are only taken when an attribute follows them, anything after the last
+                // attribute is left for the next entry
+                many1(map(tuple((take_gap, alt((
                     map(type_line_parser,  |(opttype, opt_prompt)| {
                         opt_option_type = Some(opttype);
                         opt_prompt_from_type = opt_prompt;
                     }),
-                    map(Dependency::parse("def_tristate"), |v| def_tristate.push(v)),
+                    map(Dependency::parse("def_tristate"), |v| {
+                        default_order.borrow_mut().push(DefaultKind::DefTristate);
+                        def_tristate.push(v);
+                    }),
                     map(Dependency::parse("depends on"),   |v| depends.push(v)),
-                    map(Dependency::parse("def_bool"),     |v| def_bool.push(v)),
-                    map(Dependency::parse("default"),      |v| defaults.push(v)),
+                    map(Dependency::parse("def_bool"),     |v| {
+                        default_order.borrow_mut().push(DefaultKind::DefBool);
+                        def_bool.push(v);
+                    }),
+                    map(Dependency::parse("default"),      |v| {
+                        default_order.borrow_mut().push(DefaultKind::Default);
+                        defaults.push(v);
+                    }),
                     map(Dependency::parse("select"),       |v| selects.push(v)),
                     map(Dependency::parse("imply"),        |v| implies.push(v)),
                     map(Prompt::parse("prompt"),           |v| prompt = Some(v)),
                     map(Range::parse("range"),             |v| ranges.push(v)),
                     map(Help::parse("help"),               |v| help = Some(v)),
                     map(tuple((space1, tag("modules"))),   |_| {}), // NOTE: only shows up once in MODULES option
-                ))),
+                )))), |(notes, _)| annotations.extend(notes))),
             )),
-        )(input)?;
+        ))(input)?;
 
+        let typed = opt_option_type.is_some() || !def_bool.is_empty() || !def_tristate.is_empty();
         let option_type = match opt_option_type {
             Some(option_type) => option_type,
             None => {
-                if def_bool.len() > 0 {
+                if !def_bool.is_empty() {
                     OptionType::Bool
-                } else if def_tristate.len() > 0 {
+                } else if !def_tristate.is_empty() {
                     OptionType::Tristate
                 } else {
                     // Currently there are ~3 dozen options that do not have a type definition
@@ -145,8 +187,11 @@ impl<'a> KOption<'a> {
         }
 
         Ok((input, Self{
+                span: Span::between(start, input),
                 name,
+                menuconfig,
                 option_type,
+                typed,
                 help,
                 prompt,
                 ranges:       if ranges.is_empty()       { None } else { Some(ranges)       },
@@ -156,6 +201,20 @@ impl<'a> KOption<'a> {
                 defaults:     if defaults.is_empty()     { None } else { Some(defaults)     },
                 def_bool:     if def_bool.is_empty()     { None } else { Some(def_bool)     },
                 def_tristate: if def_tristate.is_empty() { None } else { Some(def_tristate) },
+                default_order: default_order.into_inner(),
+                annotations:  if annotations.is_empty()  { None } else { Some(annotations)  },
         }))
     }
+
+    // `default`, `def_bool` and `def_tristate` together, in the order they were written
+    pub fn all_defaults(&self) -> Vec<&Dependency<'a>> {
+        let mut defaults     = self.defaults.iter().flatten();
+        let mut def_bool     = self.def_bool.iter().flatten();
+        let mut def_tristate = self.def_tristate.iter().flatten();
+        self.default_order.iter().filter_map(|kind| match kind {
+            DefaultKind::Default     => defaults.next(),
+            DefaultKind::DefBool     => def_bool.next(),
+            DefaultKind::DefTristate => def_tristate.next(),
+        }).collect()
+    }
 }
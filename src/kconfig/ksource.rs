@@ -0,0 +1,94 @@
+use super::{
+    Annotation,
+    ConstantSymbol,
+    KConfig,
+    KOption,
+    Span,
+};
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{
+        space0,
+        space1,
+    },
+    combinator::map,
+    sequence::{
+        preceded,
+        tuple,
+    },
+    IResult,
+};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SourceKind {
+    Source,   // relative to srctree, must exist
+    RSource,  // relative to the directory of the current file, must exist
+    OSource,  // relative to srctree, silently ignored when nothing matches
+    ORSource, // relative to the current file, silently ignored when nothing matches
+}
+
+impl SourceKind {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            SourceKind::Source   => "source",
+            SourceKind::RSource  => "rsource",
+            SourceKind::OSource  => "osource",
+            SourceKind::ORSource => "orsource",
+        }
+    }
+
+    pub fn is_optional(&self) -> bool {
+        matches!(self, SourceKind::OSource | SourceKind::ORSource)
+    }
+
+    pub fn is_relative(&self) -> bool {
+        matches!(self, SourceKind::RSource | SourceKind::ORSource)
+    }
+}
+
+#[derive(Debug)]
+pub struct KSource<'a> {
+    pub kind:        SourceKind,
+    pub path:        ConstantSymbol<'a>,
+
+    // Every file matched by `path`, in glob order. These are only filled in when the tree is
+    // loaded through a `SourceMap`, parsing a single file leaves this empty.
+    pub configs:     Vec<KConfig<'a>>,
+    pub annotations: Option<Vec<Annotation<'a>>>,
+    pub span:        Span,
+}
+
+impl<'a> KSource<'a> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let start = input;
+        let (input, (kind, path)) = preceded(
+            space0,
+            tuple((
+                alt((
+                    map(tag("orsource"), |_| SourceKind::ORSource),
+                    map(tag("osource"),  |_| SourceKind::OSource),
+                    map(tag("rsource"),  |_| SourceKind::RSource),
+                    map(tag("source"),   |_| SourceKind::Source),
+                )),
+                preceded(space1, ConstantSymbol::parse),
+            )),
+        )(input)?;
+        Ok((input, Self {
+            span: Span::between(start, input),
+            kind,
+            path,
+            configs: vec![],
+            annotations: None,
+        }))
+    }
+
+    pub fn path_str(&self) -> &'a str {
+        self.path.as_str()
+    }
+
+    pub fn collect_options(&self) -> Vec<&KOption<'a>> {
+        self.configs.iter().flat_map(|c| c.collect_options()).collect()
+    }
+}
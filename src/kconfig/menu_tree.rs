@@ -0,0 +1,377 @@
+use super::{
+    Dependency,
+    Entry,
+    Expr,
+    KChoice,
+    KCommentBlock,
+    KConfig,
+    KMenu,
+    KOption,
+    Prompt,
+    Span,
+};
+
+use std::fmt;
+
+// The menu structure as the kernel's configuration interfaces present it. This differs from the
+// parsed tree in a few ways:
+//
+//  * `if` blocks and sourced files do not show up, their entries become part of the enclosing
+//    menu (with the `if` condition added to their dependencies)
+//  * every entry carries its effective dependencies, those of the menus, choices and `if`
+//    blocks around it included
+//  * any config with a prompt becomes a menu of its own when the entries following it depend on
+//    it, `menuconfig` only changes how that menu is displayed
+//  * choices and menus contain their entries directly
+#[derive(Debug)]
+pub struct MenuTree<'k, 'a> {
+    pub mainmenu: Option<&'k Prompt<'a>>,
+    pub nodes:    Vec<MenuNode<'k, 'a>>,
+    pub roots:    Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MenuItem<'k, 'a> {
+    Option(&'k KOption<'a>),
+    Menu(&'k KMenu<'a>),
+    Choice(&'k KChoice<'a>),
+    Comment(&'k KCommentBlock<'a>),
+}
+
+#[derive(Debug)]
+pub struct MenuNode<'k, 'a> {
+    pub item:     MenuItem<'k, 'a>,
+    pub parent:   Option<usize>,
+    pub children: Vec<usize>,
+
+    // The direct dependencies as the kernel computes them: the entry's own `depends on`, and
+    // the conditions of the `if` blocks, menus and choice it is in. `None` means always.
+    pub dep:      Option<Expr<'a>>,
+
+    // Set when the children are only there because they depend on this entry's symbol
+    pub implicit: bool,
+}
+
+impl<'k, 'a> MenuItem<'k, 'a> {
+    pub fn prompt(&self) -> Option<&'k str> {
+        let prompt = match self {
+            MenuItem::Option(v)  => v.prompt.as_ref(),
+            MenuItem::Choice(v)  => v.prompt.as_ref(),
+            MenuItem::Comment(v) => Some(&v.prompt),
+            MenuItem::Menu(v)    => return Some(unquote(v.description)),
+        };
+        prompt.map(|p| p.text.as_str())
+    }
+
+    pub fn span(&self) -> &'k Span {
+        match self {
+            MenuItem::Option(v)  => &v.span,
+            MenuItem::Menu(v)    => &v.span,
+            MenuItem::Choice(v)  => &v.span,
+            MenuItem::Comment(v) => &v.span,
+        }
+    }
+}
+
+// `menu` descriptions are kept as written, quotes included
+fn unquote(text: &str) -> &str {
+    let text = text.trim();
+    text.strip_prefix('"').and_then(|t| t.strip_suffix('"'))
+        .or_else(|| text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')))
+        .unwrap_or(text)
+}
+
+pub fn depends_expr<'a>(depends: &Option<Vec<Dependency<'a>>>) -> Option<Expr<'a>> {
+    depends.iter().flatten().fold(None, |acc, dep| Expr::and(acc, Some(dep.expression.clone())))
+}
+
+impl<'k, 'a> MenuTree<'k, 'a> {
+    pub fn build(config: &'k KConfig<'a>) -> Self {
+        let mut tree = Self {
+            mainmenu: config.mainmenu.as_ref(),
+            nodes:    vec![],
+            roots:    vec![],
+        };
+        let mut flat = vec![];
+        flatten(config, None, &mut flat);
+        let roots = tree.add_all(flat);
+        tree.roots = tree.nest(roots, None);
+        tree
+    }
+
+    fn add_all(&mut self, flat: Vec<(MenuItem<'k, 'a>, Option<Expr<'a>>)>) -> Vec<usize> {
+        flat.into_iter().map(|(item, dep)| self.add(item, dep)).collect()
+    }
+
+    fn add(&mut self, item: MenuItem<'k, 'a>, dep: Option<Expr<'a>>) -> usize {
+        let idx = self.nodes.len();
+        self.nodes.push(MenuNode {
+            item,
+            parent:   None,
+            children: vec![],
+            dep,
+            implicit: false,
+        });
+
+        // Children inherit the dependencies of the menu or choice they are in. A menu's `visible
+        // if` only hides it and is not passed on. `if` blocks and sourced files are flattened into
+        // a choice the same as into a menu.
+        let cond = self.nodes[idx].dep.clone();
+        let entries: &[Entry] = match item {
+            MenuItem::Menu(menu)                       => &menu.entries,
+            MenuItem::Choice(choice)                   => &choice.entries,
+            MenuItem::Option(_) | MenuItem::Comment(_) => &[],
+        };
+        let mut flat = vec![];
+        for entry in entries {
+            flatten_entry(entry, cond.as_ref(), &mut flat);
+        }
+        let children = self.add_all(flat);
+        // The values of a choice never form menus of their own
+        let children = match item {
+            MenuItem::Choice(_) => children,
+            _                   => self.nest(children, Some(idx)),
+        };
+        for &child in &children {
+            self.nodes[child].parent = Some(idx);
+        }
+        self.nodes[idx].children = children;
+        idx
+    }
+
+    // The condition under which the entry's prompt is shown, its dependencies and the prompt's own
+    // `if`. This is what decides whether an entry belongs to an implicit menu.
+    pub fn visibility(&self, idx: usize) -> Option<Expr<'a>> {
+        let node = &self.nodes[idx];
+        let condition = match node.item {
+            MenuItem::Option(v)  => v.prompt.as_ref().and_then(|p| p.condition.clone()),
+            MenuItem::Choice(v)  => v.prompt.as_ref().and_then(|p| p.condition.clone()),
+            MenuItem::Comment(v) => v.prompt.condition.clone(),
+            MenuItem::Menu(_)    => None,
+        };
+        Expr::and(node.dep.clone(), condition)
+    }
+
+    // Group each run of entries that depend on the prompted config just before them under it
+    fn nest(&mut self, list: Vec<usize>, parent: Option<usize>) -> Vec<usize> {
+        let mut out = vec![];
+        let mut i = 0;
+        while i < list.len() {
+            let idx = list[i];
+            self.nodes[idx].parent = parent;
+            out.push(idx);
+            i += 1;
+
+            let MenuItem::Option(opt) = self.nodes[idx].item else {
+                continue;
+            };
+            if opt.prompt.is_none() {
+                continue;
+            }
+            let start = i;
+            while i < list.len() && self.visibility(list[i]).is_some_and(|d| d.depends_on(opt.name.name)) {
+                i += 1;
+            }
+            if i > start {
+                let children = self.nest(list[start..i].to_vec(), Some(idx));
+                self.nodes[idx].children = children;
+                self.nodes[idx].implicit = true;
+            }
+        }
+        out
+    }
+
+    // Every node for a symbol, one for each place it is defined
+    pub fn find(&self, name: &str) -> Vec<usize> {
+        (0..self.nodes.len())
+            .filter(|&idx| matches!(self.nodes[idx].item, MenuItem::Option(opt) if opt.name.name == name))
+            .collect()
+    }
+
+    // The prompts of the menus leading to a node, the way the kernel prints `Location:`
+    pub fn location(&self, idx: usize) -> Vec<&'k str> {
+        let mut path = vec![];
+        let mut current = self.nodes[idx].parent;
+        while let Some(parent) = current {
+            if let Some(prompt) = self.nodes[parent].item.prompt() {
+                path.push(prompt);
+            }
+            current = self.nodes[parent].parent;
+        }
+        if let Some(mainmenu) = self.mainmenu {
+            path.push(mainmenu.text.as_str());
+        }
+        path.reverse();
+        path
+    }
+}
+
+fn flatten<'k, 'a>(config: &'k KConfig<'a>, cond: Option<&Expr<'a>>, out: &mut Vec<(MenuItem<'k, 'a>, Option<Expr<'a>>)>) {
+    for entry in &config.entries {
+        flatten_entry(entry, cond, out);
+    }
+}
+
+fn flatten_entry<'k, 'a>(entry: &'k Entry<'a>, cond: Option<&Expr<'a>>, out: &mut Vec<(MenuItem<'k, 'a>, Option<Expr<'a>>)>) {
+    let with = |dep: Option<Expr<'a>>| Expr::and(cond.cloned(), dep);
+    match entry {
+        Entry::Option(v)  => out.push((MenuItem::Option(v),  with(depends_expr(&v.depends)))),
+        Entry::Menu(v)    => out.push((MenuItem::Menu(v),    with(depends_expr(&v.depends)))),
+        Entry::Choice(v)  => out.push((MenuItem::Choice(v),  with(depends_expr(&v.depends)))),
+        Entry::Comment(v) => out.push((MenuItem::Comment(v), with(depends_expr(&v.depends)))),
+        Entry::Block(block) => {
+            let cond = with(Some(block.condition.clone()));
+            flatten(&block.config, cond.as_ref(), out);
+        },
+        Entry::Source(source) => {
+            for config in &source.configs {
+                flatten(config, cond, out);
+            }
+        },
+    }
+}
+
+// An outline of the menus, indented by depth, roughly as menuconfig lays them out
+impl fmt::Display for MenuTree<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn node(tree: &MenuTree, idx: usize, depth: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let n = &tree.nodes[idx];
+            let indent = "    ".repeat(depth);
+            let submenu = !n.children.is_empty();
+            match n.item {
+                MenuItem::Menu(_) => writeln!(f, "{}{}  --->", indent, n.item.prompt().unwrap_or(""))?,
+                MenuItem::Comment(_) => writeln!(f, "{}*** {} ***", indent, n.item.prompt().unwrap_or(""))?,
+                MenuItem::Choice(_) => writeln!(f, "{}{} (choice)  --->", indent, n.item.prompt().unwrap_or(""))?,
+                MenuItem::Option(opt) => {
+                    let arrow = if opt.menuconfig && submenu { "  --->" } else { "" };
+                    match n.item.prompt() {
+                        Some(prompt) => writeln!(f, "{}{} ({}){}", indent, prompt, opt.name.name, arrow)?,
+                        None         => writeln!(f, "{}({})", indent, opt.name.name)?,
+                    }
+                },
+            }
+            n.children.iter().try_for_each(|&child| node(tree, child, depth + 1, f))
+        }
+        if let Some(mainmenu) = self.mainmenu {
+            writeln!(f, "{}", mainmenu.text.as_str())?;
+        }
+        self.roots.iter().try_for_each(|&idx| node(self, idx, 0, f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build<R>(text: &str, f: impl FnOnce(&MenuTree) -> R) -> R {
+        let (rest, config) = KConfig::parse(text).unwrap();
+        assert_eq!(rest.trim(), "", "not parsed");
+        f(&MenuTree::build(&config))
+    }
+
+    fn dep(tree: &MenuTree, name: &str) -> String {
+        let idx = tree.find(name)[0];
+        tree.nodes[idx].dep.as_ref().map_or("y".to_string(), |d| d.to_string())
+    }
+
+    #[test]
+    fn implicit_menus() {
+        let kconfig = "
+config A
+	bool \"a\"
+
+config B
+	bool \"b\"
+	depends on A
+
+config C
+	bool \"c\"
+	depends on A && B
+
+config D
+	bool \"d\"
+	depends on A && X
+
+config E
+	bool \"e\"
+
+config F
+	bool
+	depends on E
+
+config G
+	bool \"g\"
+	depends on F
+";
+        build(kconfig, |tree| {
+            // Runs of entries that depend on a prompted config nest under it, and nest again
+            // within. A config without a prompt never gets a menu of its own, so `G` ends the run.
+            assert_eq!(tree.to_string(), "\
+a (A)
+    b (B)
+        c (C)
+    d (D)
+e (E)
+    (F)
+g (G)
+");
+            let a = tree.find("A")[0];
+            assert!(tree.nodes[a].implicit);
+            assert_eq!(tree.location(tree.find("C")[0]), ["a", "b"]);
+            assert_eq!(tree.nodes[tree.find("G")[0]].parent, None);
+            assert!(!tree.nodes[tree.find("F")[0]].implicit);
+        });
+    }
+
+    #[test]
+    fn inherited_deps() {
+        let kconfig = "
+mainmenu \"main\"
+
+if X
+
+menu \"m\"
+	depends on Y
+	visible if V
+
+config A
+	bool \"a\"
+	depends on Z
+
+choice
+	prompt \"ch\"
+	depends on W
+
+config B
+	bool \"b\"
+	depends on U
+
+comment \"note\"
+	depends on T
+
+endchoice
+
+endmenu
+
+endif
+
+config C
+	bool \"c\"
+";
+        build(kconfig, |tree| {
+            assert_eq!(dep(tree, "A"), "X && Y && Z");
+            assert_eq!(dep(tree, "B"), "X && Y && W && U");
+            assert_eq!(dep(tree, "C"), "y");
+
+            // `visible if` hides the menu, it is not a dependency of what is in it
+            let menu = tree.nodes.iter().position(|n| matches!(n.item, MenuItem::Menu(_))).unwrap();
+            assert_eq!(tree.nodes[menu].dep.as_ref().unwrap().to_string(), "X && Y");
+            let choice = tree.nodes[menu].children[1];
+            let comment = *tree.nodes[choice].children.last().unwrap();
+            assert!(matches!(tree.nodes[comment].item, MenuItem::Comment(_)));
+            assert_eq!(tree.nodes[comment].dep.as_ref().unwrap().to_string(), "X && Y && W && T");
+            assert_eq!(tree.location(tree.find("B")[0]), ["main", "m", "ch"]);
+            assert_eq!(tree.roots.len(), 2);
+        });
+    }
+}
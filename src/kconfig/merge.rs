@@ -0,0 +1,207 @@
+use super::{
+    Assignment,
+    Cause,
+    Diagnostic,
+    DotConfig,
+    Engine,
+    Env,
+    OptionType,
+    PREFIX,
+    Tristate,
+    Value,
+    assignment_line,
+    cause::{
+        prompt_limits,
+        reverse,
+    },
+    format_number,
+    parse_number,
+};
+
+use std::collections::HashMap;
+
+// Apply configuration fragments in order, the way `scripts/kconfig/merge_config.sh` does. The
+// first is usually a base defconfig and every later one adds to or overrides it. Besides what
+// `DotConfig::apply` reports for each fragment, a symbol given a different value by a later
+// fragment is reported there, and once every value has been calculated each requested value that
+// did not make it into the result is reported with what stood in its way.
+pub fn merge(engine: &mut Engine, fragments: &[DotConfig]) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut requested: HashMap<&str, (usize, &Assignment)> = HashMap::new();
+    for (idx, fragment) in fragments.iter().enumerate() {
+        diagnostics.extend(fragment.apply(engine));
+        for assignment in &fragment.assignments {
+            let name = assignment.name.as_str();
+            if engine.symbols.get(name).is_none() {
+                continue;
+            }
+            let Some((earlier, previous)) = requested.insert(name, (idx, assignment)) else {
+                continue;
+            };
+            // Reassignments within one fragment were reported when it was applied
+            if earlier != idx && previous.value != assignment.value {
+                let mut diagnostic = fragment.diagnostic(assignment.line,
+                    format!("value of `{}{}` redefined by fragment", PREFIX, name));
+                diagnostic.notes.push(format!("previous value `{}` at {}:{}",
+                    raw_line(previous), fragments[earlier].path.display(), previous.line));
+                diagnostic.notes.push(format!("new value `{}`", raw_line(assignment)));
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+
+    // In the order the values were requested in, for each symbol only its last assignment
+    for fragment in fragments {
+        for assignment in &fragment.assignments {
+            let name = assignment.name.as_str();
+            if !requested.get(name).is_some_and(|(_, last)| std::ptr::eq(*last, assignment)) {
+                continue;
+            }
+            let Some(option_type) = engine.symbols.get(name).and_then(|info| info.option_type) else {
+                continue;
+            };
+            let Some(wanted) = assignment.typed(option_type) else {
+                continue;
+            };
+            let actual = engine.written_value(name);
+            if same(&wanted, actual.as_ref(), option_type) || replaced(engine, &requested, name) {
+                continue;
+            }
+            let actual_line = match &actual {
+                Some(value) => format!("`{}`", assignment_line(name, option_type, value)),
+                None        => "not in the final configuration".to_string(),
+            };
+            let mut diagnostic = fragment.diagnostic(assignment.line, format!("requested value `{}` not in final configuration, it is {}",
+                assignment_line(name, option_type, &wanted), actual_line));
+            diagnostic.notes = unmet(engine, name, option_type, &wanted);
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics
+}
+
+// A choice value asked for with `y` loses to another value of the same choice asked for later
+fn replaced(engine: &Engine, requested: &HashMap<&str, (usize, &Assignment)>, name: &str) -> bool {
+    let Some(choice) = engine.symbols.get(name).and_then(|info| engine.choice_of(info)) else {
+        return false;
+    };
+    engine.selection(choice).selected
+        .filter(|other| *other != name)
+        .and_then(|other| requested.get(other))
+        .is_some_and(|(_, assignment)| assignment.value.as_deref() == Some("y"))
+}
+
+fn raw_line(assignment: &Assignment) -> String {
+    match &assignment.value {
+        Some(value) => format!("{}{}={}", PREFIX, assignment.name, value),
+        None        => format!("# {}{} is not set", PREFIX, assignment.name),
+    }
+}
+
+// Numbers are compared by value, so `0x10` and `0X10` or `010` and `10` are the same
+fn same(wanted: &Value, actual: Option<&Value>, option_type: OptionType) -> bool {
+    let Some(actual) = actual else {
+        return false;
+    };
+    match option_type {
+        OptionType::Int | OptionType::Hex => {
+            let number = |value: &Value| parse_number(value.as_str(), option_type);
+            number(wanted).is_some() && number(wanted) == number(actual)
+        },
+        _ => wanted == actual,
+    }
+}
+
+// Why the symbol did not get the value, as a list of notes. Too low means its prompt, the menu
+// around it or its choice kept the user from setting it, too high that something selects it.
+fn unmet(engine: &Engine, name: &str, option_type: OptionType, wanted: &Value) -> Vec<String> {
+    let Some(info) = engine.symbols.get(name) else {
+        return vec![];
+    };
+    let mut notes = vec![];
+    let want = match wanted {
+        Value::Tristate(Tristate::No) => Tristate::Module,
+        Value::Tristate(value)        => *value,
+        Value::Str(_)                 => Tristate::Module,
+    };
+    if engine.visibility(name) < want || engine.visibility(name) == Tristate::No {
+        for cause in prompt_limits(engine, info, want) {
+            notes.push(match cause {
+                Cause::NoPrompt                   => format!("`{}` has no prompt, only defaults and `select` can set it", name),
+                Cause::Dependency { expr, value } => format!("`{}` depends on `{}`, which is {}", name, expr, value.name()),
+                cause                             => format!("`{}` is {}", name, cause),
+            });
+        }
+        if engine.choice_of(info).is_some_and(|choice| engine.selection(choice).mode < want) {
+            notes.push("its choice is not visible".to_string());
+        }
+    }
+    if let (Some(choice), Value::Tristate(Tristate::Yes)) = (engine.choice_of(info), wanted) {
+        if let Some(other) = engine.selection(choice).selected.filter(|other| *other != name) {
+            notes.push(format!("`{}` is selected in its choice instead", other));
+        }
+    }
+    if let Value::Tristate(wanted) = wanted {
+        for (by, value) in reverse(engine, engine.selected_by(name)) {
+            if value > *wanted {
+                notes.push(format!("`{}` is selected by `{}`, which is {}", name, by, value.name()));
+            }
+        }
+        let modules = option_type == OptionType::Tristate && engine.modules() != Tristate::No;
+        if *wanted == Tristate::Module && !modules {
+            notes.push(format!("`{}` can not be a module, so `m` became `y`", name));
+        }
+    }
+    if let (Some((min, max)), Some(n)) = (engine.active_range(info), parse_number(wanted.as_str(), option_type)) {
+        if n < min || n > max {
+            notes.push(format!("`{}` is outside the range {} to {}", wanted.as_str(),
+                format_number(min, option_type), format_number(max, option_type)));
+        }
+    }
+    notes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::engine::tests::with_engine;
+
+    use std::path::Path;
+
+    #[test]
+    fn overrides_and_unmet() {
+        let kconfig = "
+config A
+	bool \"a\"
+
+config B
+	bool \"b\"
+	select C
+
+config C
+	bool \"c\"
+
+config D
+	bool \"d\"
+	depends on A
+
+config E
+	bool
+";
+        let (base, _) = DotConfig::parse(Path::new("base"), "CONFIG_A=y\nCONFIG_B=y\n# CONFIG_C is not set\nCONFIG_D=y\nCONFIG_E=y\n");
+        let (extra, _) = DotConfig::parse(Path::new("extra"), "# CONFIG_A is not set\nCONFIG_B=y\n");
+        let diagnostics = with_engine(kconfig, |engine| merge(engine, &[base, extra]));
+        let text: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(text, [
+            "extra:1:1: warning: value of `CONFIG_A` redefined by fragment\n  \
+                note: previous value `CONFIG_A=y` at base:1\n  \
+                note: new value `# CONFIG_A is not set`",
+            "base:3:1: warning: requested value `# CONFIG_C is not set` not in final configuration, it is `CONFIG_C=y`\n  \
+                note: `C` is selected by `B`, which is y",
+            "base:4:1: warning: requested value `CONFIG_D=y` not in final configuration, it is `# CONFIG_D is not set`\n  \
+                note: `D` depends on `A`, which is n",
+            "base:5:1: warning: requested value `CONFIG_E=y` not in final configuration, it is `# CONFIG_E is not set`\n  \
+                note: `E` has no prompt, only defaults and `select` can set it",
+        ]);
+    }
+}
@@ -1,19 +1,170 @@
 mod kchoice;
+mod circuit;
+mod diagnostic;
+mod diff;
+mod dotconfig;
 mod kcommentblock;
+mod entry;
+mod engine;
+mod cause;
+mod explain;
+#[allow(clippy::module_inception)]
 mod kconfig;
+mod conf;
+mod cst;
+mod dead;
+mod format;
+mod generate;
 mod kmenu;
+mod menu_tree;
+mod merge;
+mod newconfig;
+mod sat;
+mod solver;
 mod koption;
+mod preprocess;
+mod shell;
+mod ksource;
+mod source_map;
+mod span;
+mod symtab;
 mod expr;
+mod eval;
+mod undefined;
 mod util;
 mod display;
 
 pub use kchoice::KChoice;
+pub use diagnostic::{
+    Diagnostic,
+    FileText,
+    Severity,
+};
+pub use dead::dead_code;
+pub use diff::{
+    Change,
+    diff,
+    format_diff,
+};
+pub use dotconfig::{
+    Assignment,
+    DotConfig,
+    PREFIX,
+    assignment_line,
+    escape,
+    write_defconfig,
+    write_dotconfig,
+};
 pub use kcommentblock::KCommentBlock;
+pub use entry::Entry;
+pub use engine::{
+    Engine,
+    Reverse,
+    Selection,
+    format_number,
+    parse_number,
+};
+pub use cause::{
+    Cause,
+    causes,
+};
+pub use explain::explain;
 pub use kconfig::KConfig;
+pub use conf::oldconfig;
+pub use cst::{
+    Cst,
+    CstAttr,
+    CstBlock,
+    CstEntry,
+    CstLine,
+    CstNode,
+};
+pub use format::format;
+pub use generate::{
+    Generator,
+    Probability,
+    Rng,
+    generate,
+};
 pub use kmenu::KMenu;
-pub use koption::KOption;
+pub use menu_tree::{
+    MenuItem,
+    MenuNode,
+    MenuTree,
+    depends_expr,
+};
+pub use merge::merge;
+pub use newconfig::{
+    NewSymbol,
+    help_new,
+    list_new,
+    new_json,
+    new_symbols,
+};
+pub use koption::{
+    DefaultKind,
+    KOption,
+};
+pub use sat::{
+    Lit,
+    Sat,
+};
+pub use solver::{
+    Solution,
+    solve,
+};
+pub use preprocess::{
+    Flavor,
+    LineState,
+    Location,
+    MacroError,
+    Preprocessor,
+    Variable,
+};
+pub use shell::{
+    HostShell,
+    NoShell,
+    ProfileError,
+    Recorder,
+    ShellHandler,
+    ShellOutput,
+    ToolchainProfile,
+};
+pub use ksource::{
+    KSource,
+    SourceKind,
+};
+pub use symtab::{
+    Definition,
+    Property,
+    SymbolInfo,
+    SymbolTable,
+};
+pub use span::{
+    Span,
+    SpanGuard,
+    enter_file,
+};
+pub use source_map::{
+    FileId,
+    LoadError,
+    SourceFile,
+    SourceMap,
+};
+pub use eval::{
+    Env,
+    Value,
+    eval_opt,
+};
+pub use expr::{
+    Expr,
+    VarType,
+    CompareOp,
+    Tristate,
+    parse_expr,
+};
+pub use undefined::undefined_symbols;
 pub use util::{
-    Expression,
     Range,
     RangeType,
     Dependency,
@@ -27,4 +178,5 @@ pub use util::{
     ConstantSymbol,
     Delimiter,
     Annotation,
+    ParseError,
 };
@@ -0,0 +1,227 @@
+use super::{
+    Engine,
+    MenuItem,
+    OptionType,
+    PREFIX,
+    Value,
+    escape,
+};
+
+use std::collections::HashSet;
+
+// A symbol the user could set but has no value for yet, usually because it is new since the
+// `.config` was written. This is what `make listnewconfig` and `make helpnewconfig` report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewSymbol<'k, 'a> {
+    pub name:        &'a str,
+    pub option_type: OptionType,
+
+    // What the symbol gets when nobody answers for it
+    pub value:       Value,
+    pub prompt:      Option<&'k str>,
+
+    // The menus leading to the prompt, outermost first
+    pub location:    Vec<&'k str>,
+    pub help:        Option<Vec<String>>,
+}
+
+// In menu order, each symbol once, at the first of its prompts that is new
+pub fn new_symbols<'k, 'a>(engine: &Engine<'_, 'k, 'a>) -> Vec<NewSymbol<'k, 'a>> {
+    let tree = engine.tree;
+    let mut seen = HashSet::new();
+    let mut out = vec![];
+    for (idx, node) in tree.nodes.iter().enumerate() {
+        let MenuItem::Option(opt) = node.item else {
+            continue;
+        };
+        let name = opt.name.name;
+        if engine.user_value(name).is_some() || !engine.is_changeable(name) || seen.contains(name) {
+            continue;
+        }
+        let Some(info) = engine.symbols.get(name) else {
+            continue;
+        };
+        let (Some(option_type), Some(value)) = (info.option_type, engine.value(name)) else {
+            continue;
+        };
+        seen.insert(name);
+        out.push(NewSymbol {
+            name,
+            option_type,
+            value,
+            prompt:   node.item.prompt(),
+            location: tree.location(idx),
+            help:     info.definitions.iter().find_map(|def| def.option.help.as_ref()).map(|help| help.lines()),
+        });
+    }
+    out
+}
+
+// `CONFIG_FOO=value` for each symbol, as `make listnewconfig` prints it. Unlike `.config`, `n` is
+// written out as a value.
+pub fn list_new(symbols: &[NewSymbol]) -> String {
+    symbols.iter()
+        .map(|symbol| match (&symbol.value, symbol.option_type) {
+            (Value::Str(text), OptionType::Str) => format!("{}{}={}\n", PREFIX, symbol.name, escape(text)),
+            (value, _)                          => format!("{}{}={}\n", PREFIX, symbol.name, value.as_str()),
+        })
+        .collect()
+}
+
+// The help screen of each symbol between `-----` lines, as `make helpnewconfig` prints it
+pub fn help_new(symbols: &[NewSymbol]) -> String {
+    let mut out = String::new();
+    for symbol in symbols {
+        out.push_str("-----\n");
+        out.push_str(&format!("{}{}:\n\n", PREFIX, symbol.name));
+        match &symbol.help {
+            Some(help) => {
+                for line in help {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+            },
+            None => out.push_str("There is no help available for this option.\n"),
+        }
+        out.push_str(&format!("\nSymbol: {} [={}]\n", symbol.name, symbol.value.as_str()));
+        out.push_str(&format!("Type  : {}\n", symbol.option_type));
+        if let Some(prompt) = symbol.prompt {
+            out.push_str(&format!("Prompt: {}\n", prompt));
+        }
+        if !symbol.location.is_empty() {
+            out.push_str("  Location:\n");
+            for (depth, menu) in symbol.location.iter().enumerate() {
+                out.push_str(&format!("  {}-> {}\n", "  ".repeat(depth + 1), menu));
+            }
+        }
+        out.push_str("-----\n");
+    }
+    out
+}
+
+// The same as a JSON array of objects, one per symbol, with `null` for a missing prompt or help
+pub fn new_json(symbols: &[NewSymbol]) -> String {
+    let mut out = String::from("[");
+    for (i, symbol) in symbols.iter().enumerate() {
+        out.push_str(if i == 0 { "\n" } else { ",\n" });
+        let location: Vec<String> = symbol.location.iter().map(|menu| json_string(menu)).collect();
+        out.push_str(&format!("  {{\"name\": {}, \"type\": {}, \"value\": {}, \"prompt\": {}, \"location\": [{}], \"help\": {}}}",
+            json_string(symbol.name),
+            json_string(&symbol.option_type.to_string()),
+            json_string(symbol.value.as_str()),
+            symbol.prompt.map_or_else(|| "null".to_string(), json_string),
+            location.join(", "),
+            symbol.help.as_ref().map_or_else(|| "null".to_string(), |help| json_string(&help.join("\n")))));
+    }
+    out.push_str(if symbols.is_empty() { "]\n" } else { "\n]\n" });
+    out
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"'                    => out.push_str("\\\""),
+            '\\'                   => out.push_str("\\\\"),
+            '\n'                   => out.push_str("\\n"),
+            '\t'                   => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c                      => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{
+        Tristate,
+        engine::tests::with_engine,
+    };
+
+    const KCONFIG: &str = "
+mainmenu \"Test\"
+
+config OLD
+	bool \"old\"
+
+menu \"Drivers\"
+
+config NEW
+	tristate \"new driver\"
+	default m
+	help
+	  Drives things.
+
+	  Tab\there.
+
+config NAME
+	string \"name\"
+	default \"x\\\\y\"
+
+config FIXED
+	bool
+	default y
+
+endmenu
+
+config NEW
+	tristate \"new again\"
+";
+
+    fn output(f: impl Fn(&[NewSymbol]) -> String) -> String {
+        with_engine(KCONFIG, |engine| {
+            engine.set("OLD", Value::Tristate(Tristate::No));
+            f(&new_symbols(engine))
+        })
+    }
+
+    #[test]
+    fn list_and_help() {
+        // `OLD` has a value and `FIXED` has no prompt, `NEW` is listed once. Without `MODULES` its
+        // default of `m` is `y`.
+        assert_eq!(output(list_new), "CONFIG_NEW=y\nCONFIG_NAME=\"x\\\\y\"\n");
+        assert_eq!(output(help_new), "\
+-----
+CONFIG_NEW:
+
+Drives things.
+
+Tab\there.
+
+Symbol: NEW [=y]
+Type  : tristate
+Prompt: new driver
+  Location:
+    -> Test
+      -> Drivers
+-----
+-----
+CONFIG_NAME:
+
+There is no help available for this option.
+
+Symbol: NAME [=x\\y]
+Type  : string
+Prompt: name
+  Location:
+    -> Test
+      -> Drivers
+-----
+");
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(output(new_json), "\
+[
+  {\"name\": \"NEW\", \"type\": \"tristate\", \"value\": \"y\", \"prompt\": \"new driver\", \"location\": [\"Test\", \"Drivers\"], \"help\": \"Drives things.\\n\\nTab\\there.\"},
+  {\"name\": \"NAME\", \"type\": \"string\", \"value\": \"x\\\\y\", \"prompt\": \"name\", \"location\": [\"Test\", \"Drivers\"], \"help\": null}
+]
+");
+        with_engine("config A\n\tbool\n", |engine| assert_eq!(new_json(&new_symbols(engine)), "[]\n"));
+    }
+}
@@ -0,0 +1,492 @@
+use super::{
+    shell::{
+        ShellHandler,
+        NoShell,
+    },
+    util::count_whitespace,
+};
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+
+// Guard against runaway recursion such as `A = $(B)` and `B = $(A)` being called with arguments,
+// which the self reference check below cannot catch.
+const MAX_EXPANSION_DEPTH: usize = 1000;
+
+#[derive(Debug)]
+pub struct MacroError {
+    pub file:    String,
+    pub line:    usize,
+    pub message: String,
+}
+
+impl fmt::Display for MacroError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for MacroError {}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Flavor {
+    Simple,    // `:=` expanded once, when assigned
+    Recursive, // `=` expanded every time it is referenced
+}
+
+#[derive(Debug)]
+pub struct Variable {
+    pub flavor: Flavor,
+    pub value:  String,
+    expanding:  usize,
+}
+
+// Where in the input we are, used for `$(filename)`, `$(lineno)` and error reporting
+#[derive(Debug, Clone, Copy)]
+pub struct Location<'f> {
+    pub file: &'f str,
+    pub line: usize,
+}
+
+impl Location<'_> {
+    fn error(&self, message: String) -> MacroError {
+        MacroError {
+            file: self.file.to_string(),
+            line: self.line,
+            message,
+        }
+    }
+}
+
+// Tracks the parts of a file where macros must not be expanded. This has to be carried from one
+// line to the next, since help text and continued lines depend on what came before them.
+#[derive(Debug, Default)]
+pub struct LineState {
+    help:      Option<Option<usize>>, // `Some(None)` until the first line of help text sets the indent
+    continued: bool,
+    statement: bool,
+}
+
+impl LineState {
+    // Whether the last processed line begins a new statement, rather than being help text or the
+    // continuation of the line before it
+    pub fn is_statement(&self) -> bool {
+        self.statement
+    }
+}
+
+// Nothing is ever executed unless a handler which does so is handed in explicitly, by default
+// `$(shell,...)` expands to nothing
+#[derive(Debug)]
+pub struct Preprocessor<S = NoShell> {
+    variables: HashMap<String, Variable>,
+    env:       HashMap<String, String>,
+    shell:     S,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Self::new(env::vars().collect(), NoShell::default())
+    }
+}
+
+impl<S: ShellHandler> Preprocessor<S> {
+    pub fn new(env: HashMap<String, String>, shell: S) -> Self {
+        Self {
+            variables: HashMap::new(),
+            env,
+            shell,
+        }
+    }
+
+    pub fn shell(&self) -> &S {
+        &self.shell
+    }
+
+    pub fn into_shell(self) -> S {
+        self.shell
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.get(name)
+    }
+
+    // Expand a single physical line. Variable assignments are consumed and replaced with an empty
+    // line so that line numbers in the expanded text still match the original file.
+    pub fn process_line(&mut self, state: &mut LineState, loc: Location, line: &str) -> Result<String, MacroError> {
+        let (body, ending) = match line.strip_suffix('\n') {
+            Some(body) => (body, "\n"),
+            None       => (line, ""),
+        };
+
+        state.statement = false;
+        if let Some(indent) = state.help {
+            let ws = count_whitespace(&body[..body.len() - body.trim_start().len()]);
+            match indent {
+                _ if body.trim().is_empty()  => return Ok(line.to_string()),
+                None if ws > 0               => {
+                    state.help = Some(Some(ws));
+                    return Ok(line.to_string());
+                },
+                Some(min) if ws >= min       => return Ok(line.to_string()),
+                _                            => state.help = None,
+            }
+        }
+
+        let continuation = state.continued;
+        state.continued = body.ends_with('\\');
+
+        let (code, comment) = body.split_at(comment_start(body));
+        if !continuation {
+            state.statement = true;
+            if let Some((name, op, value)) = split_assignment(code) {
+                self.assign(loc, name, op, value)?;
+                return Ok(ending.to_string());
+            }
+            let keyword = code.trim();
+            if keyword == "help" || keyword == "---help---" {
+                state.help = Some(None);
+            }
+        }
+
+        Ok(format!("{}{}{}", self.expand(loc, code, &[])?, comment, ending))
+    }
+
+    pub fn expand(&mut self, loc: Location, input: &str, args: &[String]) -> Result<String, MacroError> {
+        let mut out = String::new();
+        let mut rest = input;
+        while let Some(start) = rest.find("$(") {
+            out.push_str(&rest[..start]);
+            let end = matching_paren(&rest[start + 2..])
+                .ok_or_else(|| loc.error(format!("unterminated reference to '{}'", &rest[start..])))?;
+            let clause = &rest[start + 2..start + 2 + end];
+            out.push_str(&self.eval_clause(loc, clause, args)?);
+            rest = &rest[start + 2 + end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    fn eval_clause(&mut self, loc: Location, clause: &str, args: &[String]) -> Result<String, MacroError> {
+        let mut parts = split_args(clause).into_iter();
+        let name = self.expand(loc, parts.next().unwrap_or(""), args)?;
+        let mut call_args = vec![];
+        for part in parts {
+            call_args.push(self.expand(loc, part, args)?);
+        }
+
+        // `$(call,func,args)` is accepted as a more Make-like spelling of `$(func,args)`
+        let (name, call_args) = if name == "call" && !call_args.is_empty() {
+            let func = call_args.remove(0);
+            (func, call_args)
+        } else {
+            (name, call_args)
+        };
+
+        // Positional arguments of the user-defined function currently being expanded
+        if call_args.is_empty() {
+            if let Ok(n) = name.parse::<usize>() {
+                if n > 0 && n <= args.len() {
+                    return Ok(args[n - 1].clone());
+                }
+            }
+        }
+
+        if let Some(value) = self.expand_variable(loc, &name, &call_args)? {
+            return Ok(value);
+        }
+        if let Some(value) = self.call_function(loc, &name, &call_args)? {
+            return Ok(value);
+        }
+        if call_args.is_empty() {
+            if let Some(value) = self.env.get(&name) {
+                return Ok(value.clone());
+            }
+        }
+        Ok(String::new())
+    }
+
+    fn expand_variable(&mut self, loc: Location, name: &str, args: &[String]) -> Result<Option<String>, MacroError> {
+        let (flavor, value) = match self.variables.get_mut(name) {
+            Some(var) => {
+                if args.is_empty() && var.expanding > 0 {
+                    return Err(loc.error(format!("Recursive variable '{}' references itself (eventually)", name)));
+                }
+                if var.expanding > MAX_EXPANSION_DEPTH {
+                    return Err(loc.error("Too deep recursive expansion".to_string()));
+                }
+                var.expanding += 1;
+                (var.flavor, var.value.clone())
+            },
+            None => return Ok(None),
+        };
+
+        let result = match flavor {
+            Flavor::Recursive => self.expand(loc, &value, args),
+            Flavor::Simple    => Ok(value),
+        };
+        if let Some(var) = self.variables.get_mut(name) {
+            var.expanding -= 1;
+        }
+        result.map(Some)
+    }
+
+    fn call_function(&mut self, loc: Location, name: &str, args: &[String]) -> Result<Option<String>, MacroError> {
+        let arity = |n: usize| -> Result<(), MacroError> {
+            if args.len() < n {
+                return Err(loc.error(format!("too few function arguments passed to '{}'", name)));
+            }
+            if args.len() > n {
+                return Err(loc.error(format!("too many function arguments passed to '{}'", name)));
+            }
+            Ok(())
+        };
+
+        let value = match name {
+            // The kernel prints this to stdout, but here stdout carries the output of commands
+            // such as `listnewconfig --json`, so it goes to stderr with the warnings
+            "info" => {
+                arity(1)?;
+                eprintln!("{}", args[0]);
+                String::new()
+            },
+            "warning-if" => {
+                arity(2)?;
+                if args[0] == "y" {
+                    eprintln!("{}:{}: {}", loc.file, loc.line, args[1]);
+                }
+                String::new()
+            },
+            "error-if" => {
+                arity(2)?;
+                if args[0] == "y" {
+                    return Err(loc.error(args[1].clone()));
+                }
+                String::new()
+            },
+            "filename" => {
+                arity(0)?;
+                loc.file.to_string()
+            },
+            "lineno" => {
+                arity(0)?;
+                loc.line.to_string()
+            },
+            "shell" => {
+                arity(1)?;
+                let output = self.shell.shell(&args[0]).map_err(|e| loc.error(e))?;
+                shell_output(&output.stdout)
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(value))
+    }
+
+    fn assign(&mut self, loc: Location, name: &str, op: &str, value: &str) -> Result<(), MacroError> {
+        let name = self.expand(loc, name, &[])?;
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace()) {
+            return Err(loc.error(format!("invalid variable name '{}'", name)));
+        }
+
+        let existing = self.variables.get(&name).map(|var| var.flavor);
+        let (flavor, value) = match (op, existing) {
+            (":=", _)            => (Flavor::Simple, self.expand(loc, value, &[])?),
+            ("+=", Some(flavor)) => {
+                let appended = match flavor {
+                    Flavor::Simple    => self.expand(loc, value, &[])?,
+                    Flavor::Recursive => value.to_string(),
+                };
+                let current = &self.variables[&name].value;
+                if current.is_empty() {
+                    (flavor, appended)
+                } else {
+                    (flavor, format!("{} {}", current, appended))
+                }
+            },
+            _                    => (Flavor::Recursive, value.to_string()),
+        };
+
+        self.variables.insert(name, Variable {
+            flavor,
+            value,
+            expanding: 0,
+        });
+        Ok(())
+    }
+}
+
+// `$(shell,...)` output has newlines replaced by spaces, minus the trailing newline, like Make
+pub fn shell_output(stdout: &str) -> String {
+    stdout.strip_suffix('\n').unwrap_or(stdout).replace('\n', " ")
+}
+
+// Offset of the closing parenthesis matching an already consumed `(`
+fn matching_paren(input: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(idx),
+            ')' => depth -= 1,
+            _   => {},
+        }
+    }
+    None
+}
+
+// Split a clause on the commas which are not nested inside another reference
+fn split_args(clause: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in clause.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&clause[start..idx]);
+                start = idx + 1;
+            },
+            _ => {},
+        }
+    }
+    parts.push(&clause[start..]);
+    parts
+}
+
+// Byte offset where a `#` comment starts, ignoring any inside quotes or macro references
+fn comment_start(line: &str) -> usize {
+    let mut quote = None;
+    let mut depth = 0;
+    let mut escaped = false;
+    for (idx, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (c, quote) {
+            ('\\', _)                        => escaped = true,
+            ('"' | '\'', None)               => quote = Some(c),
+            (q, Some(open)) if q == open     => quote = None,
+            ('(', _)                         => depth += 1,
+            (')', _) if depth > 0            => depth -= 1,
+            ('#', None) if depth == 0        => return idx,
+            _                                => {},
+        }
+    }
+    line.len()
+}
+
+// Recognise `NAME := value`, `NAME = value` and `NAME += value`. The name may itself contain
+// references, e.g. `$(arch)-flags := ...`
+fn split_assignment(line: &str) -> Option<(&str, &str, &str)> {
+    let trimmed = line.trim_start();
+    let mut idx = 0;
+    while idx < trimmed.len() {
+        let rest = &trimmed[idx..];
+        if let Some(reference) = rest.strip_prefix("$(") {
+            idx += 2 + matching_paren(reference)? + 1;
+            continue;
+        }
+        match rest.chars().next() {
+            Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-' => idx += 1,
+            _ => break,
+        }
+    }
+    if idx == 0 {
+        return None;
+    }
+
+    let name = &trimmed[..idx];
+    let rest = trimmed[idx..].trim_start_matches([' ', '\t']);
+    for op in [":=", "+=", "="] {
+        if let Some(value) = rest.strip_prefix(op) {
+            return Some((name, op, value.trim_start_matches([' ', '\t'])));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(text: &str) -> Result<String, MacroError> {
+        let mut macros = Preprocessor::new(HashMap::from([("ARCH".to_string(), "x86".to_string())]), NoShell::default());
+        let mut state = LineState::default();
+        let mut out = String::new();
+        for (idx, line) in text.split_inclusive('\n').enumerate() {
+            let loc = Location { file: "Kconfig", line: idx + 1 };
+            out.push_str(&macros.process_line(&mut state, loc, line)?);
+        }
+        Ok(out)
+    }
+
+    #[test]
+    fn assignments() {
+        let text = "\
+A := a
+B = $(A)
+A := b
+C := $(B)
+A += c
+D = x
+D += $(A)
+E += e
+A := z
+$(C) $(A) $(D) $(E) $(ARCH) $(UNSET).
+";
+        // Lines with an assignment are left empty so the line numbers stay the same
+        assert_eq!(run(text).unwrap(), "\n\n\n\n\n\n\n\n\nb z x z e x86 .\n");
+    }
+
+    #[test]
+    fn functions() {
+        let text = "\
+pair = $(1)-$(2)
+$(pair,a,b) $(call,pair,c,d) $(pair,$(pair,e,f),g)
+$(filename):$(lineno)
+";
+        assert_eq!(run(text).unwrap(), "\na-b c-d e-f-g\nKconfig:3\n");
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text: &str| run(text).unwrap_err().to_string();
+        assert_eq!(error("X = $(X)\n\n$(X)\n"), "Kconfig:3: Recursive variable 'X' references itself (eventually)");
+        assert_eq!(error("$(error-if,y,stop here)\n"), "Kconfig:1: stop here");
+        assert_eq!(error("$(warning-if,y)\n"), "Kconfig:1: too few function arguments passed to 'warning-if'");
+        assert_eq!(error("$(info,a,b)\n"), "Kconfig:1: too many function arguments passed to 'info'");
+        assert_eq!(error("$(A\n"), "Kconfig:1: unterminated reference to '$(A'");
+
+        assert_eq!(run("$(error-if,n,fine)$(warning-if,y,careful)\n").unwrap(), "\n");
+    }
+
+    #[test]
+    fn help_and_continuations() {
+        let text = "\
+V := v
+config A
+\tbool \"$(V)\"
+\thelp
+\t  Help text keeps $(V) and
+\t  X := is not an assignment
+
+\t  even after a blank line.
+\tdepends on B || \\
+\t\tX = $(V)
+";
+        assert_eq!(run(text).unwrap(), "\
+\nconfig A
+\tbool \"v\"
+\thelp
+\t  Help text keeps $(V) and
+\t  X := is not an assignment
+
+\t  even after a blank line.
+\tdepends on B || \\
+\t\tX = v
+");
+    }
+}
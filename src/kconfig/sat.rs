@@ -0,0 +1,414 @@
+use std::ops;
+
+// A variable or its negation, as `var * 2 + negated`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub fn new(var: usize, positive: bool) -> Self {
+        Lit((var as u32) << 1 | u32::from(!positive))
+    }
+
+    pub fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 & 1 == 0
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl ops::Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+// A small CDCL solver: two watched literals, first-UIP clause learning and activity based
+// decisions, without restarts or clause deletion. The problems it gets from the configuration
+// solver are small and mostly easy, what matters more is that it can solve under assumptions and
+// tell which of them were in the way.
+#[derive(Debug, Default)]
+pub struct Sat {
+    clauses:   Vec<Vec<Lit>>,
+
+    // For each literal, the clauses watching it
+    watches:   Vec<Vec<usize>>,
+    values:    Vec<Option<bool>>,
+    levels:    Vec<usize>,
+    reasons:   Vec<Option<usize>>,
+    trail:     Vec<Lit>,
+
+    // Where each decision level starts on the trail
+    limits:    Vec<usize>,
+    head:      usize,
+    activity:  Vec<f64>,
+    increment: f64,
+
+    // The value tried first when deciding on a variable
+    phase:     Vec<bool>,
+    unsat:     bool,
+}
+
+impl Sat {
+    pub fn new() -> Self {
+        Self {
+            increment: 1.0,
+            ..Self::default()
+        }
+    }
+
+    pub fn new_var(&mut self) -> usize {
+        self.values.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.activity.push(0.0);
+        self.phase.push(false);
+        self.watches.push(vec![]);
+        self.watches.push(vec![]);
+        self.values.len() - 1
+    }
+
+    pub fn set_phase(&mut self, var: usize, value: bool) {
+        self.phase[var] = value;
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.values[lit.var()].map(|value| value == lit.is_positive())
+    }
+
+    // Only between calls to `solve`, when nothing but facts is assigned
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        let mut clause: Vec<Lit> = vec![];
+        for &lit in lits {
+            match self.value(lit) {
+                Some(true)                     => return,
+                Some(false)                    => {},
+                None if clause.contains(&!lit) => return,
+                None if clause.contains(&lit)  => {},
+                None                           => clause.push(lit),
+            }
+        }
+        match clause.len() {
+            0 => self.unsat = true,
+            1 => {
+                self.enqueue(clause[0], None);
+                if self.propagate().is_some() {
+                    self.unsat = true;
+                }
+            },
+            _ => {
+                self.attach(clause);
+            },
+        }
+    }
+
+    fn attach(&mut self, clause: Vec<Lit>) -> usize {
+        let idx = self.clauses.len();
+        self.watches[clause[0].index()].push(idx);
+        self.watches[clause[1].index()].push(idx);
+        self.clauses.push(clause);
+        idx
+    }
+
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.values[var] = Some(lit.is_positive());
+        self.levels[var] = self.limits.len();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    // Assign everything the clauses imply, returning a clause that became false
+    fn propagate(&mut self) -> Option<usize> {
+        while self.head < self.trail.len() {
+            let falsified = !self.trail[self.head];
+            self.head += 1;
+            let mut watching = std::mem::take(&mut self.watches[falsified.index()]);
+            let mut i = 0;
+            let mut conflict = None;
+            while i < watching.len() {
+                let idx = watching[i];
+                if self.clauses[idx][0] == falsified {
+                    self.clauses[idx].swap(0, 1);
+                }
+                let first = self.clauses[idx][0];
+                if self.value(first) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+                let replacement = (2..self.clauses[idx].len()).find(|&k| self.value(self.clauses[idx][k]) != Some(false));
+                if let Some(k) = replacement {
+                    self.clauses[idx].swap(1, k);
+                    let watch = self.clauses[idx][1];
+                    self.watches[watch.index()].push(idx);
+                    watching.swap_remove(i);
+                    continue;
+                }
+                if self.value(first) == Some(false) {
+                    conflict = Some(idx);
+                    break;
+                }
+                self.enqueue(first, Some(idx));
+                i += 1;
+            }
+            self.watches[falsified.index()].extend(watching);
+            if conflict.is_some() {
+                self.head = self.trail.len();
+                return conflict;
+            }
+        }
+        None
+    }
+
+    // The first-UIP clause learnt from a conflict, with the level to jump back to
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let level = self.limits.len();
+        let mut seen = vec![false; self.values.len()];
+        let mut learnt = vec![];
+        let mut pending = 0;
+        let mut clause = conflict;
+        let mut index = self.trail.len();
+        let uip = loop {
+            for k in 0..self.clauses[clause].len() {
+                let lit = self.clauses[clause][k];
+                let var = lit.var();
+                if seen[var] || self.levels[var] == 0 || self.value(lit) == Some(true) {
+                    continue;
+                }
+                seen[var] = true;
+                self.bump(var);
+                if self.levels[var] == level {
+                    pending += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+            let lit = loop {
+                index -= 1;
+                if seen[self.trail[index].var()] {
+                    break self.trail[index];
+                }
+            };
+            seen[lit.var()] = false;
+            pending -= 1;
+            if pending == 0 {
+                break lit;
+            }
+            clause = self.reasons[lit.var()].expect("only decisions lack a reason");
+        };
+        learnt.insert(0, !uip);
+        // The literal of the highest remaining level is watched, so the clause wakes up first
+        let mut back = 0;
+        for k in 1..learnt.len() {
+            let level = self.levels[learnt[k].var()];
+            if level > back {
+                back = level;
+                learnt.swap(1, k);
+            }
+        }
+        self.increment /= 0.95;
+        (learnt, back)
+    }
+
+    fn bump(&mut self, var: usize) {
+        self.activity[var] += self.increment;
+        if self.activity[var] > 1e100 {
+            for activity in &mut self.activity {
+                *activity *= 1e-100;
+            }
+            self.increment *= 1e-100;
+        }
+    }
+
+    // The assumptions that together force `lit` false
+    fn analyze_final(&self, lit: Lit) -> Vec<Lit> {
+        let mut core = vec![lit];
+        let Some(&start) = self.limits.first() else {
+            return core;
+        };
+        let mut seen = vec![false; self.values.len()];
+        seen[lit.var()] = true;
+        for &assigned in self.trail[start..].iter().rev() {
+            let var = assigned.var();
+            if !seen[var] {
+                continue;
+            }
+            match self.reasons[var] {
+                None         => core.push(assigned),
+                Some(reason) => {
+                    for &other in &self.clauses[reason] {
+                        if self.levels[other.var()] > 0 {
+                            seen[other.var()] = true;
+                        }
+                    }
+                },
+            }
+        }
+        core
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.limits.len() <= level {
+            return;
+        }
+        for lit in self.trail.drain(self.limits[level]..) {
+            self.values[lit.var()] = None;
+            self.reasons[lit.var()] = None;
+        }
+        self.limits.truncate(level);
+        self.head = self.trail.len();
+    }
+
+    fn pick(&self) -> Option<usize> {
+        (0..self.values.len())
+            .filter(|&var| self.values[var].is_none())
+            .max_by(|&a, &b| self.activity[a].total_cmp(&self.activity[b]).then(b.cmp(&a)))
+    }
+
+    // A model, one value per variable, or the assumptions that can not all hold. That list is
+    // empty when the clauses contradict each other without any assumption.
+    pub fn solve(&mut self, assumptions: &[Lit]) -> Result<Vec<bool>, Vec<Lit>> {
+        if self.unsat {
+            return Err(vec![]);
+        }
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.limits.is_empty() {
+                    self.unsat = true;
+                    return Err(vec![]);
+                }
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+                if learnt.len() == 1 {
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let first = learnt[0];
+                    let idx = self.attach(learnt);
+                    self.enqueue(first, Some(idx));
+                }
+                continue;
+            }
+            // Each assumption is decided on its own level, before anything else
+            if let Some(&assumption) = assumptions.get(self.limits.len()) {
+                match self.value(assumption) {
+                    Some(false) => {
+                        let core = self.analyze_final(assumption);
+                        self.backtrack(0);
+                        return Err(core);
+                    },
+                    Some(true) => self.limits.push(self.trail.len()),
+                    None       => {
+                        self.limits.push(self.trail.len());
+                        self.enqueue(assumption, None);
+                    },
+                }
+                continue;
+            }
+            match self.pick() {
+                Some(var) => {
+                    self.limits.push(self.trail.len());
+                    self.enqueue(Lit::new(var, self.phase[var]), None);
+                },
+                None => {
+                    let model = self.values.iter().map(|value| value.unwrap_or(false)).collect();
+                    self.backtrack(0);
+                    return Ok(model);
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Adds the clauses, given as signed 1-based variables like DIMACS, and keeps them to check
+    // models against
+    fn problem(vars: usize, clauses: &[&[i32]]) -> (Sat, Vec<Vec<Lit>>) {
+        let mut sat = Sat::new();
+        for _ in 0..vars {
+            sat.new_var();
+        }
+        let clauses: Vec<Vec<Lit>> = clauses.iter()
+            .map(|clause| clause.iter().map(|&v| lit(v)).collect())
+            .collect();
+        for clause in &clauses {
+            sat.add_clause(clause);
+        }
+        (sat, clauses)
+    }
+
+    fn lit(v: i32) -> Lit {
+        Lit::new(v.unsigned_abs() as usize - 1, v > 0)
+    }
+
+    fn satisfies(model: &[bool], clauses: &[Vec<Lit>]) -> bool {
+        clauses.iter().all(|clause| clause.iter().any(|lit| model[lit.var()] == lit.is_positive()))
+    }
+
+    // `pigeons` pigeons in `holes` holes, each pigeon in some hole and no two in the same one
+    fn pigeonhole(pigeons: usize, holes: usize) -> Vec<Vec<i32>> {
+        let var = |p: usize, h: usize| (p * holes + h + 1) as i32;
+        let mut clauses: Vec<Vec<i32>> = (0..pigeons).map(|p| (0..holes).map(|h| var(p, h)).collect()).collect();
+        for h in 0..holes {
+            for p in 0..pigeons {
+                for q in p + 1..pigeons {
+                    clauses.push(vec![-var(p, h), -var(q, h)]);
+                }
+            }
+        }
+        clauses
+    }
+
+    #[test]
+    fn satisfiable() {
+        let clauses = pigeonhole(5, 5);
+        let refs: Vec<&[i32]> = clauses.iter().map(Vec::as_slice).collect();
+        let (mut sat, clauses) = problem(25, &refs);
+        let model = sat.solve(&[]).unwrap();
+        assert!(satisfies(&model, &clauses));
+
+        // Under assumptions, and again without them afterwards
+        let model = sat.solve(&[lit(1), lit(7)]).unwrap();
+        assert!(satisfies(&model, &clauses) && model[0] && model[6]);
+        assert!(sat.solve(&[]).is_ok());
+    }
+
+    #[test]
+    fn unsatisfiable() {
+        // Only refuted by learning clauses over several levels
+        let clauses = pigeonhole(5, 4);
+        let refs: Vec<&[i32]> = clauses.iter().map(Vec::as_slice).collect();
+        let (mut sat, _) = problem(20, &refs);
+        assert_eq!(sat.solve(&[]), Err(vec![]));
+        assert_eq!(sat.solve(&[lit(1)]), Err(vec![]));
+
+        let (mut sat, _) = problem(1, &[&[1], &[-1]]);
+        assert_eq!(sat.solve(&[]), Err(vec![]));
+    }
+
+    #[test]
+    fn core() {
+        // `1` and `2` can not both hold, `3` and `4` have nothing to do with it
+        let (mut sat, clauses) = problem(5, &[&[-1, 5], &[-2, -5], &[3, 4]]);
+        let mut core = sat.solve(&[lit(3), lit(1), lit(4), lit(2)]).unwrap_err();
+        core.sort_by_key(|lit| lit.var());
+        assert_eq!(core, [lit(1), lit(2)]);
+
+        // A failed solve leaves the solver usable
+        let model = sat.solve(&[lit(1), lit(3), lit(-4)]).unwrap();
+        assert!(satisfies(&model, &clauses));
+
+        // An assumption the clauses alone rule out is a core on its own
+        let (mut sat, _) = problem(2, &[&[-1], &[2]]);
+        assert_eq!(sat.solve(&[lit(2), lit(1)]).unwrap_err(), [lit(1)]);
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ShellOutput {
+    pub stdout: String,
+    pub status: i32,
+}
+
+// Answers `$(shell,...)` for the preprocessor. Everything that probes the toolchain (`cc-option`,
+// `success`, `ld-version` and friends) is built on top of `$(shell,...)` in
+// `scripts/Kconfig.include`, so this is the only place the host is ever consulted.
+pub trait ShellHandler: fmt::Debug {
+    fn shell(&mut self, command: &str) -> Result<ShellOutput, String>;
+}
+
+// Runs commands with `sh -c`, which is what the kernel's own `conf` does
+#[derive(Debug, Default)]
+pub struct HostShell;
+
+impl ShellHandler for HostShell {
+    fn shell(&mut self, command: &str) -> Result<ShellOutput, String> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .output()
+            .map_err(|e| format!("failed to run shell command '{}': {}", command, e))?;
+        Ok(ShellOutput {
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            status: output.status.code().unwrap_or(-1),
+        })
+    }
+}
+
+// Used when no toolchain profile is given. Nothing is run and every command prints nothing, as if
+// the tool it asks about were not installed, which is what the kernel sees on a machine without
+// the toolchain. The first command is reported so the missing profile does not go unnoticed.
+#[derive(Debug, Default)]
+pub struct NoShell {
+    warned: bool,
+}
+
+impl ShellHandler for NoShell {
+    fn shell(&mut self, command: &str) -> Result<ShellOutput, String> {
+        if !self.warned {
+            eprintln!("warning: no toolchain profile, `{}` and every other $(shell,...) expand to nothing", command);
+            self.warned = true;
+        }
+        Ok(ShellOutput {
+            stdout: String::new(),
+            status: 127,
+        })
+    }
+}
+
+// Recorded answers for every command a given toolchain was asked. The file format reads like a
+// shell transcript and is stable, so recorded profiles can be kept in git and diffed:
+//
+//     # comments and blank lines are ignored
+//     $ command -v gcc
+//     > /usr/bin/gcc
+//     ? 0
+//
+// `>` lines are stdout, one per line of output, and `?` is the exit code which defaults to 0.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ToolchainProfile {
+    pub entries: BTreeMap<String, ShellOutput>,
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+    Io(io::Error),
+    Syntax {
+        line:    usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::Io(e)                     => write!(f, "{}", e),
+            ProfileError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+impl ToolchainProfile {
+    pub fn load(path: &Path) -> Result<Self, ProfileError> {
+        let content = fs::read_to_string(path).map_err(ProfileError::Io)?;
+        Self::parse(&content)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ProfileError> {
+        fs::write(path, self.to_string()).map_err(ProfileError::Io)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, ProfileError> {
+        let mut entries = BTreeMap::new();
+        let mut current: Option<(String, ShellOutput)> = None;
+
+        for (idx, line) in content.lines().enumerate() {
+            let syntax = |message: &str| ProfileError::Syntax {
+                line:    idx + 1,
+                message: message.to_string(),
+            };
+
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut chars = line.chars();
+            let tag = chars.next();
+            let rest = chars.as_str();
+            let rest = rest.strip_prefix(' ').unwrap_or(rest);
+            match (tag, current.as_mut()) {
+                (Some('$'), _) => {
+                    if let Some((command, output)) = current.take() {
+                        entries.insert(command, output);
+                    }
+                    current = Some((rest.to_string(), ShellOutput::default()));
+                },
+                (Some('>'), Some((_, output))) => {
+                    output.stdout.push_str(rest);
+                    output.stdout.push('\n');
+                },
+                (Some('?'), Some((_, output))) => {
+                    output.status = rest.trim().parse().map_err(|_| syntax("exit code is not a number"))?;
+                },
+                (Some('>' | '?'), None) => return Err(syntax("output given before any `$` command")),
+                _                       => return Err(syntax("expected a line starting with `$`, `>` or `?`")),
+            }
+        }
+        if let Some((command, output)) = current.take() {
+            entries.insert(command, output);
+        }
+
+        Ok(Self {
+            entries,
+        })
+    }
+}
+
+impl fmt::Display for ToolchainProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (command, output) in &self.entries {
+            writeln!(f, "$ {}", command)?;
+            for line in output.stdout.lines() {
+                writeln!(f, "> {}", line)?;
+            }
+            writeln!(f, "? {}", output.status)?;
+        }
+        Ok(())
+    }
+}
+
+impl ShellHandler for ToolchainProfile {
+    fn shell(&mut self, command: &str) -> Result<ShellOutput, String> {
+        self.entries
+            .get(command)
+            .cloned()
+            .ok_or_else(|| format!("`{}` is not recorded in the toolchain profile", command))
+    }
+}
+
+// Forwards every command to another handler and keeps the answers, used to produce a profile from
+// a machine which has the real toolchain installed
+#[derive(Debug, Default)]
+pub struct Recorder<S> {
+    pub inner:   S,
+    pub profile: ToolchainProfile,
+}
+
+impl<S: ShellHandler> Recorder<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            profile: ToolchainProfile::default(),
+        }
+    }
+}
+
+impl<S: ShellHandler> ShellHandler for Recorder<S> {
+    fn shell(&mut self, command: &str) -> Result<ShellOutput, String> {
+        let output = self.inner.shell(command)?;
+        self.profile.entries.insert(command.to_string(), output.clone());
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_round_trip() {
+        let text = "\
+# gcc 13
+$ command -v gcc
+
+> /usr/bin/gcc
+$ gcc --version
+> gcc (GCC) 13.2.0
+>
+> Copyright
+? 0
+$ false
+? 1
+";
+        let profile = ToolchainProfile::parse(text).unwrap();
+        assert_eq!(profile.entries.len(), 3);
+        assert_eq!(profile.entries["gcc --version"].stdout, "gcc (GCC) 13.2.0\n\nCopyright\n");
+        assert_eq!(profile.entries["false"], ShellOutput { stdout: String::new(), status: 1 });
+
+        let written = profile.to_string();
+        assert!(written.starts_with("$ command -v gcc\n> /usr/bin/gcc\n? 0\n"));
+        assert_eq!(ToolchainProfile::parse(&written).unwrap(), profile);
+    }
+
+    #[test]
+    fn profile_errors() {
+        let line = |text: &str| match ToolchainProfile::parse(text) {
+            Err(ProfileError::Syntax { line, .. }) => line,
+            other                                  => panic!("{:?}", other),
+        };
+        assert_eq!(line("> before\n"), 1);
+        assert_eq!(line("$ true\n? zero\n"), 2);
+        // Not a tag, and not a character boundary after the first byte either
+        assert_eq!(line("$ true\n\nébc\n"), 3);
+    }
+
+    #[test]
+    fn recorder() {
+        let mut inner = ToolchainProfile::default();
+        inner.entries.insert("uname".to_string(), ShellOutput { stdout: "Linux\n".to_string(), status: 0 });
+        let mut recorder = Recorder::new(inner);
+
+        assert_eq!(recorder.shell("uname").unwrap().stdout, "Linux\n");
+        assert!(recorder.shell("missing").is_err());
+        // Only what was answered ends up in the profile
+        assert_eq!(recorder.profile, recorder.inner);
+
+        let mut none = NoShell::default();
+        assert_eq!(none.shell("uname").unwrap().stdout, "");
+    }
+}
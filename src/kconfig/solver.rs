@@ -0,0 +1,785 @@
+use super::{
+    CompareOp,
+    Engine,
+    Env,
+    Expr,
+    MenuItem,
+    OptionType,
+    SymbolInfo,
+    Tristate,
+    Value,
+    VarType,
+    circuit::{
+        Bit,
+        Circuit,
+        Tri,
+    },
+    depends_expr,
+    sat::{
+        Lit,
+        Sat,
+    },
+};
+
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+// The outcome of asking what it takes to give a symbol a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Solution<'a> {
+    // The user values to set on top of the current ones, empty when the symbol already has the
+    // value. None of them can be left out.
+    Reachable(Vec<(&'a str, Tristate)>),
+
+    // Constraints of the Kconfig files that rule the value out, described in words. Dropping any
+    // one of them would make the value possible.
+    Impossible(Vec<String>),
+
+    // The constraints allow the value, but the engine did not agree with any of the assignments
+    // found. The encoding leaves out `imply` and treats choices more loosely than the engine.
+    Unverified,
+}
+
+// How many assignments the engine may reject before giving up
+const ATTEMPTS: usize = 20;
+
+// Find the fewest user values to change so that `name` gets `wanted`, the way a user would
+// answer more questions in menuconfig. Every bool and tristate symbol that can influence `name`
+// becomes a pair of SAT variables, `>= m` and `>= y`, constrained the way the engine calculates
+// its value: the user value within the prompt's visibility, else the first active default, raised
+// by `select`. Everything else keeps its current value.
+//
+// Each answer is checked against the engine before it is returned, as the encoding is not exact,
+// and another one is searched for when the engine disagrees.
+pub fn solve<'a>(engine: &mut Engine<'_, '_, 'a>, name: &str, wanted: Tristate) -> Solution<'a> {
+    match engine.symbols.get(name).and_then(|info| info.option_type) {
+        Some(OptionType::Bool | OptionType::Tristate) => {},
+        Some(option_type) => return Solution::Impossible(vec![format!("`{}` is {}, not bool or tristate", name, option_type)]),
+        None              => return Solution::Impossible(vec![format!("`{}` is not defined", name)]),
+    }
+    let mut problem = Problem::build(engine, name, wanted);
+    let base: Vec<Lit> = problem.groups.iter().map(|(lit, _)| *lit).collect();
+
+    let mut model = match problem.sat.solve(&base) {
+        Ok(model) => model,
+        Err(core) => return Solution::Impossible(problem.explain(core)),
+    };
+    for _ in 0..ATTEMPTS {
+        model = problem.minimize(&base, model);
+        let changes = problem.changes(&model);
+        if verify(engine, &changes, name, wanted) {
+            return Solution::Reachable(changes);
+        }
+        problem.block(&model);
+        model = match problem.sat.solve(&base) {
+            Ok(model) => model,
+            Err(_)    => return Solution::Unverified,
+        };
+    }
+    Solution::Unverified
+}
+
+// Try the changes on the engine and put its user values back afterwards
+fn verify(engine: &mut Engine, changes: &[(&str, Tristate)], name: &str, wanted: Tristate) -> bool {
+    let previous: Vec<Option<Value>> = changes.iter().map(|(name, _)| engine.user_value(name).cloned()).collect();
+    for (name, value) in changes {
+        engine.set(name, Value::Tristate(*value));
+    }
+    let reached = engine.value(name).is_some_and(|value| value.tristate() == wanted);
+    for ((name, _), previous) in changes.iter().zip(previous) {
+        match previous {
+            Some(value) => engine.set(name, value),
+            None        => {
+                engine.unset(name);
+            },
+        }
+    }
+    reached
+}
+
+// A user value the solver may change, and the variable that is true while it stays as it is
+#[derive(Debug)]
+struct Keep<'a> {
+    lit:    Lit,
+    change: Change<'a>,
+}
+
+#[derive(Debug)]
+enum Change<'a> {
+    Symbol {
+        name: &'a str,
+        user: Tri,
+    },
+
+    // The values of a choice, each with whether it is selected now and the variable saying it is
+    // selected in the model
+    Choice(Vec<(&'a str, bool, Bit)>),
+}
+
+struct Problem<'a> {
+    sat:    Sat,
+
+    // The constraints of each symbol and choice, and the target, each switched on by a literal
+    groups: Vec<(Lit, String)>,
+    keeps:  Vec<Keep<'a>>,
+}
+
+struct Encoder<'e, 't, 'k, 'a> {
+    engine:  &'e Engine<'t, 'k, 'a>,
+    circuit: Circuit,
+    values:  HashMap<&'a str, Tri>,
+    modules: Tri,
+    groups:  Vec<(Lit, String)>,
+    keeps:   Vec<Keep<'a>>,
+}
+
+impl<'a> Problem<'a> {
+    fn build(engine: &Engine<'_, '_, 'a>, name: &str, wanted: Tristate) -> Self {
+        let cone = cone(engine, name);
+        let mut encoder = Encoder {
+            engine,
+            circuit: Circuit::new(),
+            values:  HashMap::new(),
+            modules: Tri::constant(engine.modules()),
+            groups:  vec![],
+            keeps:   vec![],
+        };
+        for &symbol in &cone {
+            let tristate = engine.symbols.get(symbol).and_then(|info| info.option_type) == Some(OptionType::Tristate);
+            let value = encoder.circuit.tri(tristate);
+            encoder.values.insert(symbol, value);
+        }
+        if let Some(modules) = encoder.values.get("MODULES") {
+            encoder.modules = *modules;
+        }
+
+        let mut choices = HashSet::new();
+        for &symbol in &cone {
+            let Some(info) = engine.symbols.get(symbol) else {
+                continue;
+            };
+            match engine.choice_of(info) {
+                Some(choice) if choices.insert(choice) => encoder.choice(choice),
+                Some(_)                                => {},
+                None                                   => encoder.symbol(info),
+            }
+        }
+
+        let value = encoder.values[name];
+        encoder.begin(format!("`{}={}` is requested", name, wanted.name()));
+        match wanted {
+            Tristate::Yes    => encoder.circuit.require(value.y),
+            Tristate::Module => {
+                encoder.circuit.require(value.m);
+                encoder.circuit.require(value.y.not());
+            },
+            Tristate::No     => encoder.circuit.require(value.m.not()),
+        }
+        encoder.circuit.guard = None;
+
+        Problem {
+            sat:    encoder.circuit.sat,
+            groups: encoder.groups,
+            keeps:  encoder.keeps,
+        }
+    }
+
+    // The model changing the fewest user values. Keeping them one at a time quickly gives a small
+    // set of changes, after that a model with fewer changes is asked for until there is none.
+    fn minimize(&mut self, base: &[Lit], model: Vec<bool>) -> Vec<bool> {
+        let mut model = self.keep(base, model);
+        loop {
+            let changed = self.keeps.iter().filter(|keep| !model[keep.lit.var()]).count();
+            if changed == 0 {
+                return model;
+            }
+            let assumptions: Vec<Lit> = base.iter().copied().chain([self.at_most(changed - 1)]).collect();
+            match self.sat.solve(&assumptions) {
+                Ok(better) => model = self.keep(&assumptions, better),
+                Err(_)     => return model,
+            }
+        }
+    }
+
+    // Keep as many user values as they are as possible, trying one at a time
+    fn keep(&mut self, base: &[Lit], mut model: Vec<bool>) -> Vec<bool> {
+        let mut kept: Vec<Lit> = vec![];
+        let mut tried = HashSet::new();
+        while let Some(keep) = self.keeps.iter().find(|keep| !model[keep.lit.var()] && !tried.contains(&keep.lit)) {
+            tried.insert(keep.lit);
+            let assumptions: Vec<Lit> = base.iter().chain(&kept).copied().chain([keep.lit]).collect();
+            if let Ok(better) = self.sat.solve(&assumptions) {
+                kept.push(keep.lit);
+                model = better;
+            }
+        }
+        model
+    }
+
+    // A literal that, while assumed, allows at most `limit` user values to change. This is a
+    // sequential counter: `counts[j]` holds once `j + 1` of the values seen so far have changed.
+    fn at_most(&mut self, limit: usize) -> Lit {
+        let guard = Lit::new(self.sat.new_var(), true);
+        let mut counts: Vec<Lit> = vec![];
+        for changed in self.keeps.iter().map(|keep| !keep.lit) {
+            match limit.checked_sub(1).map(|last| counts.get(last)) {
+                None              => self.sat.add_clause(&[!guard, !changed]),
+                Some(Some(&last)) => self.sat.add_clause(&[!guard, !changed, !last]),
+                Some(None)        => {},
+            }
+            let next: Vec<Lit> = (0..limit).map(|_| Lit::new(self.sat.new_var(), true)).collect();
+            for (j, &count) in next.iter().enumerate() {
+                if let Some(&before) = counts.get(j) {
+                    self.sat.add_clause(&[!before, count]);
+                }
+                match j.checked_sub(1).map(|k| counts.get(k)) {
+                    None              => self.sat.add_clause(&[!changed, count]),
+                    Some(Some(&less)) => self.sat.add_clause(&[!changed, !less, count]),
+                    Some(None)        => {},
+                }
+            }
+            counts = next;
+        }
+        guard
+    }
+
+    fn changes(&self, model: &[bool]) -> Vec<(&'a str, Tristate)> {
+        let mut changes = vec![];
+        for keep in self.keeps.iter().filter(|keep| !model[keep.lit.var()]) {
+            match &keep.change {
+                Change::Symbol { name, user } => changes.push((*name, user.value(model))),
+                Change::Choice(values) => {
+                    for (name, selected, bit) in values {
+                        match (selected, bit.holds(model)) {
+                            (false, true) => changes.push((*name, Tristate::Yes)),
+                            (true, false) => changes.push((*name, Tristate::No)),
+                            _             => {},
+                        }
+                    }
+                },
+            }
+        }
+        changes
+    }
+
+    // Rule out the changes in this model, the engine did not accept them
+    fn block(&mut self, model: &[bool]) {
+        let mut clause = vec![];
+        for keep in &self.keeps {
+            if model[keep.lit.var()] {
+                clause.push(Bit::Lit(!keep.lit));
+                continue;
+            }
+            clause.push(Bit::Lit(keep.lit));
+            let bits = match &keep.change {
+                Change::Symbol { user, .. } => vec![user.m, user.y],
+                Change::Choice(values)      => values.iter().map(|(_, _, bit)| *bit).collect(),
+            };
+            for bit in bits {
+                clause.push(if bit.holds(model) { bit.not() } else { bit });
+            }
+        }
+        let lits: Vec<Lit> = clause.into_iter().filter_map(|bit| match bit {
+            Bit::Lit(lit) => Some(lit),
+            Bit::Const(_) => None,
+        }).collect();
+        self.sat.add_clause(&lits);
+    }
+
+    // Drop groups from the core one at a time while the rest still conflicts
+    fn explain(&mut self, core: Vec<Lit>) -> Vec<String> {
+        let mut core = core;
+        let mut i = 0;
+        while i < core.len() {
+            let without: Vec<Lit> = core.iter().enumerate().filter(|(k, _)| *k != i).map(|(_, lit)| *lit).collect();
+            match self.sat.solve(&without) {
+                Err(smaller) => core = without.into_iter().filter(|lit| smaller.contains(lit)).collect(),
+                Ok(_)        => i += 1,
+            }
+        }
+        self.groups.iter()
+            .filter(|(lit, _)| core.contains(lit))
+            .map(|(_, text)| text.clone())
+            .collect()
+    }
+}
+
+impl<'a> Encoder<'_, '_, '_, 'a> {
+    fn begin(&mut self, description: String) {
+        let lit = Lit::new(self.circuit.sat.new_var(), true);
+        self.groups.push((lit, description));
+        self.circuit.guard = Some(lit);
+    }
+
+    // `m` becomes `y` for bools, and for tristates while modules are disabled
+    fn fix(&mut self, value: Tri, tristate: bool) -> Tri {
+        if !tristate {
+            return Tri { m: value.m, y: value.m };
+        }
+        let no_modules = self.modules.m.not();
+        let module = self.circuit.and(value.m, no_modules);
+        Tri { m: value.m, y: self.circuit.or(value.y, module) }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Tri {
+        self.calc(expr, true)
+    }
+
+    // A `default` value, in which `m` is not rewritten
+    fn value(&mut self, expr: &Expr) -> Tri {
+        self.calc(expr, false)
+    }
+
+    fn calc(&mut self, expr: &Expr, rewrite: bool) -> Tri {
+        match expr {
+            Expr::Var(var)   => self.factor(var, expr, rewrite),
+            Expr::Not(inner) => {
+                let inner = self.calc(inner, rewrite);
+                Tri::not(inner)
+            },
+            Expr::And(l, r)  => {
+                let (l, r) = (self.calc(l, rewrite), self.calc(r, rewrite));
+                self.circuit.tri_and(l, r)
+            },
+            Expr::Or(l, r)   => {
+                let (l, r) = (self.calc(l, rewrite), self.calc(r, rewrite));
+                self.circuit.tri_or(l, r)
+            },
+            Expr::Compare(op @ (CompareOp::Equal | CompareOp::NotEqual), l, r) => {
+                let (Some(l), Some(r)) = (self.operand(l), self.operand(r)) else {
+                    return Tri::constant(expr.eval(self.engine));
+                };
+                let m = self.circuit.iff(l.m, r.m);
+                let y = self.circuit.iff(l.y, r.y);
+                let equal = self.circuit.and(m, y);
+                let bit = if *op == CompareOp::Equal { equal } else { equal.not() };
+                Tri { m: bit, y: bit }
+            },
+            // Ordering and anything involving strings or numbers is taken as it is now
+            Expr::Compare(..) => Tri::constant(expr.eval(self.engine)),
+        }
+    }
+
+    fn expr_opt(&mut self, expr: Option<&Expr>) -> Tri {
+        match expr {
+            Some(expr) => self.expr(expr),
+            None       => Tri::constant(Tristate::Yes),
+        }
+    }
+
+    fn factor(&mut self, var: &VarType, expr: &Expr, rewrite: bool) -> Tri {
+        match var {
+            // `m && MODULES`
+            VarType::Tristate(Tristate::Module) if rewrite => Tri { m: self.modules.m, y: Bit::Const(false) },
+            VarType::Symbol(s) if self.values.contains_key(s.name) => self.values[s.name],
+            _ if rewrite => Tri::constant(expr.eval(self.engine)),
+            _            => Tri::constant(expr.eval_value(self.engine)),
+        }
+    }
+
+    // One side of `=` or `!=` that is a tristate, without the rewriting of `m`
+    fn operand(&self, var: &VarType) -> Option<Tri> {
+        match var {
+            VarType::Tristate(t) => Some(Tri::constant(*t)),
+            VarType::Symbol(s)   => match self.values.get(s.name) {
+                Some(value) => Some(*value),
+                None => match self.engine.option_type(s.name) {
+                    Some(OptionType::Bool | OptionType::Tristate) => self.engine.value(s.name).map(|v| Tri::constant(v.tristate())),
+                    _                                             => None,
+                },
+            },
+            _ => None,
+        }
+    }
+
+    // `visible if` of the menus around a node
+    fn menu_visible(&mut self, idx: usize) -> Tri {
+        let tree = self.engine.tree;
+        let mut visible = Tri::constant(Tristate::Yes);
+        let mut current = tree.nodes[idx].parent;
+        while let Some(parent) = current {
+            if let MenuItem::Menu(menu) = tree.nodes[parent].item {
+                let own = self.expr_opt(depends_expr(&menu.visible).as_ref());
+                visible = self.circuit.tri_and(visible, own);
+            }
+            current = tree.nodes[parent].parent;
+        }
+        visible
+    }
+
+    // The highest value a prompt of the symbol allows
+    fn visibility(&mut self, info: &SymbolInfo) -> Tri {
+        let mut visible = Tri::constant(Tristate::No);
+        for prompt in &info.prompts {
+            let def = &info.definitions[prompt.definition];
+            let dep = self.expr_opt(def.dep.as_ref());
+            let condition = self.expr_opt(prompt.value.condition.as_ref());
+            let menu = self.menu_visible(def.node);
+            let this = self.circuit.tri_and(dep, condition);
+            let this = self.circuit.tri_and(this, menu);
+            visible = self.circuit.tri_or(visible, this);
+        }
+        let tristate = info.option_type == Some(OptionType::Tristate);
+        self.fix(visible, tristate)
+    }
+
+    // The first default whose condition holds, `n` when there is none
+    fn defaults(&mut self, info: &SymbolInfo) -> Tri {
+        let mut value = Tri::constant(Tristate::No);
+        for default in info.defaults.iter().rev() {
+            let def = &info.definitions[default.definition];
+            let dep = self.expr_opt(def.dep.as_ref());
+            let condition = self.expr_opt(default.value.condition.as_ref());
+            let condition = self.circuit.tri_and(dep, condition);
+            let this = self.value(&default.value.expression);
+            let this = self.circuit.tri_and(this, condition);
+            value = Tri {
+                m: self.circuit.ite(condition.m, this.m, value.m),
+                y: self.circuit.ite(condition.m, this.y, value.y),
+            };
+        }
+        value
+    }
+
+    // The strongest `select` whose condition holds
+    fn selected(&mut self, name: &str) -> Tri {
+        let engine = self.engine;
+        let mut value = Tri::constant(Tristate::No);
+        for rev in engine.selected_by(name) {
+            let from = self.values.get(rev.symbol).copied()
+                .unwrap_or_else(|| Tri::constant(engine.value(rev.symbol).map_or(Tristate::No, |v| v.tristate())));
+            let dep = self.expr_opt(rev.dep);
+            let condition = self.expr_opt(rev.condition);
+            let this = self.circuit.tri_and(from, dep);
+            let this = self.circuit.tri_and(this, condition);
+            value = self.circuit.tri_or(value, this);
+        }
+        value
+    }
+
+    // The value a user can give the symbol. While it is kept this is the current user value, or
+    // the default without one.
+    fn user(&mut self, info: &SymbolInfo<'_, 'a>, default: Tri, keep: Option<Lit>) -> Tri {
+        let tristate = info.option_type == Some(OptionType::Tristate);
+        let user = self.circuit.tri(tristate);
+        let current = match self.engine.user_value(info.name) {
+            Some(value) => Tri::constant(value.tristate()),
+            None        => default,
+        };
+        let keep = keep.map_or(Bit::Const(true), Bit::Lit);
+        let same_m = self.circuit.iff(user.m, current.m);
+        let same_y = self.circuit.iff(user.y, current.y);
+        self.circuit.implies(keep, same_m);
+        self.circuit.implies(keep, same_y);
+        // Leaning towards the current value keeps the first answers small
+        let now = self.engine.value(info.name).map_or(Tristate::No, |v| v.tristate());
+        for (bit, on) in [(user.m, now >= Tristate::Module), (user.y, now == Tristate::Yes)] {
+            if let Bit::Lit(lit) = bit {
+                self.circuit.sat.set_phase(lit.var(), on == lit.is_positive());
+            }
+        }
+        user
+    }
+
+    // The value as the engine calculates it before choices are considered, with the visibility and
+    // the user value it was made from
+    fn calculated(&mut self, info: &SymbolInfo<'_, 'a>, keep: Option<Lit>) -> (Tri, Tri, Tri) {
+        let tristate = info.option_type == Some(OptionType::Tristate);
+        let visible = self.visibility(info);
+        let default = self.defaults(info);
+        let user = self.user(info, default, keep);
+        let selected = self.selected(info.name);
+
+        let set = self.circuit.tri_and(user, visible);
+        let hidden = visible.m.not();
+        let fallback = Tri {
+            m: self.circuit.and(hidden, default.m),
+            y: self.circuit.and(hidden, default.y),
+        };
+        let value = self.circuit.tri_or(set, fallback);
+        let value = self.circuit.tri_or(value, selected);
+        (self.fix(value, tristate), visible, user)
+    }
+
+    fn describe(&self, info: &SymbolInfo) -> String {
+        let mut parts = vec![];
+        if info.prompts.is_empty() {
+            parts.push("has no prompt".to_string());
+        }
+        for prompt in &info.prompts {
+            let def = &info.definitions[prompt.definition];
+            match Expr::and(def.dep.clone(), prompt.value.condition.clone()) {
+                Some(dep) => parts.push(format!("can be set when {}", dep)),
+                None      => parts.push("can always be set".to_string()),
+            }
+        }
+        for default in &info.defaults {
+            match &default.value.condition {
+                Some(condition) => parts.push(format!("default {} if {}", default.value.expression, condition)),
+                None            => parts.push(format!("default {}", default.value.expression)),
+            }
+        }
+        for rev in self.engine.selected_by(info.name) {
+            match Expr::and(rev.dep.cloned(), rev.condition.cloned()) {
+                Some(condition) => parts.push(format!("selected by {} if {}", rev.symbol, condition)),
+                None            => parts.push(format!("selected by {}", rev.symbol)),
+            }
+        }
+        format!("`{}`: {}", info.name, parts.join("; "))
+    }
+
+    fn symbol(&mut self, info: &SymbolInfo<'_, 'a>) {
+        self.begin(self.describe(info));
+        let keep = (!info.prompts.is_empty()).then(|| {
+            let lit = Lit::new(self.circuit.sat.new_var(), true);
+            self.circuit.sat.set_phase(lit.var(), true);
+            lit
+        });
+        let (value, _, user) = self.calculated(info, keep);
+        let current = self.values[info.name];
+        self.circuit.equal(current.m, value.m);
+        self.circuit.equal(current.y, value.y);
+        self.circuit.guard = None;
+        if let Some(lit) = keep {
+            self.keeps.push(Keep { lit, change: Change::Symbol { name: info.name, user } });
+        }
+    }
+
+    // A choice in `y` mode has exactly one of its values selected, at most one when it is
+    // optional. Which one stays as it is unless the choice is changed as a whole.
+    fn choice(&mut self, choice: usize) {
+        let engine = self.engine;
+        let tree = engine.tree;
+        let MenuItem::Choice(kchoice) = tree.nodes[choice].item else {
+            return;
+        };
+        let names: Vec<&'a str> = tree.nodes[choice].children.iter()
+            .filter_map(|&child| match tree.nodes[child].item {
+                MenuItem::Option(opt) => engine.symbols.get(opt.name.name).map(|info| info.name),
+                _                     => None,
+            })
+            .collect();
+        let keep = Lit::new(self.circuit.sat.new_var(), true);
+        self.circuit.sat.set_phase(keep.var(), true);
+        let selected = engine.selection(choice).selected;
+
+        let mut values = vec![];
+        for &name in &names {
+            let Some(info) = engine.symbols.get(name) else {
+                continue;
+            };
+            self.begin(self.describe(info));
+            let (calculated, visible, _) = self.calculated(info, None);
+            let bit = self.circuit.var();
+            self.circuit.implies(bit, visible.y);
+            let current = self.values[name];
+            let mode = self.mode(choice);
+            let on = self.circuit.and(visible.y, mode);
+            let m = self.circuit.ite(on, bit, calculated.m);
+            let y = self.circuit.ite(on, bit, calculated.y);
+            self.circuit.equal(current.m, m);
+            self.circuit.equal(current.y, y);
+            self.circuit.implies(Bit::Lit(keep), if selected == Some(name) { bit } else { bit.not() });
+            self.circuit.guard = None;
+            values.push((name, selected == Some(name), bit));
+        }
+
+        let prompt = tree.nodes[choice].item.prompt().unwrap_or("choice");
+        let list = names.join(", ");
+        match kchoice.optional {
+            true  => self.begin(format!("choice \"{}\" selects at most one of {}", prompt, list)),
+            false => self.begin(format!("choice \"{}\" selects one of {}", prompt, list)),
+        }
+        for (i, (_, _, a)) in values.iter().enumerate() {
+            for (_, _, b) in &values[i + 1..] {
+                self.circuit.clause(&[a.not(), b.not()]);
+            }
+        }
+        if !kchoice.optional {
+            let mode = self.mode(choice);
+            let mut any = mode.not();
+            for (_, _, bit) in &values {
+                any = self.circuit.or(any, *bit);
+            }
+            self.circuit.require(any);
+        }
+        self.circuit.guard = None;
+        self.keeps.push(Keep { lit: keep, change: Change::Choice(values) });
+    }
+
+    // Whether the choice is visible, which puts it in `y` mode
+    fn mode(&mut self, choice: usize) -> Bit {
+        let visibility = self.engine.tree.visibility(choice);
+        let own = self.expr_opt(visibility.as_ref());
+        let menu = self.menu_visible(choice);
+        self.circuit.and(own.m, menu.m)
+    }
+}
+
+// The bool and tristate symbols the value of `name` can depend on: those in its dependencies,
+// defaults and prompts, the symbols selecting it and the other values of its choice, and then
+// theirs
+fn cone<'a>(engine: &Engine<'_, '_, 'a>, name: &str) -> Vec<&'a str> {
+    let tree = engine.tree;
+    let mut seen = HashSet::new();
+    let mut order = vec![];
+    let mut queue = vec![name.to_string()];
+    if engine.symbols.get("MODULES").is_some() {
+        queue.push("MODULES".to_string());
+    }
+    while let Some(name) = queue.pop() {
+        let Some(info) = engine.symbols.get(&name) else {
+            continue;
+        };
+        if !matches!(info.option_type, Some(OptionType::Bool | OptionType::Tristate)) || !seen.insert(info.name) {
+            continue;
+        }
+        order.push(info.name);
+        let mut exprs: Vec<Expr> = vec![];
+        let mut nodes = vec![];
+        for prompt in &info.prompts {
+            let def = &info.definitions[prompt.definition];
+            exprs.extend(def.dep.clone());
+            exprs.extend(prompt.value.condition.clone());
+            nodes.push(def.node);
+        }
+        for default in &info.defaults {
+            exprs.extend(info.definitions[default.definition].dep.clone());
+            exprs.push(default.value.expression.clone());
+            exprs.extend(default.value.condition.clone());
+        }
+        for rev in engine.selected_by(info.name) {
+            queue.push(rev.symbol.to_string());
+            exprs.extend(rev.dep.cloned());
+            exprs.extend(rev.condition.cloned());
+        }
+        if let Some(choice) = engine.choice_of(info) {
+            exprs.extend(tree.visibility(choice));
+            nodes.push(choice);
+            for &child in &tree.nodes[choice].children {
+                if let MenuItem::Option(opt) = tree.nodes[child].item {
+                    queue.push(opt.name.name.to_string());
+                }
+            }
+        }
+        for node in nodes {
+            let mut current = tree.nodes[node].parent;
+            while let Some(parent) = current {
+                if let MenuItem::Menu(menu) = tree.nodes[parent].item {
+                    exprs.extend(depends_expr(&menu.visible));
+                }
+                current = tree.nodes[parent].parent;
+            }
+        }
+        for expr in &exprs {
+            queue.extend(expr.symbols().into_iter().map(|symbol| symbol.name.to_string()));
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::engine::tests::with_engine;
+
+    const KCONFIG: &str = "
+config MODULES
+	bool \"modules\"
+	default y
+
+config A
+	bool \"a\"
+
+config B
+	bool \"b\"
+
+config C
+	bool \"c\"
+
+config TARGET
+	tristate \"target\"
+	depends on A && (B || C)
+
+config HIDDEN
+	bool
+
+config NEVER
+	bool \"never\"
+	depends on HIDDEN
+
+config SEL
+	bool \"sel\"
+	select TARGET
+";
+
+    const VALUES: [Tristate; 3] = [Tristate::No, Tristate::Module, Tristate::Yes];
+    const SYMBOLS: [&str; 7] = ["MODULES", "A", "B", "C", "TARGET", "NEVER", "SEL"];
+
+    // Whether any `size` user values reach the value, trying every symbol and value
+    fn reachable_with(engine: &mut Engine, size: usize, from: usize, changes: &mut Vec<(&str, Tristate)>, name: &str, wanted: Tristate) -> bool {
+        if changes.len() == size {
+            return verify(engine, changes, name, wanted);
+        }
+        for (i, symbol) in SYMBOLS.iter().enumerate().skip(from) {
+            for value in VALUES {
+                changes.push((symbol, value));
+                let found = reachable_with(engine, size, i + 1, changes, name, wanted);
+                changes.pop();
+                if found {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn fewest(engine: &mut Engine, name: &str, wanted: Tristate) -> Vec<(String, Tristate)> {
+        let Solution::Reachable(changes) = solve(engine, name, wanted) else {
+            panic!("`{}={}` not reached", name, wanted.name());
+        };
+        let changes: Vec<(String, Tristate)> = changes.into_iter().map(|(name, value)| (name.to_string(), value)).collect();
+        let borrowed: Vec<(&str, Tristate)> = changes.iter().map(|(name, value)| (name.as_str(), *value)).collect();
+        assert!(verify(engine, &borrowed, name, wanted));
+        for size in 0..changes.len() {
+            assert!(!reachable_with(engine, size, 0, &mut vec![], name, wanted), "{} changes reach `{}`", size, name);
+        }
+        changes
+    }
+
+    #[test]
+    fn fewest_changes() {
+        with_engine(KCONFIG, |engine| {
+            // Selecting it takes one change, meeting its dependencies three
+            assert_eq!(fewest(engine, "TARGET", Tristate::Yes), [("SEL".to_string(), Tristate::Yes)]);
+            assert_eq!(fewest(engine, "TARGET", Tristate::Module).len(), 3);
+
+            // User values already there count
+            engine.set("A", Value::Tristate(Tristate::Yes));
+            engine.set("B", Value::Tristate(Tristate::Yes));
+            assert_eq!(fewest(engine, "TARGET", Tristate::Module), [("TARGET".to_string(), Tristate::Module)]);
+            assert_eq!(solve(engine, "TARGET", Tristate::No), Solution::Reachable(vec![]));
+
+            // Turning it off means dropping the `select`, there is no user value of its own
+            engine.set("SEL", Value::Tristate(Tristate::Yes));
+            assert_eq!(fewest(engine, "TARGET", Tristate::No), [("SEL".to_string(), Tristate::No)]);
+        });
+    }
+
+    #[test]
+    fn impossible() {
+        with_engine(KCONFIG, |engine| {
+            // Only the constraints in the way, none of those of the other symbols
+            assert_eq!(solve(engine, "NEVER", Tristate::Yes), Solution::Impossible(vec![
+                "`NEVER`: can be set when HIDDEN".to_string(),
+                "`HIDDEN`: has no prompt".to_string(),
+                "`NEVER=y` is requested".to_string(),
+            ]));
+            assert_eq!(solve(engine, "MISSING", Tristate::Yes), Solution::Impossible(vec![
+                "`MISSING` is not defined".to_string(),
+            ]));
+        });
+    }
+}
@@ -0,0 +1,560 @@
+use super::{
+    Block,
+    Entry,
+    KConfig,
+    KSource,
+    diagnostic::{
+        Diagnostic,
+        FileText,
+        Severity,
+    },
+    span::{
+        self,
+        Span,
+    },
+    util::ParseError,
+    preprocess::{
+        LineState,
+        Location,
+        MacroError,
+        Preprocessor,
+    },
+    shell::ShellHandler,
+};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{
+    Component,
+    Path,
+    PathBuf,
+};
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct FileId(pub usize);
+
+#[derive(Debug)]
+pub struct SourceFile {
+    // Path as written in diagnostics, relative to srctree whenever the file lives inside it
+    pub path:    PathBuf,
+
+    // The file after macro expansion, with the same line numbering as the file on disk
+    pub content: String,
+}
+
+// Only problems which make it impossible to carry on end the load, anything wrong with an
+// individual file is reported as a `Diagnostic` instead
+#[derive(Debug)]
+pub enum LoadError {
+    Io {
+        path:  PathBuf,
+        error: io::Error,
+    },
+    Macro(MacroError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            LoadError::Macro(e)           => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+// Owns the text of every Kconfig file reachable from the root file. Loading happens up front so
+// that the parsed tree can borrow from all of the files at once.
+#[derive(Debug)]
+pub struct SourceMap {
+    pub srctree: PathBuf,
+    files:       Vec<SourceFile>,
+
+    // Keyed by the including file and the byte offset of the `source` path within it
+    includes:    HashMap<(FileId, usize), Vec<FileId>>,
+
+    // Sourced files which could not be found or read
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl SourceMap {
+    pub fn load(srctree: &Path, root: &Path) -> Result<Self, LoadError> {
+        Self::load_with(srctree, root, &mut Preprocessor::default())
+    }
+
+    // Files are preprocessed in the order the kernel's lexer would read them, so a variable is
+    // visible to everything sourced after its assignment
+    pub fn load_with<S: ShellHandler>(srctree: &Path, root: &Path, macros: &mut Preprocessor<S>) -> Result<Self, LoadError> {
+        let mut map = Self {
+            srctree:  srctree.to_path_buf(),
+            files:       vec![],
+            includes:    HashMap::new(),
+            diagnostics: vec![],
+        };
+        let mut stack = vec![];
+        // Normalized like every sourced path, so the root is recognized when a file sources it
+        map.load_file(macros, &normalize(&srctree.join(root)), &mut stack)?;
+        Ok(map)
+    }
+
+    pub fn root(&self) -> FileId {
+        FileId(0)
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files.iter().enumerate().map(|(idx, file)| (FileId(idx), file))
+    }
+
+    pub fn load_diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    // Parse the whole tree, splicing each sourced file into the `KSource` entry that included it.
+    // Broken entries are skipped and reported, the rest of the tree is still returned.
+    pub fn parse(&self) -> (KConfig<'_>, Vec<Diagnostic>) {
+        let mut diagnostics = vec![];
+        let config = self.parse_file(self.root(), &mut diagnostics);
+        (config, diagnostics)
+    }
+
+    fn parse_file(&self, id: FileId, diagnostics: &mut Vec<Diagnostic>) -> KConfig<'_> {
+        let mut config = self.parse_single(id, diagnostics);
+        visit_sources(&mut config, &mut |source| {
+            let offset = self.offset_of(id, source.path_str());
+            for child in self.includes.get(&(id, offset)).into_iter().flatten() {
+                source.configs.push(self.parse_file(*child, diagnostics));
+            }
+        });
+        config
+    }
+
+    // `drivers/net/Kconfig:1234`, the form editors and compilers use
+    pub fn location(&self, span: &Span) -> String {
+        if span.is_detached() {
+            return "<unknown>".to_string();
+        }
+        format!("{}:{}", self.file(span.file).path.display(), span.line())
+    }
+
+    // A diagnostic pointing at the start of a node
+    pub fn diagnostic(&self, severity: Severity, span: &Span, message: String) -> Diagnostic {
+        if span.is_detached() {
+            return Diagnostic {
+                severity,
+                file:   PathBuf::from("<unknown>"),
+                line:   0,
+                column: 0,
+                message,
+                notes:  vec![],
+            };
+        }
+        let file = self.file(span.file);
+        let before = &file.content[..span.bytes.start];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        Diagnostic {
+            severity,
+            file:   file.path.clone(),
+            line:   span.line(),
+            column: before[line_start..].chars().count() + 1,
+            message,
+            notes:  vec![],
+        }
+    }
+
+    pub fn text(&self, id: FileId) -> FileText<'_> {
+        let file = self.file(id);
+        FileText {
+            path:    &file.path,
+            content: &file.content,
+        }
+    }
+
+    fn parse_single(&self, id: FileId, diagnostics: &mut Vec<Diagnostic>) -> KConfig<'_> {
+        let text = self.text(id);
+        let _span = span::enter_file(id, text.content);
+        let mut config = KConfig::default();
+        let mut input = text.content;
+        loop {
+            let (remaining, part) = KConfig::parse(input).unwrap_or((input, KConfig::default()));
+            config.append(part);
+            if remaining.trim().is_empty() {
+                break;
+            }
+
+            // Only closing keywords without a matching opener, or blocks that are never closed,
+            // make it out here. Everything else was already skipped inside the entry.
+            let (rest, error) = match ParseError::parse(None)(remaining) {
+                Ok(skipped) => skipped,
+                Err(_) => {
+                    let line = remaining.find('\n').map(|idx| idx + 1).unwrap_or(remaining.len());
+                    (&remaining[line..], ParseError { skipped: &remaining[..line], inside: None })
+                },
+            };
+            config.errors.get_or_insert_with(Vec::new).push(error);
+            input = rest;
+        }
+
+        config.span = Span::between(text.content, "");
+
+        let mut found = vec![];
+        visit_errors(&config, &mut |error| found.push(text.describe(error)));
+        found.sort_by_key(|d| (d.line, d.column));
+        diagnostics.extend(found);
+        config
+    }
+
+    fn offset_of(&self, id: FileId, slice: &str) -> usize {
+        slice.as_ptr() as usize - self.file(id).content.as_ptr() as usize
+    }
+
+    fn display_path(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.srctree).unwrap_or(path).to_path_buf()
+    }
+
+    fn load_file<S: ShellHandler>(&mut self, macros: &mut Preprocessor<S>, path: &Path, stack: &mut Vec<PathBuf>) -> Result<FileId, LoadError> {
+        let raw = fs::read_to_string(path).map_err(|error| LoadError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let id = FileId(self.files.len());
+        let display = self.display_path(path);
+        let filename = display.to_string_lossy().to_string();
+        self.files.push(SourceFile {
+            path:    display,
+            content: String::new(),
+        });
+
+        stack.push(path.to_path_buf());
+        let mut content = String::new();
+        let mut state = LineState::default();
+        for (idx, line) in raw.split_inclusive('\n').enumerate() {
+            let loc = Location {
+                file: &filename,
+                line: idx + 1,
+            };
+            let expanded = macros.process_line(&mut state, loc, line).map_err(LoadError::Macro)?;
+
+            // Sourced files are read as soon as the statement is seen, just like the lexer does
+            if state.is_statement() {
+                if let Ok((_, source)) = KSource::parse(&expanded) {
+                    let offset = source.path_str().as_ptr() as usize - expanded.as_ptr() as usize;
+                    let before = &expanded[..offset];
+                    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+                    let column = before[line_start..].chars().count() + 1;
+                    let children = self.load_source(macros, path, loc, column, &source, stack)?;
+                    self.includes.insert((id, content.len() + offset), children);
+                }
+            }
+            content.push_str(&expanded);
+        }
+        stack.pop();
+
+        self.files[id.0].content = content;
+        Ok(id)
+    }
+
+    fn load_source<S: ShellHandler>(
+        &mut self,
+        macros: &mut Preprocessor<S>,
+        path:   &Path,
+        loc:    Location,
+        column: usize,
+        source: &KSource,
+        stack:  &mut Vec<PathBuf>,
+    ) -> Result<Vec<FileId>, LoadError> {
+        let mut report = |message: String, notes: Vec<String>| {
+            self.diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                file:     PathBuf::from(loc.file),
+                line:     loc.line,
+                column,
+                message,
+                notes,
+            });
+        };
+
+        let base = if source.kind.is_relative() {
+            path.parent().unwrap_or(&self.srctree).to_path_buf()
+        } else {
+            self.srctree.clone()
+        };
+        let matches = glob(&normalize(&base.join(source.path_str())));
+        if matches.is_empty() && !source.kind.is_optional() {
+            report(format!("{} \"{}\" did not match any file", source.kind.keyword(), source.path_str()), vec![]);
+            return Ok(vec![]);
+        }
+
+        let mut children = vec![];
+        for matched in matches {
+            if stack.contains(&matched) {
+                let chain = stack.iter()
+                    .chain(std::iter::once(&matched))
+                    .map(|p| format!("included from {}", self.display_path(p).display()))
+                    .collect();
+                self.diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    file:     PathBuf::from(loc.file),
+                    line:     loc.line,
+                    column,
+                    message:  format!("recursive inclusion of {}", self.display_path(&matched).display()),
+                    notes:    chain,
+                });
+                continue;
+            }
+            match self.load_file(macros, &matched, stack) {
+                Ok(child) => children.push(child),
+                Err(LoadError::Io { path, error }) => {
+                    self.diagnostics.push(Diagnostic {
+                        severity: Severity::Error,
+                        file:     PathBuf::from(loc.file),
+                        line:     loc.line,
+                        column,
+                        message:  format!("unable to read {}: {}", self.display_path(&path).display(), error),
+                        notes:    vec![],
+                    });
+                },
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(children)
+    }
+}
+
+fn visit_errors<'a, F: FnMut(&ParseError<'a>)>(config: &KConfig<'a>, f: &mut F) {
+    fn walk<'a, F: FnMut(&ParseError<'a>)>(entries: &[Entry<'a>], f: &mut F) {
+        for entry in entries {
+            match entry {
+                Entry::Block(block)   => visit_errors(&block.config, f),
+                Entry::Choice(choice) => {
+                    choice.errors.iter().flatten().for_each(&mut *f);
+                    walk(&choice.entries, f);
+                },
+                Entry::Menu(menu)     => {
+                    menu.errors.iter().flatten().for_each(&mut *f);
+                    walk(&menu.entries, f);
+                },
+                Entry::Option(_) | Entry::Source(_) | Entry::Comment(_) => {},
+            }
+        }
+    }
+
+    config.errors.iter().flatten().for_each(&mut *f);
+    walk(&config.entries, f);
+}
+
+fn visit_sources<'a, F: FnMut(&mut KSource<'a>)>(config: &mut KConfig<'a>, f: &mut F) {
+    fn walk<'a, F: FnMut(&mut KSource<'a>)>(entries: &mut [Entry<'a>], f: &mut F) {
+        for entry in entries {
+            match entry {
+                Entry::Source(source)              => f(source),
+                Entry::Block(Block { config, .. }) => visit_sources(config, f),
+                Entry::Menu(menu)                  => walk(&mut menu.entries, f),
+                Entry::Choice(choice)              => walk(&mut choice.entries, f),
+                Entry::Option(_) | Entry::Comment(_) => {},
+            }
+        }
+    }
+
+    walk(&mut config.entries, f);
+}
+
+// Resolve `.` and `..` without touching the filesystem, so `rsource "../Kconfig.common"` is
+// reported as `drivers/Kconfig.common` rather than `drivers/net/../Kconfig.common`
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir    => {},
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push("..");
+                }
+            },
+            _ => out.push(component.as_os_str()),
+        }
+    }
+    out
+}
+
+// Expand a path which may contain `*`, `?` and `[...]` wildcards, the same subset glob(3)
+// supports. A path without any wildcards is returned as is when it exists.
+fn glob(pattern: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let part = component.as_os_str().to_string_lossy();
+        if !part.contains(['*', '?', '[']) {
+            for candidate in candidates.iter_mut() {
+                candidate.push(component.as_os_str());
+            }
+            continue;
+        }
+
+        let mut next = vec![];
+        for candidate in candidates {
+            let mut entries: Vec<PathBuf> = match fs::read_dir(&candidate) {
+                Ok(entries) => entries
+                    .flatten()
+                    .filter(|e| wildcard_match(&part, &e.file_name().to_string_lossy()))
+                    .map(|e| e.path())
+                    .collect(),
+                Err(_) => vec![],
+            };
+            entries.sort();
+            next.extend(entries);
+        }
+        candidates = next;
+    }
+    candidates.into_iter().filter(|p| p.is_file()).collect()
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    // Hidden files are only matched by a pattern that explicitly starts with a dot
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    match_from(&pattern, &name)
+}
+
+fn match_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None      => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| match_from(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && match_from(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(close) = pattern.iter().skip(2).position(|&c| c == ']').map(|p| p + 2) else {
+                return name.first() == Some(&'[') && match_from(&pattern[1..], &name[1..]);
+            };
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            let mut class = &pattern[1..close];
+            let negate = matches!(class.first(), Some('!') | Some('^'));
+            if negate {
+                class = &class[1..];
+            }
+            let mut found = false;
+            let mut idx = 0;
+            while idx < class.len() {
+                if idx + 2 < class.len() && class[idx + 1] == '-' {
+                    found |= class[idx] <= c && c <= class[idx + 2];
+                    idx += 3;
+                } else {
+                    found |= class[idx] == c;
+                    idx += 1;
+                }
+            }
+            found != negate && match_from(&pattern[close + 1..], &name[1..])
+        },
+        Some(&p)  => name.first() == Some(&p) && match_from(&pattern[1..], &name[1..]),
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    // A scratch source tree holding `files`, removed again when dropped
+    pub(in super::super) struct Tree(pub PathBuf);
+
+    impl Tree {
+        pub(in super::super) fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let root = std::env::temp_dir().join(format!("kconfigurator-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            for (path, content) in files {
+                let path = root.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, content).unwrap();
+            }
+            Self(root)
+        }
+
+        fn load(&self) -> (Vec<String>, Vec<String>) {
+            let map = SourceMap::load(&self.0, Path::new("Kconfig")).unwrap();
+            let files = map.files().map(|(_, file)| file.path.display().to_string()).collect();
+            let diagnostics = map.load_diagnostics().iter().map(|d| d.to_string()).collect();
+            (files, diagnostics)
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn wildcards() {
+        let cases = [
+            ("Kconfig*",       "Kconfig",        true),
+            ("Kconfig*",       "Kconfig.debug",  true),
+            ("*.debug",        "Kconfig.debug",  true),
+            ("*.debug",        "Kconfig.debugx", false),
+            ("Kconfig.?",      "Kconfig.a",      true),
+            ("Kconfig.?",      "Kconfig.ab",     false),
+            ("Kconfig.[ab]",   "Kconfig.b",      true),
+            ("Kconfig.[ab]",   "Kconfig.c",      false),
+            ("Kconfig.[a-c]x", "Kconfig.bx",     true),
+            ("Kconfig.[!a-c]", "Kconfig.b",      false),
+            ("Kconfig.[^a-c]", "Kconfig.d",      true),
+            ("Kconfig.[a",     "Kconfig.[a",     true),
+            ("*",              ".hidden",        false),
+            (".*",             ".hidden",        true),
+        ];
+        for (pattern, name, expected) in cases {
+            assert_eq!(wildcard_match(pattern, name), expected, "{} {}", pattern, name);
+        }
+        assert_eq!(normalize(Path::new("a/b/../c/./Kconfig")), PathBuf::from("a/c/Kconfig"));
+    }
+
+    #[test]
+    fn sources() {
+        let tree = Tree::new("sources", &[
+            ("Kconfig",               "source \"arch/Kconfig\"\nosource \"missing/Kconfig\"\nsource \"gone/Kconfig\"\n"),
+            ("arch/Kconfig",          "rsource \"Kconfig.cpu\"\nsource \"drivers/Kconfig.*\"\norsource \"none*\"\n"),
+            ("arch/Kconfig.cpu",      "config CPU\n\tbool\n"),
+            ("drivers/Kconfig.net",   "config NET\n\tbool\n"),
+            ("drivers/Kconfig.block", "config BLOCK\n\tbool\n"),
+            ("Kconfig.cpu",           "config NOT_THIS_ONE\n\tbool\n"),
+        ]);
+        let (files, diagnostics) = tree.load();
+        // `rsource` is relative to the file it is in, `source` to srctree, and globs are sorted
+        assert_eq!(files, ["Kconfig", "arch/Kconfig", "arch/Kconfig.cpu", "drivers/Kconfig.block", "drivers/Kconfig.net"]);
+        // Only the missing `source` is an error, `osource` and `orsource` may match nothing
+        assert_eq!(diagnostics, ["Kconfig:3:9: error: source \"gone/Kconfig\" did not match any file"]);
+    }
+
+    #[test]
+    fn recursion() {
+        let tree = Tree::new("recursion", &[
+            ("Kconfig",   "source \"a/Kconfig\"\n"),
+            ("a/Kconfig", "config A\n\tbool\n\nrsource \"../Kconfig\"\n"),
+        ]);
+        let (files, diagnostics) = tree.load();
+        assert_eq!(files, ["Kconfig", "a/Kconfig"]);
+        assert_eq!(diagnostics, ["a/Kconfig:4:10: error: recursive inclusion of Kconfig\n  \
+            note: included from Kconfig\n  \
+            note: included from a/Kconfig\n  \
+            note: included from Kconfig"]);
+
+        // Caught right away, however the root is spelled
+        let tree = Tree::new("recursion-root", &[
+            ("Kconfig",   "config A\n\tbool\n\nsource \"Kconfig\"\n"),
+            ("a/Kconfig", ""),
+        ]);
+        let map = SourceMap::load(&tree.0, Path::new("a/../Kconfig")).unwrap();
+        let diagnostics: Vec<String> = map.load_diagnostics().iter().map(|d| d.to_string()).collect();
+        assert_eq!(map.files().count(), 1);
+        assert_eq!(diagnostics, ["Kconfig:4:9: error: recursive inclusion of Kconfig\n  \
+            note: included from Kconfig\n  \
+            note: included from Kconfig"]);
+    }
+}
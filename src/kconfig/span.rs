@@ -0,0 +1,176 @@
+use super::FileId;
+
+use std::cell::RefCell;
+use std::ops::Range;
+
+// Where a node came from. `bytes` index into `SourceFile::content` and `lines` are 1-based with an
+// exclusive end, so a single line entry at line 12 has `lines == 12..13`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Span {
+    pub file:  FileId,
+    pub bytes: Range<usize>,
+    pub lines: Range<usize>,
+}
+
+impl Span {
+    // Used for nodes parsed outside of a `SourceMap`, there is nothing to point at
+    pub const DETACHED: FileId = FileId(usize::MAX);
+
+    pub fn is_detached(&self) -> bool {
+        self.file == Self::DETACHED
+    }
+
+    pub fn line(&self) -> usize {
+        self.lines.start
+    }
+
+    // The span of everything consumed between `start` and `end`, where both are the remaining
+    // input at those two points. Surrounding whitespace and blank lines are not included.
+    pub fn between(start: &str, end: &str) -> Self {
+        let consumed = &start[..start.len() - end.len()];
+        let trimmed_start = consumed.trim_start();
+        let trimmed = trimmed_start.trim_end();
+        Self::of(trimmed)
+    }
+
+    pub fn of(slice: &str) -> Self {
+        CURRENT.with(|current| match &*current.borrow() {
+            Some(ctx) if ctx.contains(slice) => {
+                let start = slice.as_ptr() as usize - ctx.base;
+                let end = start + slice.len();
+                Self {
+                    file:  ctx.file,
+                    bytes: start..end,
+                    lines: ctx.line_of(start)..ctx.line_of(end.max(start + 1) - 1) + 1,
+                }
+            },
+            _ => Self {
+                file:  Self::DETACHED,
+                bytes: 0..slice.len(),
+                lines: 0..0,
+            },
+        })
+    }
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Self {
+            file:  Self::DETACHED,
+            bytes: 0..0,
+            lines: 0..0,
+        }
+    }
+}
+
+struct Context {
+    file:        FileId,
+    base:        usize,
+    len:         usize,
+    line_starts: Vec<usize>,
+}
+
+impl Context {
+    fn contains(&self, slice: &str) -> bool {
+        let ptr = slice.as_ptr() as usize;
+        ptr >= self.base && ptr + slice.len() <= self.base + self.len
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(idx)  => idx + 1,
+            Err(idx) => idx,
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+// Spans are only meaningful relative to the file being parsed. The parsers themselves only ever
+// see `&str`, so the file is made known to `Span::of` for as long as the guard lives.
+pub struct SpanGuard {
+    previous: Option<Context>,
+}
+
+pub fn enter_file(file: FileId, content: &str) -> SpanGuard {
+    let line_starts = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(idx, _)| idx + 1))
+        .collect();
+    let ctx = Context {
+        file,
+        base: content.as_ptr() as usize,
+        len:  content.len(),
+        line_starts,
+    };
+    SpanGuard {
+        previous: CURRENT.with(|current| current.replace(Some(ctx))),
+    }
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::KOption;
+
+    #[test]
+    fn ranges() {
+        let content = "config A\n\tbool \"a\"\n\n\nconfig B\n\tint\n";
+        let _guard = enter_file(FileId(3), content);
+
+        let span = Span::of(&content[9..19]);
+        assert_eq!((span.file, span.bytes, span.lines), (FileId(3), 9..19, 2..3));
+
+        // The line ending and blank lines after `A` are left out, and those before `B`
+        let span = Span::between(content, &content[21..]);
+        assert_eq!((span.bytes, span.lines), (0..18, 1..3));
+        let span = Span::between(&content[19..], "");
+        assert_eq!(span.line(), 5);
+        assert_eq!((span.bytes, span.lines), (21..34, 5..7));
+
+        // An empty slice still sits on a line
+        let span = Span::of(&content[content.len()..]);
+        assert_eq!(span.lines, 7..8);
+    }
+
+    #[test]
+    fn detached() {
+        // Not part of any file entered
+        let span = Span::of("config A");
+        assert!(span.is_detached());
+        assert_eq!((span.bytes, span.lines), (0..8, 0..0));
+
+        let (_, option) = KOption::parse("config A\n\tbool \"a\"\n").unwrap();
+        assert!(option.span.is_detached());
+        assert_eq!(option.span.bytes, 0..18);
+
+        let other = String::from("config B\n");
+        let _guard = enter_file(FileId(0), "config A\n");
+        assert!(Span::of(&other).is_detached());
+    }
+
+    #[test]
+    fn nested_files() {
+        let outer = "config OUTER\n";
+        let inner = "\nconfig INNER\n";
+        let outer_guard = enter_file(FileId(1), outer);
+        {
+            let _inner_guard = enter_file(FileId(2), inner);
+            let span = Span::of(&inner[1..]);
+            assert_eq!((span.file, span.lines), (FileId(2), 2..3));
+            // The outer file is not current while the inner one is
+            assert!(Span::of(outer).is_detached());
+        }
+        assert_eq!(Span::of(outer).file, FileId(1));
+        drop(outer_guard);
+        assert!(Span::of(outer).is_detached());
+    }
+}
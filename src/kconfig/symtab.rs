@@ -0,0 +1,287 @@
+use super::{
+    Dependency,
+    Diagnostic,
+    Expr,
+    KChoice,
+    KOption,
+    MenuItem,
+    MenuTree,
+    OptionType,
+    Prompt,
+    Range,
+    Severity,
+    SourceMap,
+};
+
+use std::collections::BTreeMap;
+
+// Every symbol in the tree, with all of its definitions merged. Kconfig allows a symbol to be
+// defined any number of times, and each definition adds its prompt, defaults, selects and so on
+// to the same symbol. This is common across the arch Kconfigs, where a generic symbol gets an
+// extra default or a prompt under some condition.
+#[derive(Debug)]
+pub struct SymbolTable<'k, 'a> {
+    pub symbols: BTreeMap<&'a str, SymbolInfo<'k, 'a>>,
+
+    // Names in the order they are first defined, which is the order `.config` lists them in
+    pub order:   Vec<&'a str>,
+}
+
+#[derive(Debug)]
+pub struct SymbolInfo<'k, 'a> {
+    pub name:        &'a str,
+
+    // Taken from the first definition that declares one, `None` if none of them do
+    pub option_type: Option<OptionType>,
+    pub definitions: Vec<Definition<'k, 'a>>,
+
+    // The symbol can be enabled when any one of its definitions' dependencies hold, this is
+    // their `||`. `None` when one of them has no dependencies at all.
+    pub dir_dep:     Option<Expr<'a>>,
+
+    // Properties from all definitions, in definition order. Each remembers which definition it
+    // came from, as that definition's dependencies apply to it.
+    pub prompts:     Vec<Property<'k, Prompt<'a>>>,
+    pub defaults:    Vec<Property<'k, Dependency<'a>>>,
+    pub selects:     Vec<Property<'k, Dependency<'a>>>,
+    pub implies:     Vec<Property<'k, Dependency<'a>>>,
+    pub ranges:      Vec<Property<'k, Range<'a>>>,
+}
+
+#[derive(Debug)]
+pub struct Definition<'k, 'a> {
+    pub option:   &'k KOption<'a>,
+
+    // Index into `MenuTree::nodes`
+    pub node:     usize,
+
+    // The prompts of the menus leading to this definition
+    pub location: Vec<&'k str>,
+
+    // Set when the definition is one of the values of a choice
+    pub choice:   Option<&'k KChoice<'a>>,
+
+    // Effective dependencies of this definition, see `MenuNode::dep`
+    pub dep:      Option<Expr<'a>>,
+}
+
+#[derive(Debug)]
+pub struct Property<'k, T> {
+    // Index into `SymbolInfo::definitions`
+    pub definition: usize,
+    pub value:      &'k T,
+}
+
+impl<'k, 'a> SymbolInfo<'k, 'a> {
+    fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            option_type: None,
+            definitions: vec![],
+            dir_dep:     None,
+            prompts:     vec![],
+            defaults:    vec![],
+            selects:     vec![],
+            implies:     vec![],
+            ranges:      vec![],
+        }
+    }
+
+    fn add(&mut self, definition: Definition<'k, 'a>) {
+        let opt = definition.option;
+        let idx = self.definitions.len();
+        if opt.typed && self.option_type.is_none() {
+            self.option_type = Some(opt.option_type);
+        }
+
+        fn each<'k, T>(out: &mut Vec<Property<'k, T>>, idx: usize, values: &'k Option<Vec<T>>) {
+            out.extend(values.iter().flatten().map(|value| Property { definition: idx, value }));
+        }
+        if let Some(prompt) = &opt.prompt {
+            self.prompts.push(Property { definition: idx, value: prompt });
+        }
+        // `def_bool` and `def_tristate` are a type and a default in one, and the first default
+        // that applies wins, so they stay in the order they were written in
+        self.defaults.extend(opt.all_defaults().into_iter().map(|value| Property { definition: idx, value }));
+        each(&mut self.selects,  idx, &opt.selects);
+        each(&mut self.implies,  idx, &opt.implies);
+        each(&mut self.ranges,   idx, &opt.ranges);
+        self.dir_dep = match idx {
+            0 => definition.dep.clone(),
+            _ => Expr::or(self.dir_dep.take(), definition.dep.clone()),
+        };
+        self.definitions.push(definition);
+    }
+
+    pub fn choice(&self) -> Option<&'k KChoice<'a>> {
+        self.definitions.iter().find_map(|d| d.choice)
+    }
+}
+
+impl<'k, 'a> SymbolTable<'k, 'a> {
+    pub fn build(tree: &MenuTree<'k, 'a>) -> Self {
+        let mut table = Self {
+            symbols: BTreeMap::new(),
+            order:   vec![],
+        };
+        // Depth first, so definitions are visited in the order they appear in the tree
+        let mut stack: Vec<usize> = tree.roots.iter().rev().copied().collect();
+        while let Some(idx) = stack.pop() {
+            let node = &tree.nodes[idx];
+            stack.extend(node.children.iter().rev());
+
+            let MenuItem::Option(option) = node.item else {
+                continue;
+            };
+            let choice = node.parent.and_then(|parent| match tree.nodes[parent].item {
+                MenuItem::Choice(choice) => Some(choice),
+                _                        => None,
+            });
+            let name = option.name.name;
+            let info = table.symbols.entry(name).or_insert_with(|| SymbolInfo::new(name));
+            if info.definitions.is_empty() {
+                table.order.push(name);
+            }
+            info.add(Definition {
+                option,
+                node: idx,
+                location: tree.location(idx),
+                choice,
+                dep: node.dep.clone(),
+            });
+        }
+        table
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SymbolInfo<'k, 'a>> {
+        self.symbols.get(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    // Symbols in the order they are first defined
+    pub fn iter(&self) -> impl Iterator<Item = &SymbolInfo<'k, 'a>> {
+        self.order.iter().map(|name| &self.symbols[name])
+    }
+
+    // Definitions that declare a different type than the symbol already has, and symbols that are
+    // never given a type at all
+    pub fn diagnostics(&self, sources: &SourceMap) -> Vec<Diagnostic> {
+        let mut out = vec![];
+        for info in self.iter() {
+            let Some(option_type) = info.option_type else {
+                let first = info.definitions[0].option;
+                out.push(sources.diagnostic(Severity::Warning, &first.span,
+                    format!("config symbol `{}` is never given a type", info.name)));
+                continue;
+            };
+            let first = info.definitions.iter().find(|d| d.option.typed).unwrap();
+            for def in &info.definitions {
+                if def.option.typed && def.option.option_type != option_type {
+                    let mut diagnostic = sources.diagnostic(Severity::Warning, &def.option.span,
+                        format!("`{}` redefined as {} but it is already {}, the new type is ignored",
+                            info.name, def.option.option_type, option_type));
+                    diagnostic.notes.push(format!("first defined as {} at {}", option_type, sources.location(&first.option.span)));
+                    out.push(diagnostic);
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{
+        Tristate,
+        engine::tests::with_engine,
+        source_map::tests::Tree,
+    };
+
+    use std::path::Path;
+
+    const KCONFIG: &str = "
+config MODULES
+	bool \"modules\"
+	default y
+
+config B
+	bool
+
+config A
+	tristate \"a\"
+	default n if B
+	def_tristate m
+	default y
+	depends on B || MODULES
+
+menu \"more\"
+
+config A
+	prompt \"a again\"
+	def_bool y
+	select B
+	depends on MODULES
+
+endmenu
+";
+
+    #[test]
+    fn merge() {
+        with_engine(KCONFIG, |engine| {
+            let info = engine.symbols.get("A").unwrap();
+            assert_eq!(info.option_type, Some(OptionType::Tristate));
+            assert_eq!(info.definitions.len(), 2);
+            assert_eq!(info.definitions[1].location, ["more"]);
+            assert_eq!(info.prompts.iter().map(|p| (p.definition, p.value.text.as_str())).collect::<Vec<_>>(),
+                [(0, "a"), (1, "a again")]);
+
+            // Defaults keep the order they were written in, whatever keyword they were written with
+            let defaults: Vec<(usize, String)> = info.defaults.iter()
+                .map(|d| (d.definition, d.value.expression.to_string()))
+                .collect();
+            assert_eq!(defaults, [(0, "n".to_string()), (0, "m".to_string()), (0, "y".to_string()), (1, "y".to_string())]);
+            assert_eq!(info.selects.len(), 1);
+            assert_eq!(info.dir_dep.as_ref().unwrap().to_string(), "B || MODULES || MODULES");
+            assert_eq!(engine.symbols.order, ["MODULES", "B", "A"]);
+
+            // `def_tristate m` comes before `default y`, and `B` is only selected once `A` is set
+            assert_eq!(engine.value("A").unwrap().tristate(), Tristate::Module);
+        });
+    }
+
+    #[test]
+    fn type_conflicts() {
+        let tree = Tree::new("symtab", &[("Kconfig", "\
+config A
+\tbool \"a\"
+
+config A
+\ttristate
+
+config A
+\tdefault y
+
+config B
+\tdefault y
+")]);
+        let sources = SourceMap::load(&tree.0, Path::new("Kconfig")).unwrap();
+        let (config, diagnostics) = sources.parse();
+        assert!(diagnostics.is_empty());
+        let menu_tree = MenuTree::build(&config);
+        let table = SymbolTable::build(&menu_tree);
+        assert_eq!(table.get("A").unwrap().option_type, Some(OptionType::Bool));
+        let text: Vec<String> = table.diagnostics(&sources).iter().map(|d| d.to_string()).collect();
+        assert_eq!(text, [
+            "Kconfig:4:1: warning: `A` redefined as tristate but it is already bool, the new type is ignored\n  note: first defined as bool at Kconfig:1",
+            "Kconfig:10:1: warning: config symbol `B` is never given a type",
+        ]);
+    }
+}
@@ -0,0 +1,225 @@
+use super::{
+    Dependency,
+    Diagnostic,
+    Entry,
+    Expr,
+    KCommentBlock,
+    KConfig,
+    KOption,
+    Prompt,
+    RangeType,
+    Severity,
+    SourceMap,
+    Span,
+    SymbolTable,
+};
+
+use std::collections::HashSet;
+
+// Every reference to a symbol that no config defines, in `depends on`, `select`, `imply`,
+// `default`, `range`, `visible if`, the conditions of prompts and of `if` blocks, as `scripts/checkkconfigsymbols.py` reports them for
+// Kconfig files. An undefined symbol reads as `n`, or as its own name when compared, which is
+// almost always a typo or a leftover from a symbol that was removed.
+pub fn undefined_symbols(config: &KConfig, symbols: &SymbolTable, sources: &SourceMap) -> Vec<Diagnostic> {
+    let mut lint = Lint {
+        symbols,
+        sources,
+        out: vec![],
+    };
+    lint.entries(&config.entries);
+    lint.out
+}
+
+struct Lint<'s, 'k, 'a> {
+    symbols: &'s SymbolTable<'k, 'a>,
+    sources: &'s SourceMap,
+    out:     Vec<Diagnostic>,
+}
+
+impl Lint<'_, '_, '_> {
+    fn entries(&mut self, entries: &[Entry]) {
+        for entry in entries {
+            match entry {
+                Entry::Option(opt)      => self.option(opt),
+                Entry::Menu(menu)       => {
+                    let owner = format!("menu {}", menu.description);
+                    self.properties("depends on", &owner, menu.depends.as_deref());
+                    self.properties("visible if", &owner, menu.visible.as_deref());
+                    self.entries(&menu.entries);
+                },
+                Entry::Choice(choice)   => {
+                    let owner = match &choice.prompt {
+                        Some(prompt) => format!("choice \"{}\"", prompt.text.as_str()),
+                        None         => "choice".to_string(),
+                    };
+                    self.prompt(&owner, choice.prompt.as_ref());
+                    self.properties("depends on", &owner, choice.depends.as_deref());
+                    self.properties("default", &owner, choice.defaults.as_deref());
+                    self.entries(&choice.entries);
+                },
+                Entry::Comment(comment) => self.comment(comment),
+                Entry::Block(block)     => {
+                    self.check("`if` block", &block.span, names(Some(&block.condition)));
+                    self.entries(&block.config.entries);
+                },
+                Entry::Source(source)   => {
+                    for config in &source.configs {
+                        self.entries(&config.entries);
+                    }
+                },
+            }
+        }
+    }
+
+    fn option(&mut self, opt: &KOption) {
+        let owner = format!("`{}`", opt.name.name);
+        self.prompt(&owner, opt.prompt.as_ref());
+        self.properties("depends on", &owner, opt.depends.as_deref());
+        self.properties("select", &owner, opt.selects.as_deref());
+        self.properties("imply", &owner, opt.implies.as_deref());
+        self.properties("default", &owner, opt.defaults.as_deref());
+        self.properties("def_bool", &owner, opt.def_bool.as_deref());
+        self.properties("def_tristate", &owner, opt.def_tristate.as_deref());
+        for range in opt.ranges.iter().flatten() {
+            let bounds = [&range.start, &range.end].into_iter().filter_map(|bound| match bound {
+                RangeType::Symbol(symbol) => Some(symbol.name),
+                _                         => None,
+            });
+            let names: Vec<&str> = bounds.chain(names(range.condition.as_ref())).collect();
+            self.check(&format!("`range` of {}", owner), &range.span, names);
+        }
+    }
+
+    fn comment(&mut self, comment: &KCommentBlock) {
+        let owner = format!("comment \"{}\"", comment.prompt.text.as_str());
+        self.prompt(&owner, Some(&comment.prompt));
+        self.properties("depends on", &owner, comment.depends.as_deref());
+    }
+
+    // The `if` of a prompt, `bool "x" if FOO`
+    fn prompt(&mut self, owner: &str, prompt: Option<&Prompt>) {
+        if let Some(prompt) = prompt {
+            self.check(&format!("`prompt` of {}", owner), &prompt.span, names(prompt.condition.as_ref()));
+        }
+    }
+
+    fn properties(&mut self, keyword: &str, owner: &str, properties: Option<&[Dependency]>) {
+        for property in properties.into_iter().flatten() {
+            let names = names(Some(&property.expression)).into_iter().chain(names(property.condition.as_ref())).collect();
+            self.check(&format!("`{}` of {}", keyword, owner), &property.span, names);
+        }
+    }
+
+    // One warning for each undefined symbol of a property. Numbers the parser took for symbols,
+    // such as the bounds of some ranges, are constants.
+    fn check(&mut self, place: &str, span: &Span, names: Vec<&str>) {
+        let mut seen = HashSet::new();
+        for name in names {
+            if self.symbols.get(name).is_some() || is_number(name) || !seen.insert(name) {
+                continue;
+            }
+            self.out.push(self.sources.diagnostic(Severity::Warning, span,
+                format!("`{}` in {} is not defined", name, place)));
+        }
+    }
+}
+
+fn names<'e>(expr: Option<&'e Expr>) -> Vec<&'e str> {
+    expr.map_or_else(Vec::new, |expr| expr.symbols().into_iter().map(|symbol| symbol.name).collect())
+}
+
+fn is_number(name: &str) -> bool {
+    let digits = name.strip_prefix('-').unwrap_or(name);
+    match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None      => !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{
+        MenuTree,
+        dead_code,
+        source_map::tests::Tree,
+    };
+
+    use std::path::Path;
+
+    #[test]
+    fn references() {
+        let tree = Tree::new("undefined", &[("Kconfig", "\
+config A
+\tbool \"a\"
+\tdepends on MISSING || B != 0
+\tselect GONE if MISSING
+\tdefault TYPO
+
+config B
+\tint \"b\"
+\trange 0 LIMIT
+\tdefault 0x10
+
+menu \"m\"
+\tvisible if HIDDEN
+
+config C
+\tbool \"c\"
+\tdepends on A && B = 010
+
+endmenu
+
+config D
+\tbool \"d\"
+\tdepends on MISSING
+
+if NOT_THERE
+config E
+\tbool \"e\" if PROMPT_IF
+endif
+
+choice
+\tprompt \"ch\" if CHOICE_IF
+
+config F
+\tbool \"f\"
+
+endchoice
+
+comment \"note\" if COMMENT_IF
+")]);
+        let sources = SourceMap::load(&tree.0, Path::new("Kconfig")).unwrap();
+        let (config, diagnostics) = sources.parse();
+        assert!(diagnostics.is_empty());
+        let menu_tree = MenuTree::build(&config);
+        let symbols = SymbolTable::build(&menu_tree);
+        let text = |found: Vec<Diagnostic>| found.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+
+        // Each symbol once per property, numbers are not symbols
+        assert_eq!(text(undefined_symbols(&config, &symbols, &sources)), [
+            "Kconfig:3:2: warning: `MISSING` in `depends on` of `A` is not defined",
+            "Kconfig:4:2: warning: `GONE` in `select` of `A` is not defined",
+            "Kconfig:4:2: warning: `MISSING` in `select` of `A` is not defined",
+            "Kconfig:5:2: warning: `TYPO` in `default` of `A` is not defined",
+            "Kconfig:9:2: warning: `LIMIT` in `range` of `B` is not defined",
+            "Kconfig:13:2: warning: `HIDDEN` in `visible if` of menu \"m\" is not defined",
+            "Kconfig:23:2: warning: `MISSING` in `depends on` of `D` is not defined",
+            "Kconfig:25:1: warning: `NOT_THERE` in `if` block is not defined",
+            "Kconfig:27:7: warning: `PROMPT_IF` in `prompt` of `E` is not defined",
+            "Kconfig:31:2: warning: `CHOICE_IF` in `prompt` of choice \"ch\" is not defined",
+            "Kconfig:38:9: warning: `COMMENT_IF` in `prompt` of comment \"note\" is not defined",
+        ]);
+
+        // The dead code lint reports the same symbols again where nothing else can make up for
+        // them, as an entry that can never be enabled or a prompt that is never shown. `E` is
+        // inside a block that is already reported.
+        assert_eq!(text(dead_code(&config, &menu_tree, &symbols, &sources)), [
+            "Kconfig:12:1: warning: prompt of menu \"m\" can never be visible\n  note: shown when HIDDEN\n  note: `HIDDEN` is not defined",
+            "Kconfig:21:1: warning: dependencies of `D` can never be met\n  note: depends on MISSING\n  note: `MISSING` is not defined",
+            "Kconfig:25:1: warning: condition of `if NOT_THERE` can never hold\n  note: needs NOT_THERE\n  note: `NOT_THERE` is not defined",
+            "Kconfig:30:1: warning: prompt of choice \"ch\" can never be visible\n  note: shown when CHOICE_IF\n  note: `CHOICE_IF` is not defined",
+            "Kconfig:38:1: warning: prompt of comment \"note\" can never be visible\n  note: shown when COMMENT_IF\n  note: `COMMENT_IF` is not defined",
+        ]);
+    }
+}
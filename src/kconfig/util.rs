@@ -1,6 +1,8 @@
 use super::{
     KConfig,
+    Span,
     expr::{
+        Expr,
         parse_expr,
     }
 };
@@ -41,6 +43,8 @@ use nom::{
     IResult,
 };
 
+use std::borrow::Cow;
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum OptionType {
     Tristate,
@@ -63,7 +67,7 @@ impl OptionType {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Delimiter<'a> {
     SingleQuote(&'a str),
     DoubleQuote(&'a str),
@@ -71,15 +75,40 @@ pub enum Delimiter<'a> {
     //DollarParentheses(&'a str),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct ConstantSymbol<'a> {
+    // Macros have already been expanded by the `Preprocessor` by the time a string is parsed out
+    // of a file loaded through `SourceMap`, the content is never expanded a second time.
     pub delimiter: Delimiter<'a>,
-
-    // Once the string has been processed through macros, it should not be reprocessed.
-    processed:     bool,
 }
 
 impl<'a> ConstantSymbol<'a> {
+    // The text between the quotes
+    pub fn as_str(&self) -> &'a str {
+        match self.delimiter {
+            Delimiter::SingleQuote(s) => s,
+            Delimiter::DoubleQuote(s) => s,
+        }
+    }
+
+    // The value of the string, a backslash escapes whatever character follows it like in the
+    // kernel's lexer
+    pub fn unescaped(&self) -> Cow<'a, str> {
+        let text = self.as_str();
+        if !text.contains('\\') {
+            return Cow::Borrowed(text);
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => out.extend(chars.next()),
+                c    => out.push(c),
+            }
+        }
+        Cow::Owned(out)
+    }
+
     pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
         // Grab the matching delimiter tag, but do not move the input position forward. This allows
         // for better readability later when we call the `delimited` parser instead of using the
@@ -123,12 +152,11 @@ impl<'a> ConstantSymbol<'a> {
 
         Ok((input, Self{
             delimiter,
-            processed: false,
         }))
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Symbol<'a> {
     pub name: &'a str,
 }
@@ -145,10 +173,12 @@ impl<'a> Symbol<'a> {
 #[derive(Debug, PartialEq)]
 pub struct Annotation<'a> {
     pub text: &'a str,
+    pub span: Span,
 }
 
 impl<'a> Annotation<'a> {
     pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let start = input;
         let (input, text) = preceded(
             tuple((
                 space0,
@@ -158,34 +188,23 @@ impl<'a> Annotation<'a> {
             take_until("\n"),
         )(input)?;
         Ok((input, Self{
+            span: Span::between(start, input),
             text,
         }))
     }
 }
 
-#[derive(Debug)]
-pub struct Expression<'a> {
-    pub val: &'a str,  // NOTE: transition hack before we switch to expr::Expr
-}
-
-impl<'a> Expression<'a> {
-    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        let (input, e) = recognize(parse_expr)(input)?;
-        Ok((input, Self{
-            val: e,
-        }))
-    }
-}
-
 #[derive(Debug)]
 pub struct Prompt<'a> {
     pub text:      ConstantSymbol<'a>,
-    pub condition: Option<Expression<'a>>,
+    pub condition: Option<Expr<'a>>,
+    pub span:      Span,
 }
 
 impl<'a> Prompt<'a> {
     pub fn parse(str_match: &str) -> impl Fn(&'a str) -> IResult<&'a str, Prompt<'a>> + '_ {
         move |input: &str| -> IResult<&str, Self> {
+            let start = input;
             let (input, (text, condition)) = preceded(
                 tuple((
                     space0,
@@ -200,12 +219,13 @@ impl<'a> Prompt<'a> {
                             tag("if"),
                             special_space,
                         )),
-                        Expression::parse,
+                        parse_expr,
                     )),
                 )),
             )(input)?;
 
             Ok((input, Self {
+                span: Span::between(start, input),
                 text,
                 condition,
             }))
@@ -215,14 +235,16 @@ impl<'a> Prompt<'a> {
 
 #[derive(Debug)]
 pub struct Dependency<'a> {
-    pub expression: Expression<'a>,
-    pub condition:  Option<Expression<'a>>,
+    pub expression: Expr<'a>,
+    pub condition:  Option<Expr<'a>>,
     pub annotation: Option<Annotation<'a>>,
+    pub span:       Span,
 }
 
 impl<'a> Dependency<'a> {
     pub fn parse(str_match: &str) -> impl Fn(&'a str) -> IResult<&'a str, Dependency<'a>> + '_ {
         move |input: &str| -> IResult<&str, Self> {
+            let start = input;
             let (input, (expression, condition, annotation)) = delimited(
                 tuple((
                     space0,
@@ -230,14 +252,14 @@ impl<'a> Dependency<'a> {
                     space1,
                 )),
                 tuple((
-                    Expression::parse,
+                    parse_expr,
                     opt(preceded(
                         tuple((
                             special_space,
                             tag("if"),
                             special_space,
                         )),
-                        Expression::parse,
+                        parse_expr,
                     )),
                     opt(Annotation::parse),
                 )),
@@ -248,6 +270,7 @@ impl<'a> Dependency<'a> {
             )(input)?;
 
             Ok((input, Self {
+                span: Span::between(start, input),
                 expression,
                 condition,
                 annotation,
@@ -267,12 +290,14 @@ pub enum RangeType<'a> {
 pub struct Range<'a> {
     pub start: RangeType<'a>,
     pub end:   RangeType<'a>,
-    pub condition: Option<Expression<'a>>,
+    pub condition: Option<Expr<'a>>,
+    pub span:  Span,
 }
 
 impl<'a> Range<'a> {
     pub fn parse(str_match: &str) -> impl Fn(&'a str) -> IResult<&'a str, Range<'a>> + '_ {
         move |input: &str| -> IResult<&str, Self> {
+            let begin = input;
             let (input, ((start, end), condition)) = preceded(
                 tuple((
                     space0,
@@ -291,11 +316,12 @@ impl<'a> Range<'a> {
                             tag("if"),
                             special_space,
                         )),
-                        Expression::parse,
+                        parse_expr,
                     )),
                 )),
             )(input)?;
             Ok((input, Self {
+                span: Span::between(begin, input),
                 start,
                 end,
                 condition,
@@ -307,11 +333,13 @@ impl<'a> Range<'a> {
 #[derive(Debug)]
 pub struct Help<'a> {
     pub text: Vec<&'a str>,
+    pub span: Span,
 }
 
 impl<'a> Help<'a> {
     pub fn parse(str_match: &str) -> impl Fn(&'a str) -> IResult<&'a str, Help> + '_ {
         move |input: &str| -> IResult<&str, Self> {
+            let start = input;
             //let (input, _) = space0(input)?;
             //let (input, _) = tag("help")(input)?;
             //let (input, _) = many1(tuple((space0, line_ending)))(input)?;
@@ -339,10 +367,29 @@ impl<'a> Help<'a> {
             )))(input)?;
 
             Ok((input, Self {
+                span: Span::between(start, input),
                 text,
             }))
         }
     }
+
+    // The text as it is shown to the user: the indentation of the first line is removed from
+    // every line, deeper indentation is kept and trailing blank lines are dropped
+    pub fn lines(&self) -> Vec<String> {
+        let text = self.text.concat();
+        let indent = |line: &str| count_whitespace(&line[..line.len() - line.trim_start().len()]);
+        let base = text.lines().find(|line| !line.trim().is_empty()).map_or(0, indent);
+        let mut lines: Vec<String> = text.lines()
+            .map(|line| match line.trim() {
+                ""   => String::new(),
+                rest => format!("{}{}", " ".repeat(indent(line).saturating_sub(base)), rest),
+            })
+            .collect();
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        lines
+    }
 }
 
 pub fn count_whitespace(s: &str) -> usize {
@@ -364,18 +411,19 @@ fn take_while_help(min_ws: usize) -> impl Fn(&str) -> IResult<&str, &str> {
         let (_, raw_ws) = space1(input)?;
         let ws = count_whitespace(raw_ws);
 
-        // Now we will take all characters from that line until and including the '\n' or EOF
-        let (input, line) = preceded(
+        // Now we will take all characters from that line until and including the '\n' or EOF. The
+        // indentation is kept, lines indented deeper than the first are meant to stand out.
+        let (input, line) = recognize(tuple((
             space0,
             alt((
                 take_until("\n"),          // Take until newline
                 recognize(many1(anychar)), // There is no '\n', take any char until eof
             )),
-        )(input)?;
-        if ws < min_ws && !line.is_empty() { // if current line `ws` is less that `min_ws` the block has ended
+        )))(input)?;
+        if ws < min_ws && !line.trim().is_empty() { // if current line `ws` is less that `min_ws` the block has ended
             Err(nom::Err::Error(
                 nom::error::Error{
-                    input: input,
+                    input,
                     code: nom::error::ErrorKind::Tag,
                 }
             ))
@@ -389,6 +437,16 @@ pub fn take_line_ending(input: &str) -> IResult<&str, &str> {
     recognize(many1(tuple((space0, line_ending))))(input)
 }
 
+// Blank lines and `#` comments between two entries or attributes, the comments are handed back so
+// they can be attached to whatever follows
+pub fn take_gap(input: &str) -> IResult<&str, Vec<Annotation<'_>>> {
+    let (input, notes) = many0(alt((
+        map(take_line_ending,  |_| None),
+        map(Annotation::parse, Some),
+    )))(input)?;
+    Ok((input, notes.into_iter().flatten().collect()))
+}
+
 pub fn special_space(input: &str) -> IResult<&str, &str> {
     recognize(many0(alt((
         space1,
@@ -419,14 +477,97 @@ pub fn take_continued_line(input: &str) -> IResult<&str, &str> {
     )))(input)
 }
 
+// Every keyword which starts (or ends) an entry. Skipping a broken entry stops at the next line
+// beginning with one of these.
+const ENTRY_KEYWORDS: [&str; 14] = [
+    "config", "menuconfig", "choice", "endchoice", "comment", "menu", "endmenu",
+    "if", "endif", "source", "rsource", "osource", "orsource", "mainmenu",
+];
+const END_KEYWORDS: [&str; 3] = ["endchoice", "endmenu", "endif"];
+
+pub fn first_word(input: &str) -> &str {
+    let line = input.trim_start_matches([' ', '\t']);
+    let end = line.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-')).unwrap_or(line.len());
+    &line[..end]
+}
+
+// Consume one line, including any continuation lines joined to it with a trailing backslash
+fn skip_line(input: &str) -> &str {
+    let mut rest = input;
+    loop {
+        match rest.find('\n') {
+            Some(idx) => {
+                let continued = rest[..idx].trim_end_matches([' ', '\t']).ends_with('\\');
+                rest = &rest[idx + 1..];
+                if !continued {
+                    return rest;
+                }
+            },
+            None => return "",
+        }
+    }
+}
+
+// Lines which could not be parsed as part of the entry containing them. They are skipped up to
+// the next entry keyword so a single mistake does not take the rest of the file down with it.
+#[derive(Debug)]
+pub struct ParseError<'a> {
+    pub skipped: &'a str,
+
+    // Keyword and starting text of the enclosing `menu`, `choice` or `if`, when there is one
+    pub inside:  Option<(&'static str, &'a str)>,
+}
+
+impl<'a> ParseError<'a> {
+    pub fn parse(inside: Option<(&'static str, &'a str)>) -> impl Fn(&'a str) -> IResult<&'a str, ParseError<'a>> {
+        move |input: &'a str| -> IResult<&'a str, Self> {
+            // Closing keywords are left for the enclosing entry, or the caller, to deal with
+            if input.is_empty() || END_KEYWORDS.contains(&first_word(input)) {
+                return Err(nom::Err::Error(
+                    nom::error::Error::new(input, nom::error::ErrorKind::Verify)
+                ));
+            }
+
+            // Help text is free form, a line of it starting with `if` is not an entry
+            let mut help_indent: Option<Option<usize>> = None;
+            let mut rest = skip_line(input);
+            while !rest.is_empty() {
+                let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+                let ws = count_whitespace(&line[..line.len() - line.trim_start().len()]);
+                match help_indent {
+                    Some(_) if line.trim().is_empty()        => {},
+                    Some(None) if ws > 0                     => help_indent = Some(Some(ws)),
+                    Some(Some(min)) if ws >= min             => {},
+                    _ if ENTRY_KEYWORDS.contains(&first_word(line)) => break,
+                    _                                        => {
+                        help_indent = None;
+                        if line.trim() == "help" || line.trim() == "---help---" {
+                            help_indent = Some(None);
+                        }
+                    },
+                }
+                rest = skip_line(rest);
+            }
+
+            Ok((rest, Self {
+                skipped: &input[..input.len() - rest.len()],
+                inside,
+            }))
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Block<'a> {
-    pub config:    KConfig<'a>,
-    pub condition: Expression<'a>,
+    pub config:      KConfig<'a>,
+    pub condition:   Expr<'a>,
+    pub annotations: Option<Vec<Annotation<'a>>>,
+    pub span:        Span,
 }
 
 impl<'a> Block<'a> {
     pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let start = input;
         let (input, (condition, config)) = delimited(
             tuple((
                 space0,
@@ -434,8 +575,8 @@ impl<'a> Block<'a> {
                 space1,
             )),
             tuple((
-                Expression::parse,
-                KConfig::parse,
+                parse_expr,
+                KConfig::parse_inside(Some(("if", input))),
             )),
             tuple((
                 space0,
@@ -443,13 +584,15 @@ impl<'a> Block<'a> {
             )),
         )(input)?;
         Ok((input, Self {
+            span: Span::between(start, input),
             config,
             condition,
+            annotations: None,
         }))
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Int {
     pub val: i128,
 }
@@ -472,7 +615,7 @@ impl Int {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Hex {
     pub val: u128,
 }
@@ -505,3 +648,33 @@ impl Hex {
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_to_next_entry() {
+        let input = "\
+config A
+\tbogus
+\thelp
+\t  menu is only a word in the help text
+
+\t  and so is config
+endif
+";
+        // Skipping stops at the closing keyword, not at the entry keywords in the help text
+        let (rest, error) = ParseError::parse(None)(input).unwrap();
+        assert_eq!(rest, "endif\n");
+        assert_eq!(error.skipped, &input[..input.len() - rest.len()]);
+
+        let (rest, error) = ParseError::parse(Some(("menu", input)))("bogus\n\tline\n  source \"a\"\n").unwrap();
+        assert_eq!((rest, error.skipped), ("  source \"a\"\n", "bogus\n\tline\n"));
+        assert_eq!(error.inside.map(|(keyword, _)| keyword), Some("menu"));
+
+        // Closing keywords are left for the enclosing entry
+        assert!(ParseError::parse(None)("endmenu\n").is_err());
+        assert!(ParseError::parse(None)("").is_err());
+    }
+}
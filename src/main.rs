@@ -1,70 +1,580 @@
-mod kconfig;
+use kconfigurator::kconfig::{
+    assignment_line,
+    dead_code,
+    diff,
+    explain,
+    format,
+    format_diff,
+    generate,
+    help_new,
+    list_new,
+    merge,
+    new_json,
+    new_symbols,
+    oldconfig,
+    solve,
+    undefined_symbols,
+    write_defconfig,
+    write_dotconfig,
+    Diagnostic,
+    DotConfig,
+    Engine,
+    Generator,
+    HostShell,
+    NoShell,
+    MenuTree,
+    OptionType,
+    SymbolTable,
+    Preprocessor,
+    Probability,
+    Recorder,
+    Solution,
+    SourceMap,
+    ToolchainProfile,
+    Tristate,
+    Value,
+};
 
 use std::env;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::io::{
+    self,
+    IsTerminal,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::{
+    self,
+    exit,
+};
+use std::time::{
+    SystemTime,
+    UNIX_EPOCH,
+};
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} /path/to/linux_kernel_source", args[0]);
-        return;
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+enum Command {
+    // Print the parsed tree, or what the options ask for
+    #[default]
+    Show,
+
+    // Bring a `.config` up to date, giving new symbols their defaults
+    OldDefConfig,
+
+    // The same, asking about new symbols on stdin
+    OldConfig,
+
+    // Write the minimal configuration that reproduces a `.config`
+    SaveDefConfig,
+
+    // allyesconfig, allnoconfig, allmodconfig and alldefconfig
+    All(Generator),
+    RandConfig,
+
+    // Combine `.config` fragments, as merge_config.sh does
+    MergeConfig,
+
+    // Report the symbols a `.config` has no value for
+    ListNewConfig,
+    HelpNewConfig,
+
+    // Compare two `.config` files symbol by symbol
+    DiffConfig,
+
+    // Show why a symbol has its value
+    Explain,
+
+    // Find what else has to be set for a symbol to get a value
+    Solve,
+
+    // Report entries of the tree that can never take effect
+    Lint,
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    command:          Command,
+    srctree:          Option<PathBuf>,
+    root:             Option<PathBuf>,
+    toolchain:        Option<PathBuf>,
+    record_toolchain: Option<PathBuf>,
+    config:           Option<PathBuf>,
+    write_config:     Option<PathBuf>,
+    allconfig:        Option<PathBuf>,
+    seed:             Option<String>,
+    probability:      Option<String>,
+    fragments:        Vec<PathBuf>,
+    json:             bool,
+    old:              Option<PathBuf>,
+    symbol:           Option<String>,
+    wanted:           Option<Tristate>,
+    depth:            Option<String>,
+    tree:             bool,
+    symbols:          bool,
+}
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {} [options] /path/to/linux_kernel_source [root Kconfig]", program);
+    eprintln!("       {} <command> [options] /path/to/linux_kernel_source [root Kconfig]", program);
+    eprintln!("       {} explain CONFIG_FOO [options] /path/to/linux_kernel_source [root Kconfig]", program);
+    eprintln!("       {} solve CONFIG_FOO[=y|m|n] [options] /path/to/linux_kernel_source [root Kconfig]", program);
+    eprintln!("       {} fmt [--check] <Kconfig file>...", program);
+    eprintln!();
+    eprintln!("Commands:");
+    eprintln!("  olddefconfig               update a configuration, new symbols take their defaults");
+    eprintln!("  oldconfig                  update a configuration, asking about new symbols");
+    eprintln!("  savedefconfig              write the minimal configuration to defconfig");
+    eprintln!("  allyesconfig, allnoconfig, allmodconfig, alldefconfig, randconfig");
+    eprintln!("                             write a new configuration with every answer the same, or random");
+    eprintln!("  mergeconfig                write a new configuration from the --fragment files, in order");
+    eprintln!("  listnewconfig              list the symbols the configuration has no value for yet");
+    eprintln!("  helpnewconfig              the same, with the help text of each");
+    eprintln!("  diffconfig                 show how the configuration differs from --old, and why");
+    eprintln!("  explain                    show why a symbol has its value, and why the symbols behind it do");
+    eprintln!("  solve                      show the fewest other values to set for a symbol to get a value");
+    eprintln!("  lint                       report undefined symbols, and dependencies, if blocks and prompts that can never hold");
+    eprintln!();
+    eprintln!("Options:");
+    eprintln!("  --toolchain <file>         answer $(shell,...) from a recorded toolchain profile");
+    eprintln!("  --record-toolchain <file>  run $(shell,...) on this machine and record the answers");
+    eprintln!("  --config <file>            read symbol values from a .config file");
+    eprintln!("  --write-config <file>      write the resulting configuration in .config format");
+    eprintln!("  --allconfig <file>         values to keep for all*config and randconfig");
+    eprintln!("  --seed <n>                 seed for randconfig");
+    eprintln!("  --probability <n[:n[:n]]>  chances of y and m for randconfig, in percent");
+    eprintln!("  --fragment <file>          a .config fragment for mergeconfig, can be given several times");
+    eprintln!("  --json                     print listnewconfig and helpnewconfig as JSON");
+    eprintln!("  --old <file>               the configuration diffconfig compares with, .config.old by default");
+    eprintln!("  --depth <n>                how many levels of symbols explain follows, 2 by default");
+    eprintln!("  --tree                     print the menu structure instead of the parsed entries");
+    eprintln!("  --symbols                  print every symbol with its merged definitions");
+    eprintln!();
+    eprintln!("Commands read and write $KCONFIG_CONFIG, or .config, unless --config and --write-config say");
+    eprintln!("otherwise, and keep the previous file with .old appended to its name. savedefconfig writes to");
+    eprintln!("defconfig instead. $KCONFIG_ALLCONFIG, $KCONFIG_SEED and $KCONFIG_PROBABILITY are used as");
+    eprintln!("with make when the matching options are not given.");
+    exit(2);
+}
+
+fn parse_args(args: &[String]) -> Options {
+    let mut opts = Options::default();
+    let mut iter = args.iter().skip(1).peekable();
+    opts.command = match iter.peek().map(|arg| arg.as_str()) {
+        Some("olddefconfig")  => Command::OldDefConfig,
+        Some("oldconfig")     => Command::OldConfig,
+        Some("savedefconfig") => Command::SaveDefConfig,
+        Some("allyesconfig")  => Command::All(Generator::AllYes),
+        Some("allnoconfig")   => Command::All(Generator::AllNo),
+        Some("allmodconfig")  => Command::All(Generator::AllMod),
+        Some("alldefconfig")  => Command::All(Generator::AllDef),
+        Some("randconfig")    => Command::RandConfig,
+        Some("mergeconfig")   => Command::MergeConfig,
+        Some("listnewconfig") => Command::ListNewConfig,
+        Some("helpnewconfig") => Command::HelpNewConfig,
+        Some("diffconfig")    => Command::DiffConfig,
+        Some("explain")       => Command::Explain,
+        Some("solve")         => Command::Solve,
+        Some("lint")          => Command::Lint,
+        _                     => Command::Show,
+    };
+    if opts.command != Command::Show {
+        iter.next();
+    }
+    if opts.command == Command::Explain {
+        match iter.next() {
+            Some(symbol) => opts.symbol = Some(symbol.strip_prefix("CONFIG_").unwrap_or(symbol).to_string()),
+            None         => usage(&args[0]),
+        }
     }
+    if opts.command == Command::Solve {
+        let Some(arg) = iter.next() else {
+            usage(&args[0]);
+        };
+        let (symbol, wanted) = arg.split_once('=').unwrap_or((arg, "y"));
+        opts.symbol = Some(symbol.strip_prefix("CONFIG_").unwrap_or(symbol).to_string());
+        opts.wanted = Some(Tristate::from_name(wanted).unwrap_or_else(|| usage(&args[0])));
+    }
+    while let Some(arg) = iter.next() {
+        let mut value = || match iter.next() {
+            Some(v) => v.clone(),
+            None    => usage(&args[0]),
+        };
+        match arg.as_str() {
+            "--toolchain"        => opts.toolchain = Some(PathBuf::from(value())),
+            "--record-toolchain" => opts.record_toolchain = Some(PathBuf::from(value())),
+            "--config"           => opts.config = Some(PathBuf::from(value())),
+            "--write-config"     => opts.write_config = Some(PathBuf::from(value())),
+            "--allconfig"        => opts.allconfig = Some(PathBuf::from(value())),
+            "--seed"             => opts.seed = Some(value()),
+            "--probability"      => opts.probability = Some(value()),
+            "--fragment"         => opts.fragments.push(PathBuf::from(value())),
+            "--json"             => opts.json = true,
+            "--old"              => opts.old = Some(PathBuf::from(value())),
+            "--depth"            => opts.depth = Some(value()),
+            "--tree"             => opts.tree = true,
+            "--symbols"          => opts.symbols = true,
+            _ if arg.starts_with("--") => usage(&args[0]),
+            _ if opts.srctree.is_none() => opts.srctree = Some(PathBuf::from(arg)),
+            _ if opts.root.is_none()    => opts.root = Some(PathBuf::from(arg)),
+            _ => usage(&args[0]),
+        }
+    }
+    opts
+}
 
-    let dir = &args[1];
-    let matches = search(&Path::new(dir));
+fn load_sources(opts: &Options, srctree: &Path) -> Result<SourceMap, String> {
+    let root = opts.root.as_deref().unwrap_or(Path::new("Kconfig"));
+    let env = env::vars().collect();
 
-    let mut opts: usize = 0;
-    for path in matches {
-        let content = load_from_file(path.display().to_string());
-        let config = take_kconfig(&content);
-        println!("{}", config);
+    if let Some(record) = &opts.record_toolchain {
+        let mut macros = Preprocessor::new(env, Recorder::new(HostShell));
+        let sources = SourceMap::load_with(srctree, root, &mut macros).map_err(|e| e.to_string())?;
+        macros.shell().profile.save(record).map_err(|e| format!("{}: {}", record.display(), e))?;
+        return Ok(sources);
+    }
+
+    match &opts.toolchain {
+        Some(path) => {
+            let profile = ToolchainProfile::load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            SourceMap::load_with(srctree, root, &mut Preprocessor::new(env, profile)).map_err(|e| e.to_string())
+        },
+        None => SourceMap::load_with(srctree, root, &mut Preprocessor::new(env, NoShell::default())).map_err(|e| e.to_string()),
+    }
+}
+
+// Rewrite each file in place, or with `--check` only list the ones that would change
+fn run_fmt(program: &str, args: &[String]) {
+    let check = args.iter().any(|a| a == "--check");
+    let files: Vec<&String> = args.iter().filter(|a| *a != "--check").collect();
+    if files.is_empty() || files.iter().any(|f| f.starts_with("--")) {
+        usage(program);
+    }
 
-        opts = opts + config.collect_options().len();
+    let mut failed = false;
+    let mut unformatted = 0;
+    for file in files {
+        let content = match fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                failed = true;
+                continue;
+            },
+        };
+        let formatted = format(&content);
+        if formatted == content {
+            continue;
+        }
+        if check {
+            println!("{}", file);
+            unformatted += 1;
+        } else if let Err(e) = fs::write(file, formatted) {
+            eprintln!("{}: {}", file, e);
+            failed = true;
+        }
+    }
+    if failed || unformatted > 0 {
+        exit(1);
     }
-    eprintln!("Total options found across all KConfigs in '{}': {}", dir, opts);
 }
 
-fn search(path: &Path) -> Vec<PathBuf> {
-    let mut result = Vec::new();
-
-    if let Ok(entries) = fs::read_dir(path) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.is_dir() {
-                    result.extend(search(&path));
-                } else if path.file_name().map_or(false, |f| f.to_string_lossy().starts_with("Kconfig")) { 
-					result.push(path.to_path_buf());
+// Roughly what the help screen of menuconfig shows for each symbol
+fn print_symbols(symbols: &SymbolTable, sources: &SourceMap) {
+    for info in symbols.iter() {
+        println!("Symbol: {}", info.name);
+        match info.option_type {
+            Some(option_type) => println!("Type  : {}", option_type),
+            None              => println!("Type  : unknown"),
+        }
+        for def in &info.definitions {
+            println!("Defined at {}", sources.location(&def.option.span));
+            if let Some(prompt) = &def.option.prompt {
+                println!("  Prompt: {}", prompt.text.as_str());
+            }
+            if let Some(dep) = &def.dep {
+                println!("  Depends on: {}", dep);
+            }
+            if !def.location.is_empty() {
+                println!("  Location:");
+                for (depth, menu) in def.location.iter().enumerate() {
+                    println!("  {}-> {}", "  ".repeat(depth + 1), menu);
                 }
             }
         }
+        println!();
     }
+}
 
-    result
+fn default_config() -> PathBuf {
+    env::var_os("KCONFIG_CONFIG").map_or_else(|| PathBuf::from(".config"), PathBuf::from)
 }
 
-fn load_from_file(path_string: String) -> String {
-    let pathed = std::path::Path::new(&path_string).to_path_buf();
-    match std::fs::read_to_string(pathed) {
-        Ok(content) => return content,
+// Keep the previous configuration next to the new one, as `make` does
+fn write_config(path: &Path, content: &str, backup: bool) -> io::Result<()> {
+    if backup && path.exists() {
+        let mut old = path.as_os_str().to_owned();
+        old.push(".old");
+        fs::rename(path, old)?;
+    }
+    fs::write(path, content)
+}
+
+// The seed and probabilities for randconfig, from the options or the environment. The seed is
+// printed so the same configuration can be made again.
+fn random(opts: &Options) -> Generator {
+    let seed = match opts.seed.clone().or_else(|| env::var("KCONFIG_SEED").ok()) {
+        Some(text) => {
+            let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None      => text.parse(),
+            };
+            parsed.unwrap_or_else(|_| {
+                eprintln!("invalid seed `{}`", text);
+                exit(2);
+            })
+        },
+        None => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            now.as_secs() ^ u64::from(now.subsec_nanos()) << 32 ^ u64::from(process::id())
+        },
+    };
+    eprintln!("KCONFIG_SEED=0x{:x}", seed);
+    let probability = match opts.probability.clone().or_else(|| env::var("KCONFIG_PROBABILITY").ok()) {
+        Some(text) => Probability::parse(&text).unwrap_or_else(|| {
+            eprintln!("invalid probability `{}`", text);
+            exit(2);
+        }),
+        None => Probability::default(),
+    };
+    Generator::Random { seed, probability }
+}
+
+// The file of preset values for a generator. `KCONFIG_ALLCONFIG` names it, or when set to an
+// empty string or `1` it is `all<mode>.config` with `all.config` as a fallback, as with `make`.
+fn allconfig(opts: &Options, generator: Generator) -> Option<PathBuf> {
+    if let Some(path) = &opts.allconfig {
+        return Some(path.clone());
+    }
+    let value = env::var_os("KCONFIG_ALLCONFIG")?;
+    if !value.is_empty() && value != "1" {
+        return Some(PathBuf::from(value));
+    }
+    let mode = match generator {
+        Generator::AllYes        => "yes",
+        Generator::AllNo         => "no",
+        Generator::AllMod        => "mod",
+        Generator::AllDef        => "def",
+        Generator::Random { .. } => "random",
+    };
+    let name = PathBuf::from(format!("all{}.config", mode));
+    if name.exists() {
+        return Some(name);
+    }
+    let fallback = PathBuf::from("all.config");
+    if !fallback.exists() {
+        eprintln!("KCONFIG_ALLCONFIG set, but no `{}` or `all.config` file found", name.display());
+        exit(1);
+    }
+    Some(fallback)
+}
+
+// A file of values to start from, which has to exist
+fn load_fragment(path: &Path, diagnostics: &mut Vec<Diagnostic>) -> DotConfig {
+    match DotConfig::load(path) {
+        Ok((dotconfig, found)) => {
+            diagnostics.extend(found);
+            dotconfig
+        },
         Err(e) => {
-            panic!("Failed to open '{}' with error '{}'", path_string, e);
-        }
+            eprintln!("{}: {}", path.display(), e);
+            exit(1);
+        },
     }
 }
 
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("fmt") {
+        run_fmt(&args[0], &args[2..]);
+        return;
+    }
+    let opts = parse_args(&args);
+    let Some(srctree) = opts.srctree.clone() else {
+        usage(&args[0]);
+    };
+
+    let sources = match load_sources(&opts, &srctree) {
+        Ok(sources) => sources,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        },
+    };
+    let (config, mut diagnostics) = sources.parse();
+    let tree = MenuTree::build(&config);
+    let symbols = SymbolTable::build(&tree);
+    diagnostics.extend(symbols.diagnostics(&sources));
+
+    let generator = match opts.command {
+        Command::All(generator) => Some(generator),
+        Command::RandConfig     => Some(random(&opts)),
+        _                       => None,
+    };
+    if opts.command == Command::MergeConfig && opts.fragments.is_empty() {
+        usage(&args[0]);
+    }
+    let update = opts.command != Command::Show;
+    // Generated and merged configurations start from scratch, not from the one they replace
+    let fresh = generator.is_some() || opts.command == Command::MergeConfig;
+    let config_path = match update && !fresh && opts.command != Command::Lint {
+        true  => Some(opts.config.clone().unwrap_or_else(default_config)),
+        false => opts.config.clone(),
+    };
+    let write_path = match opts.command {
+        Command::Show          => opts.write_config.clone(),
+        Command::SaveDefConfig => Some(opts.write_config.clone().unwrap_or_else(|| PathBuf::from("defconfig"))),
+        Command::ListNewConfig => None,
+        Command::HelpNewConfig => None,
+        Command::DiffConfig    => None,
+        Command::Explain       => None,
+        Command::Solve         => opts.write_config.clone(),
+        Command::Lint          => None,
+        _                      => opts.write_config.clone().or_else(|| Some(default_config())),
+    };
+
+    let mut engine = Engine::new(&tree, &symbols);
+    if let Some(path) = &config_path {
+        match DotConfig::load(path) {
+            Ok((dotconfig, found)) => {
+                diagnostics.extend(found);
+                diagnostics.extend(dotconfig.apply(&mut engine));
+            },
+            // Without a configuration to update everything starts out at its default
+            Err(e) if update && e.kind() == io::ErrorKind::NotFound && opts.command != Command::DiffConfig => {},
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                exit(1);
+            },
+        }
+    }
+
+    if let Some(generator) = generator {
+        if let Some(path) = allconfig(&opts, generator) {
+            let preset = load_fragment(&path, &mut diagnostics);
+            diagnostics.extend(preset.apply(&mut engine));
+        }
+        generate(&mut engine, generator);
+    }
+
+    if opts.command == Command::MergeConfig {
+        let fragments: Vec<DotConfig> = opts.fragments.iter()
+            .map(|path| load_fragment(path, &mut diagnostics))
+            .collect();
+        diagnostics.extend(merge(&mut engine, &fragments));
+    }
 
-fn take_kconfig(input: &str) -> kconfig::KConfig {
-    match kconfig::KConfig::parse(input) {
-        Ok((remaining, config)) => {
-            if remaining != "" {
-                panic!("SAMMAS ERROR Unprocessed input:```\n{}'\n```", remaining);
+    if opts.command == Command::OldConfig {
+        let stdin = io::stdin();
+        let echo = !stdin.is_terminal();
+        if let Err(e) = oldconfig(&mut engine, stdin.lock(), io::stdout().lock(), echo) {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+
+    let mut failed = false;
+    if let (Command::Explain, Some(symbol)) = (opts.command, &opts.symbol) {
+        let depth = match &opts.depth {
+            Some(depth) => depth.parse().unwrap_or_else(|_| usage(&args[0])),
+            None        => 2,
+        };
+        print!("{}", explain(&engine, symbol, depth));
+    } else if let (Command::Solve, Some(symbol), Some(wanted)) = (opts.command, &opts.symbol, opts.wanted) {
+        match solve(&mut engine, symbol, wanted) {
+            Solution::Reachable(changes) if changes.is_empty() => println!("CONFIG_{}={} already holds", symbol, wanted.name()),
+            Solution::Reachable(changes) => {
+                for (name, value) in changes {
+                    let option_type = symbols.get(name).and_then(|info| info.option_type).unwrap_or(OptionType::Tristate);
+                    println!("{}", assignment_line(name, option_type, &Value::Tristate(value)));
+                    engine.set(name, Value::Tristate(value));
+                }
+            },
+            Solution::Impossible(constraints) => {
+                println!("CONFIG_{}={} is not possible, these constraints conflict:", symbol, wanted.name());
+                for constraint in constraints {
+                    println!("  {}", constraint);
+                }
+                failed = true;
+            },
+            Solution::Unverified => {
+                println!("CONFIG_{}={} could not be reached with any assignment tried", symbol, wanted.name());
+                failed = true;
+            },
+        }
+        if let (Some(path), false) = (&write_path, failed) {
+            if let Err(e) = write_config(path, &write_dotconfig(&engine), write_path == config_path) {
+                eprintln!("{}: {}", path.display(), e);
+                exit(1);
             }
-            return config;
         }
-        Err(error) => {
-            panic!("SAMMAS ERROR Proper error:\n{:?}\n\n", error);
+    } else if opts.command == Command::Lint {
+        let mut found = dead_code(&config, &tree, &symbols, &sources);
+        found.extend(undefined_symbols(&config, &symbols, &sources));
+        found.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        failed = !found.is_empty();
+        diagnostics.extend(found);
+    } else if opts.command == Command::DiffConfig {
+        let path = opts.old.clone().unwrap_or_else(|| {
+            let mut old = config_path.clone().unwrap_or_else(default_config).into_os_string();
+            old.push(".old");
+            PathBuf::from(old)
+        });
+        let mut old = Engine::new(&tree, &symbols);
+        let dotconfig = load_fragment(&path, &mut diagnostics);
+        diagnostics.extend(dotconfig.apply(&mut old));
+        print!("{}", format_diff(&diff(&old, &engine)));
+    } else if matches!(opts.command, Command::ListNewConfig | Command::HelpNewConfig) {
+        let symbols = new_symbols(&engine);
+        match opts.command {
+            _ if opts.json         => print!("{}", new_json(&symbols)),
+            Command::HelpNewConfig => print!("{}", help_new(&symbols)),
+            _                      => print!("{}", list_new(&symbols)),
+        }
+    } else if let Some(path) = &write_path {
+        let (content, backup) = match opts.command {
+            Command::SaveDefConfig   => (write_defconfig(&engine), false),
+            _ if fresh               => (write_dotconfig(&engine), true),
+            _                        => (write_dotconfig(&engine), update && write_path == config_path),
+        };
+        if let Err(e) = write_config(path, &content, backup) {
+            eprintln!("{}: {}", path.display(), e);
+            exit(1);
+        }
+    } else if opts.tree {
+        print!("{}", tree);
+    } else if opts.symbols {
+        print_symbols(&symbols, &sources);
+    } else {
+        println!("{}", config);
+    }
+
+    let mut errors = 0;
+    for diagnostic in sources.load_diagnostics().iter().chain(diagnostics.iter()) {
+        eprintln!("{}", diagnostic);
+        if diagnostic.is_error() {
+            errors += 1;
         }
     }
+
+    eprintln!("Total options found across {} KConfigs in '{}': {} ({} distinct symbols)",
+        sources.files().count(), srctree.display(), config.collect_options().len(), symbols.len());
+    if errors > 0 {
+        eprintln!("{} error(s) found", errors);
+        exit(1);
+    }
+    if failed {
+        exit(1);
+    }
 }